@@ -0,0 +1,242 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Data, DeriveInput, Expr, ExprLit, Fields, Ident, Lit, LitBool, Path, Token,
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Implements `PrefabData` for a struct with named fields
+///
+/// - `#[prefab(construct = path::to::function)]` sets the construct function, called
+///   with `(&self, &mut World, Entity)`, by default nothing is done; `post_construct` and `on_despawn`
+///   hooks are set the same way
+/// - `#[prefab(source_prefab_required = false)]` sets what the `PrefabData::source_prefab_required()`
+///   method returns, `true` by default
+/// - `#[prefab(skip)]`, `#[prefab(range(min, max))]`, `#[prefab(one_of(a, b, ..))]`
+///   and `#[prefab(non_empty_handle)]` field attributes build the `OverrideRules`; `one_of` takes
+///   numbers, string literals or any value with reflected `PartialEq`, like `Mode::Steady`
+#[proc_macro_derive(PrefabData, attributes(prefab))]
+pub fn derive_prefab_data(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_prefab_data(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_prefab_data(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut construct = None;
+    let mut post_construct = None;
+    let mut on_despawn = None;
+    let mut source_prefab_required = true;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("prefab"))
+    {
+        let args =
+            attr.parse_args_with(Punctuated::<ContainerAttr, Token![,]>::parse_terminated)?;
+        for arg in args {
+            match arg {
+                ContainerAttr::Construct(path) => construct = Some(path),
+                ContainerAttr::PostConstruct(path) => post_construct = Some(path),
+                ContainerAttr::OnDespawn(path) => on_despawn = Some(path),
+                ContainerAttr::SourcePrefabRequired(value) => source_prefab_required = value.value,
+            }
+        }
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "`PrefabData` can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "`PrefabData` can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut rules = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let field_name = ident.to_string();
+
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("prefab"))
+        {
+            let args =
+                attr.parse_args_with(Punctuated::<FieldAttr, Token![,]>::parse_terminated)?;
+            for arg in args {
+                rules.push(match arg {
+                    FieldAttr::Skip => quote! { .skip(#field_name) },
+                    FieldAttr::Range(min, max) => quote! {
+                        .constrain(
+                            #field_name,
+                            ::bevy_prefab::data::FieldConstraint::range((#min) as f64, (#max) as f64),
+                        )
+                    },
+                    FieldAttr::OneOf(values) => {
+                        let values = values.iter().map(allowed_value);
+                        quote! {
+                            .constrain(
+                                #field_name,
+                                ::bevy_prefab::data::FieldConstraint::one_of(vec![#(#values),*]),
+                            )
+                        }
+                    }
+                    FieldAttr::NonEmptyHandle => quote! {
+                        .constrain(
+                            #field_name,
+                            ::bevy_prefab::data::FieldConstraint::NonEmptyHandle,
+                        )
+                    },
+                });
+            }
+        }
+    }
+
+    let construct = match construct {
+        Some(path) => quote! { #path(self, world, root) },
+        None => quote! {
+            let _ = world;
+            let _ = root;
+            Ok(())
+        },
+    };
+    let post_construct = post_construct.map(|path| hook(quote! { post_construct }, path));
+    let on_despawn = on_despawn.map(|path| hook(quote! { on_despawn }, path));
+
+    Ok(quote! {
+        impl #impl_generics ::bevy_prefab::data::PrefabData for #name #ty_generics #where_clause {
+            fn construct(
+                &self,
+                world: &mut ::bevy_prefab::__macro::World,
+                root: ::bevy_prefab::__macro::Entity,
+            ) -> ::bevy_prefab::__macro::Result<()> {
+                #construct
+            }
+
+            #post_construct
+            #on_despawn
+
+            fn source_prefab_required() -> bool {
+                #source_prefab_required
+            }
+
+            fn override_rules() -> ::bevy_prefab::data::OverrideRules {
+                ::bevy_prefab::data::OverrideRules::default()
+                    #(#rules)*
+            }
+        }
+    })
+}
+
+/// Boxes a `one_of` value, string literals become `String`s
+fn allowed_value(value: &Expr) -> TokenStream2 {
+    let value = match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(_), ..
+        }) => quote! { ::std::string::String::from(#value) },
+        _ => quote! { #value },
+    };
+    quote! {
+        ::std::boxed::Box::new(#value) as ::std::boxed::Box<dyn ::bevy_prefab::__macro::Reflect>
+    }
+}
+
+fn hook(name: TokenStream2, path: Path) -> TokenStream2 {
+    quote! {
+        fn #name(
+            &self,
+            world: &mut ::bevy_prefab::__macro::World,
+            root: ::bevy_prefab::__macro::Entity,
+        ) -> ::bevy_prefab::__macro::Result<()> {
+            #path(self, world, root)
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+enum ContainerAttr {
+    Construct(Path),
+    PostConstruct(Path),
+    OnDespawn(Path),
+    SourcePrefabRequired(LitBool),
+}
+
+impl Parse for ContainerAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        match ident.to_string().as_str() {
+            "construct" => Ok(ContainerAttr::Construct(input.parse()?)),
+            "post_construct" => Ok(ContainerAttr::PostConstruct(input.parse()?)),
+            "on_despawn" => Ok(ContainerAttr::OnDespawn(input.parse()?)),
+            "source_prefab_required" => Ok(ContainerAttr::SourcePrefabRequired(input.parse()?)),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "unknown prefab attribute, expected `construct`, `post_construct`, `on_despawn` or `source_prefab_required`",
+            )),
+        }
+    }
+}
+
+enum FieldAttr {
+    Skip,
+    Range(Box<Expr>, Box<Expr>),
+    OneOf(Vec<Expr>),
+    NonEmptyHandle,
+}
+
+impl Parse for FieldAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "skip" => Ok(FieldAttr::Skip),
+            "non_empty_handle" => Ok(FieldAttr::NonEmptyHandle),
+            "range" => {
+                let args = parse_args(input)?;
+                if args.len() != 2 {
+                    return Err(syn::Error::new(ident.span(), "expected `range(min, max)`"));
+                }
+                let mut args = args.into_iter();
+                Ok(FieldAttr::Range(
+                    Box::new(args.next().unwrap()),
+                    Box::new(args.next().unwrap()),
+                ))
+            }
+            "one_of" => Ok(FieldAttr::OneOf(parse_args(input)?)),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "unknown prefab field attribute, expected `skip`, `range(min, max)`, `one_of(..)` or `non_empty_handle`",
+            )),
+        }
+    }
+}
+
+fn parse_args(input: ParseStream) -> syn::Result<Vec<Expr>> {
+    let content;
+    parenthesized!(content in input);
+    let args = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?;
+    Ok(args.into_iter().collect())
+}
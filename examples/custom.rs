@@ -1,7 +1,5 @@
-use anyhow::Result;
 use bevy::{
     asset::AssetServerSettings,
-    ecs::entity::EntityMap,
     math::{
         curves::{Curve, CurveFixed},
         interpolation::utils::lerp_unclamped,
@@ -30,7 +28,7 @@ fn main() {
                 .with_primitives_prefabs()
                 .with_objects_prefabs(),
         )
-        .register_prefab::<BlinkingLightPrefab>(true)
+        .register_prefab::<BlinkingLightPrefab>()
         .add_startup_system(setup.system())
         .add_system(blinking_light_update.system())
         .run();
@@ -60,14 +58,18 @@ impl Default for BlinkingLightLocal {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Reflect, TypeUuid)]
+#[derive(Debug, Clone, Serialize, Deserialize, Reflect, TypeUuid, PrefabData)]
 #[serde(default)]
 #[uuid = "0833291b-ecc0-4fff-ae45-42ee8698dd43"]
+#[prefab(post_construct = blinking_light_post_construct)]
 struct BlinkingLightPrefab {
     //pub color: Color,
     pub min: f32,
     pub max: f32,
+    #[prefab(range(0.0, 10.0))]
     pub speed: f32,
+    // assigned by code, instances shouldn't override it
+    #[prefab(skip)]
     light_entity: Entity,
 }
 
@@ -83,18 +85,17 @@ impl Default for BlinkingLightPrefab {
     }
 }
 
-impl PrefabData for BlinkingLightPrefab {
-    fn construct(&self, world: &mut World, root_entity: Entity) -> Result<()> {
-        let _ = world;
-        let _ = root_entity;
-        // TODO: point light should be available for this function but it isn't
-        Ok(())
-    }
-
-    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<()> {
-        self.light_entity = entity_map.get(self.light_entity)?;
-        Ok(())
-    }
+/// Point light is a nested prefab, so it's only available after `construct`
+fn blinking_light_post_construct(
+    blinking_light: &BlinkingLightPrefab,
+    world: &mut World,
+    _: Entity,
+) -> anyhow::Result<()> {
+    let mut point_light = world
+        .get_mut::<PointLight>(blinking_light.light_entity)
+        .ok_or_else(|| anyhow::anyhow!("blinking light without a point light"))?;
+    point_light.range = blinking_light.min;
+    Ok(())
 }
 
 fn blinking_light_update(
@@ -1,4 +1,4 @@
-use std::any::type_name;
+use std::{any::type_name, fs, path::Path};
 
 use bevy::{
     ecs::{component::Component, entity::MapEntities},
@@ -6,15 +6,23 @@ use bevy::{
     reflect::TypeUuid,
     render::render_graph::base::MainPass,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    data::{BlankPrefab, BoxedPrefabOverrides},
-    manager::{prefab_commit_startup_system, prefab_managing_system},
+    data::{
+        BlankPrefab, BoxedPrefabOverrides, DynamicComponentType, DynamicPrefab, DynamicPrefabType,
+        PrefabComponentOverrides, PrefabOverrideLayers,
+    },
+    manager::{
+        prefab_commit_registries, prefab_commit_startup_system, prefab_managing_system,
+        prefab_register_reflected_components_system,
+    },
+    path::PrefabEntityProxy,
     prelude::BoxedPrefabData,
     registry::{
         shorten_name, ComponentDescriptorRegistry, ComponentEntityMapperRegistry,
-        PrefabDescriptorRegistry,
+        ComponentMergePolicy, PrefabDescriptorRegistry, PrefabMigrationFn,
+        ResourceDescriptorRegistry, ResourceDespawnPolicy,
     },
     Prefab, PrefabConstruct, PrefabData, PrefabNotInstantiatedTag, PrefabTransformOverride,
     PrefabTypeUuid,
@@ -25,6 +33,7 @@ use crate::{
 pub struct PrefabPlugin {
     primitives_prefabs: bool,
     objects_prefabs: bool,
+    reflected_components: bool,
 }
 
 impl PrefabPlugin {
@@ -33,6 +42,7 @@ impl PrefabPlugin {
         Self {
             primitives_prefabs: true,
             objects_prefabs: true,
+            ..self
         }
     }
 
@@ -48,6 +58,14 @@ impl PrefabPlugin {
         self
     }
 
+    /// Every component in bevy's type registry with `#[reflect(Component, Deserialize)]` can be used
+    /// in prefabs without being registered again; Components registered this way can't be patched
+    /// or removed by prefab instances, register them explicitly when that's needed
+    pub fn with_reflected_components(mut self) -> Self {
+        self.reflected_components = true;
+        self
+    }
+
     fn register_prefab_internal_components(&self, app_builder: &mut AppBuilder) {
         let mut component_registry = app_builder
             .app
@@ -59,6 +77,14 @@ impl PrefabPlugin {
             .register_private::<BoxedPrefabOverrides>("BoxedPrefabOverrides".to_string())
             .unwrap();
 
+        component_registry
+            .register_private::<PrefabOverrideLayers>("PrefabOverrideLayers".to_string())
+            .unwrap();
+
+        component_registry
+            .register_private::<PrefabComponentOverrides>("PrefabComponentOverrides".to_string())
+            .unwrap();
+
         component_registry
             .register_private::<Handle<Prefab>>("Handle<Prefab>".to_string())
             .unwrap();
@@ -79,6 +105,14 @@ impl PrefabPlugin {
             .register_private::<PrefabTypeUuid>("PrefabTypeUuid".to_string())
             .unwrap();
 
+        component_registry
+            .register_private::<PrefabEntityProxy>("PrefabEntityProxy".to_string())
+            .unwrap();
+
+        component_registry
+            .register_prefab_data::<DynamicPrefab>("DynamicPrefab".to_string())
+            .unwrap();
+
         let mut component_entity_mapper = app_builder
             .app
             .world
@@ -86,6 +120,8 @@ impl PrefabPlugin {
             .unwrap();
 
         component_entity_mapper.register::<BoxedPrefabOverrides>();
+        component_entity_mapper.register::<PrefabOverrideLayers>();
+        component_entity_mapper.register::<PrefabComponentOverrides>();
     }
 }
 
@@ -107,6 +143,8 @@ impl Plugin for PrefabPlugin {
                 data: BoxedPrefabData(Box::new(BlankPrefab)),
                 transform: Transform::default(),
                 world: World::default(),
+                source_to_prefab: Default::default(),
+                resources: vec![],
             },
         );
 
@@ -114,7 +152,8 @@ impl Plugin for PrefabPlugin {
         app_builder
             .insert_resource(PrefabDescriptorRegistry::default())
             .insert_resource(ComponentDescriptorRegistry::default())
-            .insert_resource(ComponentEntityMapperRegistry::default());
+            .insert_resource(ComponentEntityMapperRegistry::default())
+            .insert_resource(ResourceDescriptorRegistry::default());
 
         // add prefab manager system
         app_builder
@@ -133,10 +172,10 @@ impl Plugin for PrefabPlugin {
             .register_prefab_component::<Transform>()
             .register_prefab_component::<MainPass>()
             .register_prefab_component::<Draw>()
-            .register_prefab_component::<Visible>()
+            .register_prefab_patchable_component::<Visible>()
             .register_prefab_component::<RenderPipelines>()
-            .register_prefab_component::<PointLight>()
-            .register_prefab_component::<DirectionalLight>()
+            .register_prefab_patchable_component::<PointLight>()
+            .register_prefab_patchable_component::<DirectionalLight>()
             .register_prefab_component_aliased::<Handle<Mesh>>("Mesh".to_string())
             .register_prefab_component::<Handle<StandardMaterial>>();
 
@@ -150,6 +189,14 @@ impl Plugin for PrefabPlugin {
         if self.objects_prefabs {
             crate::builtin::objects::register_objects_prefabs(app_builder);
         }
+
+        if self.reflected_components {
+            // types are registered by other plugins, some are only added after this one
+            app_builder.add_startup_system_to_stage(
+                StartupStage::PreStartup,
+                prefab_register_reflected_components_system.exclusive_system(),
+            );
+        }
     }
 }
 
@@ -161,13 +208,51 @@ pub trait PrefabAppBuilder: Sized {
         self.register_prefab_mappable_component_aliased::<C>(shorten_name(type_name::<C>()))
     }
 
+    /// Register a component, entities inside it are found through [`Reflect`] and mapped to the
+    /// instance space; See [`PrefabAppBuilder::register_prefab_mappable_component`] to use it's own
+    /// [`MapEntities`] instead
     fn register_prefab_component<C>(self) -> Self
     where
-        C: Component + Clone + for<'de> Deserialize<'de> + 'static,
+        C: Component + Clone + Reflect + for<'de> Deserialize<'de> + 'static,
     {
         self.register_prefab_component_aliased::<C>(shorten_name(type_name::<C>()))
     }
 
+    /// Register a struct component prefab instances can partially override, only the fields
+    /// written are changed, e.g. `PointLight(( intensity: 500 ))`
+    fn register_prefab_patchable_component<C>(self) -> Self
+    where
+        C: Component + Clone + Reflect + Default + for<'de> Deserialize<'de> + 'static,
+    {
+        self.register_prefab_patchable_component_aliased::<C>(shorten_name(type_name::<C>()))
+    }
+
+    /// Register a component with a custom [`ComponentMergePolicy`], components registered with
+    /// [`ComponentMergePolicy::Reflect`] are also patchable
+    fn register_prefab_component_with_merge_policy<C>(
+        self,
+        merge_policy: ComponentMergePolicy,
+    ) -> Self
+    where
+        C: Component + Clone + Reflect + Default + for<'de> Deserialize<'de> + 'static,
+    {
+        self.register_prefab_component_aliased_with_merge_policy::<C>(
+            shorten_name(type_name::<C>()),
+            merge_policy,
+        )
+    }
+
+    /// Register a component deserialized through the `P` proxy type, for components that can't be
+    /// deserialized (like third-party types) or have an awkward shape on disk;
+    /// See [`PrefabAppBuilder::register_prefab_component_serialize_from`] to also serialize it
+    fn register_prefab_component_from<P, C>(self) -> Self
+    where
+        C: Component + Clone,
+        P: for<'de> Deserialize<'de> + Into<C> + 'static,
+    {
+        self.register_prefab_component_from_aliased::<P, C>(shorten_name(type_name::<C>()))
+    }
+
     fn register_prefab_component_non_serializable<C>(self) -> Self
     where
         C: Component + Default + Clone,
@@ -175,11 +260,76 @@ pub trait PrefabAppBuilder: Sized {
         self.register_prefab_component_aliased_non_serializable::<C>(shorten_name(type_name::<C>()))
     }
 
-    fn register_prefab<P>(self, source_prefab_required: bool) -> Self
+    /// Register a non serializable component built by the `constructor` for every prefab instance,
+    /// e.g. to give each instance their own material handle
+    fn register_prefab_component_with_constructor<C, F>(self, constructor: F) -> Self
+    where
+        C: Component,
+        F: Fn(&mut World, Entity) -> C + Send + Sync + 'static,
+    {
+        self.register_prefab_component_aliased_with_constructor::<C, F>(
+            shorten_name(type_name::<C>()),
+            constructor,
+        )
+    }
+
+    /// Register a resource prefabs can declare in their `resources` list, it's inserted
+    /// when the prefab instance spawns and kept after the instance is despawned
+    fn register_prefab_resource<R>(self) -> Self
+    where
+        R: Component + Clone + for<'de> Deserialize<'de>,
+    {
+        self.register_prefab_resource_with_despawn_policy::<R>(ResourceDespawnPolicy::Keep)
+    }
+
+    /// Register a prefab resource with a custom [`ResourceDespawnPolicy`], e.g. to restore the
+    /// ambient light a level prefab replaced once it's despawned
+    fn register_prefab_resource_with_despawn_policy<R>(
+        self,
+        despawn_policy: ResourceDespawnPolicy,
+    ) -> Self
+    where
+        R: Component + Clone + for<'de> Deserialize<'de>,
+    {
+        self.register_prefab_resource_aliased_with_despawn_policy::<R>(
+            shorten_name(type_name::<R>()),
+            despawn_policy,
+        )
+    }
+
+    fn register_prefab<P>(self) -> Self
     where
         P: PrefabData + TypeUuid + Default + Struct + Clone + for<'de> Deserialize<'de>,
     {
-        self.register_prefab_aliased::<P>(shorten_name(type_name::<P>()), source_prefab_required)
+        self.register_prefab_aliased::<P>(shorten_name(type_name::<P>()))
+    }
+
+    /// Registers every prefab type listed in the file, see [`DynamicPrefabType::from_ron`]
+    fn register_dynamic_prefabs_from_file(self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let prefab_types = fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|source| DynamicPrefabType::from_ron(&source))
+            .unwrap_or_else(|err| panic!("`{}` couldn't be read: {}", path.display(), err));
+
+        prefab_types.into_iter().fold(self, |builder, prefab_type| {
+            builder.register_dynamic_prefab(prefab_type)
+        })
+    }
+
+    /// Registers every component type listed in the file, see [`DynamicComponentType::from_ron`]
+    fn register_dynamic_components_from_file(self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let component_types = fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|source| DynamicComponentType::from_ron(&source))
+            .unwrap_or_else(|err| panic!("`{}` couldn't be read: {}", path.display(), err));
+
+        component_types
+            .into_iter()
+            .fold(self, |builder, component_type| {
+                builder.register_dynamic_component(component_type)
+            })
     }
 
     fn register_prefab_mappable_component_aliased<C>(self, alias: String) -> Self
@@ -188,15 +338,85 @@ pub trait PrefabAppBuilder: Sized {
 
     fn register_prefab_component_aliased<C>(self, alias: String) -> Self
     where
-        C: Component + Clone + for<'de> Deserialize<'de>;
+        C: Component + Clone + Reflect + for<'de> Deserialize<'de>;
+
+    fn register_prefab_patchable_component_aliased<C>(self, alias: String) -> Self
+    where
+        C: Component + Clone + Reflect + Default + for<'de> Deserialize<'de>;
+
+    fn register_prefab_component_aliased_with_merge_policy<C>(
+        self,
+        alias: String,
+        merge_policy: ComponentMergePolicy,
+    ) -> Self
+    where
+        C: Component + Clone + Reflect + Default + for<'de> Deserialize<'de>;
+
+    fn register_prefab_component_from_aliased<P, C>(self, alias: String) -> Self
+    where
+        C: Component + Clone,
+        P: for<'de> Deserialize<'de> + Into<C> + 'static;
 
     fn register_prefab_component_aliased_non_serializable<C>(self, alias: String) -> Self
     where
         C: Component + Default + Clone;
 
-    fn register_prefab_aliased<P>(self, alias: String, source_prefab_required: bool) -> Self
+    fn register_prefab_component_aliased_with_constructor<C, F>(
+        self,
+        alias: String,
+        constructor: F,
+    ) -> Self
+    where
+        C: Component,
+        F: Fn(&mut World, Entity) -> C + Send + Sync + 'static;
+
+    fn register_prefab_resource_aliased_with_despawn_policy<R>(
+        self,
+        alias: String,
+        despawn_policy: ResourceDespawnPolicy,
+    ) -> Self
+    where
+        R: Component + Clone + for<'de> Deserialize<'de>;
+
+    fn register_prefab_aliased<P>(self, alias: String) -> Self
     where
         P: PrefabData + TypeUuid + Default + Struct + Clone + for<'de> Deserialize<'de>;
+
+    /// Keeps an old name of the already registered component `C` working, files using it
+    /// log a warning with the current name and can be fixed with [`crate::rename`]
+    fn register_prefab_component_deprecated_alias<C>(self, alias: String) -> Self
+    where
+        C: Component;
+
+    /// Serializes the already registered component `C` through the `P` proxy type,
+    /// so it can be written back to prefab files
+    fn register_prefab_component_serialize_from<P, C>(self) -> Self
+    where
+        C: Component,
+        P: Serialize + for<'a> From<&'a C> + 'static;
+
+    /// Keeps an old name of the already registered prefab `P` working, see
+    /// [`PrefabAppBuilder::register_prefab_component_deprecated_alias`]
+    fn register_prefab_deprecated_alias<P>(self, alias: String) -> Self
+    where
+        P: PrefabData;
+
+    /// Upgrades the data of the already registered prefab `P` saved before `version`, the highest
+    /// version registered is the current one; Files can be rewritten with [`crate::upgrade`]
+    ///
+    /// **NOTE** Versions are shared by every prefab type and must go up with each registration,
+    /// plugins registering migrations have to be added in the order of their versions
+    fn register_prefab_migration<P>(self, version: u32, migrate: PrefabMigrationFn) -> Self
+    where
+        P: PrefabData + Serialize;
+
+    /// Registers a prefab type declared in data, instances can override it's fields just like
+    /// the ones of Rust prefab types
+    fn register_dynamic_prefab(self, prefab_type: DynamicPrefabType) -> Self;
+
+    /// Registers a component declared in data, it's found by name in the entity
+    /// [`PrefabDynamicComponents`](crate::data::PrefabDynamicComponents)
+    fn register_dynamic_component(self, component_type: DynamicComponentType) -> Self;
 }
 
 impl PrefabAppBuilder for &mut AppBuilder {
@@ -204,26 +424,182 @@ impl PrefabAppBuilder for &mut AppBuilder {
     where
         C: Component + MapEntities + Clone + for<'de> Deserialize<'de>,
     {
-        let builder = self.register_prefab_component_aliased::<C>(alias);
+        self.app
+            .world
+            .register_prefab_mappable_component_aliased::<C>(alias);
+        self
+    }
 
-        let mut component_entity_mapper_registry = builder
-            .app
+    fn register_prefab_component_aliased<C>(self, alias: String) -> Self
+    where
+        C: Component + Clone + Reflect + for<'de> Deserialize<'de>,
+    {
+        self.app.world.register_prefab_component_aliased::<C>(alias);
+        self
+    }
+
+    fn register_prefab_patchable_component_aliased<C>(self, alias: String) -> Self
+    where
+        C: Component + Clone + Reflect + Default + for<'de> Deserialize<'de>,
+    {
+        self.app
+            .world
+            .register_prefab_patchable_component_aliased::<C>(alias);
+        self
+    }
+
+    fn register_prefab_component_aliased_with_merge_policy<C>(
+        self,
+        alias: String,
+        merge_policy: ComponentMergePolicy,
+    ) -> Self
+    where
+        C: Component + Clone + Reflect + Default + for<'de> Deserialize<'de>,
+    {
+        self.app
+            .world
+            .register_prefab_component_aliased_with_merge_policy::<C>(alias, merge_policy);
+        self
+    }
+
+    fn register_prefab_component_from_aliased<P, C>(self, alias: String) -> Self
+    where
+        C: Component + Clone,
+        P: for<'de> Deserialize<'de> + Into<C> + 'static,
+    {
+        self.app
+            .world
+            .register_prefab_component_from_aliased::<P, C>(alias);
+        self
+    }
+
+    fn register_prefab_component_aliased_non_serializable<C>(self, alias: String) -> Self
+    where
+        C: Component + Default + Clone,
+    {
+        self.app
+            .world
+            .register_prefab_component_aliased_non_serializable::<C>(alias);
+        self
+    }
+
+    fn register_prefab_component_aliased_with_constructor<C, F>(
+        self,
+        alias: String,
+        constructor: F,
+    ) -> Self
+    where
+        C: Component,
+        F: Fn(&mut World, Entity) -> C + Send + Sync + 'static,
+    {
+        self.app
+            .world
+            .register_prefab_component_aliased_with_constructor::<C, F>(alias, constructor);
+        self
+    }
+
+    fn register_prefab_resource_aliased_with_despawn_policy<R>(
+        self,
+        alias: String,
+        despawn_policy: ResourceDespawnPolicy,
+    ) -> Self
+    where
+        R: Component + Clone + for<'de> Deserialize<'de>,
+    {
+        self.app
+            .world
+            .register_prefab_resource_aliased_with_despawn_policy::<R>(alias, despawn_policy);
+        self
+    }
+
+    fn register_prefab_aliased<P>(self, alias: String) -> Self
+    where
+        P: PrefabData + TypeUuid + Default + Struct + Clone + for<'de> Deserialize<'de>,
+    {
+        self.app.world.register_prefab_aliased::<P>(alias);
+        self
+    }
+
+    fn register_prefab_component_deprecated_alias<C>(self, alias: String) -> Self
+    where
+        C: Component,
+    {
+        self.app
+            .world
+            .register_prefab_component_deprecated_alias::<C>(alias);
+        self
+    }
+
+    fn register_prefab_component_serialize_from<P, C>(self) -> Self
+    where
+        C: Component,
+        P: Serialize + for<'a> From<&'a C> + 'static,
+    {
+        self.app
+            .world
+            .register_prefab_component_serialize_from::<P, C>();
+        self
+    }
+
+    fn register_prefab_deprecated_alias<P>(self, alias: String) -> Self
+    where
+        P: PrefabData,
+    {
+        self.app.world.register_prefab_deprecated_alias::<P>(alias);
+        self
+    }
+
+    fn register_prefab_migration<P>(self, version: u32, migrate: PrefabMigrationFn) -> Self
+    where
+        P: PrefabData + Serialize,
+    {
+        self.app
             .world
+            .register_prefab_migration::<P>(version, migrate);
+        self
+    }
+
+    fn register_dynamic_prefab(self, prefab_type: DynamicPrefabType) -> Self {
+        self.app.world.register_dynamic_prefab(prefab_type);
+        self
+    }
+
+    fn register_dynamic_component(self, component_type: DynamicComponentType) -> Self {
+        self.app.world.register_dynamic_component(component_type);
+        self
+    }
+}
+
+/// Registrations made after startup are published right away, prefabs that failed to load
+/// because of a name unknown at the time are loaded again
+impl PrefabAppBuilder for &mut World {
+    fn register_prefab_mappable_component_aliased<C>(self, alias: String) -> Self
+    where
+        C: Component + MapEntities + Clone + for<'de> Deserialize<'de>,
+    {
+        let mut component_registry = self
+            .get_resource_mut::<ComponentDescriptorRegistry>()
+            .unwrap();
+
+        component_registry
+            .register::<C>(alias)
+            .expect("prefab component couldn't be registered");
+
+        let mut component_entity_mapper_registry = self
             .get_resource_mut::<ComponentEntityMapperRegistry>()
             .unwrap();
 
         component_entity_mapper_registry.register::<C>();
 
-        builder
+        prefab_commit_registries(self);
+        self
     }
 
     fn register_prefab_component_aliased<C>(self, alias: String) -> Self
     where
-        C: Component + Clone + for<'de> Deserialize<'de>,
+        C: Component + Clone + Reflect + for<'de> Deserialize<'de>,
     {
         let mut component_registry = self
-            .app
-            .world
             .get_resource_mut::<ComponentDescriptorRegistry>()
             .unwrap();
 
@@ -231,6 +607,89 @@ impl PrefabAppBuilder for &mut AppBuilder {
             .register::<C>(alias)
             .expect("prefab component couldn't be registered");
 
+        // entities are found through reflection unless the component has it's own `MapEntities`
+        self.get_resource_mut::<ComponentEntityMapperRegistry>()
+            .unwrap()
+            .register_reflect::<C>();
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_prefab_patchable_component_aliased<C>(self, alias: String) -> Self
+    where
+        C: Component + Clone + Reflect + Default + for<'de> Deserialize<'de>,
+    {
+        self.resource_scope(
+            |world, mut prefab_registry: Mut<PrefabDescriptorRegistry>| {
+                let mut component_registry = world
+                    .get_resource_mut::<ComponentDescriptorRegistry>()
+                    .unwrap();
+
+                component_registry
+                    .register_patchable::<C>(alias, &mut prefab_registry.overrides)
+                    .expect("prefab component couldn't be registered");
+
+                // entities are found through reflection unless the component has it's own `MapEntities`
+                world
+                    .get_resource_mut::<ComponentEntityMapperRegistry>()
+                    .unwrap()
+                    .register_reflect_checked::<C>();
+            },
+        );
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_prefab_component_aliased_with_merge_policy<C>(
+        self,
+        alias: String,
+        merge_policy: ComponentMergePolicy,
+    ) -> Self
+    where
+        C: Component + Clone + Reflect + Default + for<'de> Deserialize<'de>,
+    {
+        self.resource_scope(
+            |world, mut prefab_registry: Mut<PrefabDescriptorRegistry>| {
+                let mut component_registry = world
+                    .get_resource_mut::<ComponentDescriptorRegistry>()
+                    .unwrap();
+
+                component_registry
+                    .register_with_merge_policy::<C>(
+                        alias,
+                        merge_policy,
+                        &mut prefab_registry.overrides,
+                    )
+                    .expect("prefab component couldn't be registered");
+
+                // entities are found through reflection unless the component has it's own `MapEntities`
+                world
+                    .get_resource_mut::<ComponentEntityMapperRegistry>()
+                    .unwrap()
+                    .register_reflect_checked::<C>();
+            },
+        );
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_prefab_component_from_aliased<P, C>(self, alias: String) -> Self
+    where
+        C: Component + Clone,
+        P: for<'de> Deserialize<'de> + Into<C> + 'static,
+    {
+        let mut component_registry = self
+            .get_resource_mut::<ComponentDescriptorRegistry>()
+            .unwrap();
+
+        component_registry
+            .register_from::<P, C>(alias)
+            .expect("prefab component couldn't be registered");
+
+        prefab_commit_registries(self);
         self
     }
 
@@ -239,8 +698,6 @@ impl PrefabAppBuilder for &mut AppBuilder {
         C: Component + Default + Clone,
     {
         let mut component_registry = self
-            .app
-            .world
             .get_resource_mut::<ComponentDescriptorRegistry>()
             .unwrap();
 
@@ -248,26 +705,62 @@ impl PrefabAppBuilder for &mut AppBuilder {
             .register_non_serializable::<C>(alias)
             .expect("prefab component couldn't be registered");
 
+        prefab_commit_registries(self);
         self
     }
 
-    fn register_prefab_aliased<P>(self, alias: String, source_prefab_required: bool) -> Self
+    fn register_prefab_component_aliased_with_constructor<C, F>(
+        self,
+        alias: String,
+        constructor: F,
+    ) -> Self
     where
-        P: PrefabData + TypeUuid + Default + Struct + Clone + for<'de> Deserialize<'de>,
+        C: Component,
+        F: Fn(&mut World, Entity) -> C + Send + Sync + 'static,
     {
-        let mut prefab_registry = self
-            .app
-            .world
-            .get_resource_mut::<PrefabDescriptorRegistry>()
+        let mut component_registry = self
+            .get_resource_mut::<ComponentDescriptorRegistry>()
             .unwrap();
 
+        component_registry
+            .register_with_constructor::<C, F>(alias, constructor)
+            .expect("prefab component couldn't be registered");
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_prefab_resource_aliased_with_despawn_policy<R>(
+        self,
+        alias: String,
+        despawn_policy: ResourceDespawnPolicy,
+    ) -> Self
+    where
+        R: Component + Clone + for<'de> Deserialize<'de>,
+    {
+        let mut resource_registry = self
+            .get_resource_mut::<ResourceDescriptorRegistry>()
+            .unwrap();
+
+        resource_registry
+            .register::<R>(alias, despawn_policy)
+            .expect("prefab resource couldn't be registered");
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_prefab_aliased<P>(self, alias: String) -> Self
+    where
+        P: PrefabData + TypeUuid + Default + Struct + Clone + for<'de> Deserialize<'de>,
+    {
+        let mut prefab_registry = self.get_resource_mut::<PrefabDescriptorRegistry>().unwrap();
+
         prefab_registry
-            .register_aliased::<P>(alias.clone(), source_prefab_required)
+            .register_aliased::<P>(alias.clone())
             .expect("prefab couldn't be registered");
 
         let mut component_registry = self
-            .app
-            .world
             .get_resource_mut::<ComponentDescriptorRegistry>()
             .unwrap();
 
@@ -275,6 +768,94 @@ impl PrefabAppBuilder for &mut AppBuilder {
             .register_prefab_data::<P>(alias)
             .expect("prefab data component couldn't be registered");
 
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_prefab_component_deprecated_alias<C>(self, alias: String) -> Self
+    where
+        C: Component,
+    {
+        let mut component_registry = self
+            .get_resource_mut::<ComponentDescriptorRegistry>()
+            .unwrap();
+
+        component_registry
+            .register_deprecated_alias::<C>(alias)
+            .expect("prefab component alias couldn't be registered");
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_prefab_component_serialize_from<P, C>(self) -> Self
+    where
+        C: Component,
+        P: Serialize + for<'a> From<&'a C> + 'static,
+    {
+        let mut component_registry = self
+            .get_resource_mut::<ComponentDescriptorRegistry>()
+            .unwrap();
+
+        component_registry
+            .register_serialize_from::<P, C>()
+            .expect("prefab component serializer couldn't be registered");
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_prefab_deprecated_alias<P>(self, alias: String) -> Self
+    where
+        P: PrefabData,
+    {
+        let mut prefab_registry = self.get_resource_mut::<PrefabDescriptorRegistry>().unwrap();
+
+        prefab_registry
+            .register_deprecated_alias::<P>(alias)
+            .expect("prefab alias couldn't be registered");
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_prefab_migration<P>(self, version: u32, migrate: PrefabMigrationFn) -> Self
+    where
+        P: PrefabData + Serialize,
+    {
+        let mut prefab_registry = self.get_resource_mut::<PrefabDescriptorRegistry>().unwrap();
+
+        prefab_registry
+            .register_migration::<P>(version, migrate)
+            .expect("prefab migration couldn't be registered");
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_dynamic_prefab(self, prefab_type: DynamicPrefabType) -> Self {
+        let mut prefab_registry = self.get_resource_mut::<PrefabDescriptorRegistry>().unwrap();
+
+        prefab_registry
+            .register_dynamic(prefab_type)
+            .expect("dynamic prefab couldn't be registered");
+
+        prefab_commit_registries(self);
+        self
+    }
+
+    fn register_dynamic_component(self, component_type: DynamicComponentType) -> Self {
+        self.resource_scope(
+            |world, mut prefab_registry: Mut<PrefabDescriptorRegistry>| {
+                world
+                    .get_resource_mut::<ComponentDescriptorRegistry>()
+                    .unwrap()
+                    .register_dynamic(component_type, &mut prefab_registry.overrides)
+                    .expect("dynamic component couldn't be registered");
+            },
+        );
+
+        prefab_commit_registries(self);
         self
     }
 }
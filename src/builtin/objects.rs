@@ -26,6 +26,10 @@ impl PrefabData for StaticMeshPrefab {
 
         Ok(())
     }
+
+    fn source_prefab_required() -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Reflect, TypeUuid)]
@@ -62,6 +66,10 @@ impl PrefabData for PointLightPrefab {
 
         Ok(())
     }
+
+    fn source_prefab_required() -> bool {
+        false
+    }
 }
 
 // TODO: DirectionalLightPrefab
@@ -71,6 +79,6 @@ impl PrefabData for PointLightPrefab {
 ///////////////////////////////////////////////////////////////////////////////
 
 pub fn register_objects_prefabs(app_builder: &mut AppBuilder) {
-    app_builder.register_prefab::<StaticMeshPrefab>(false);
-    app_builder.register_prefab::<PointLightPrefab>(false);
+    app_builder.register_prefab::<StaticMeshPrefab>();
+    app_builder.register_prefab::<PointLightPrefab>();
 }
@@ -75,6 +75,10 @@ impl PrefabData for CubePrefab {
     fn construct(&self, world: &mut World, root: Entity) -> anyhow::Result<()> {
         common_construct(world, root, |primitives| primitives.cube.clone())
     }
+
+    fn source_prefab_required() -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, Reflect, TypeUuid)]
@@ -85,6 +89,10 @@ impl PrefabData for UVSpherePrefab {
     fn construct(&self, world: &mut World, root: Entity) -> anyhow::Result<()> {
         common_construct(world, root, |primitives| primitives.uv_sphere.clone())
     }
+
+    fn source_prefab_required() -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, Reflect, TypeUuid)]
@@ -95,6 +103,10 @@ impl PrefabData for PlanePrefab {
     fn construct(&self, world: &mut World, root: Entity) -> anyhow::Result<()> {
         common_construct(world, root, |primitives| primitives.plane.clone())
     }
+
+    fn source_prefab_required() -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, Reflect, TypeUuid)]
@@ -105,6 +117,10 @@ impl PrefabData for CapsulePrefab {
     fn construct(&self, world: &mut World, root: Entity) -> anyhow::Result<()> {
         common_construct(world, root, |primitives| primitives.capsule.clone())
     }
+
+    fn source_prefab_required() -> bool {
+        false
+    }
 }
 
 // TODO: CylinderPrefab
@@ -116,8 +132,8 @@ pub fn register_primitives_prefabs(app_builder: &mut AppBuilder) {
     let primitives = Primitives::from_world(&mut app_builder.app.world);
     app_builder
         .insert_resource(primitives)
-        .register_prefab::<CubePrefab>(false)
-        .register_prefab::<UVSpherePrefab>(false)
-        .register_prefab::<PlanePrefab>(false)
-        .register_prefab::<CapsulePrefab>(false);
+        .register_prefab::<CubePrefab>()
+        .register_prefab::<UVSpherePrefab>()
+        .register_prefab::<PlanePrefab>()
+        .register_prefab::<CapsulePrefab>();
 }
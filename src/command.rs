@@ -1,6 +1,10 @@
-use bevy::{ecs::system::Command, prelude::*};
+use bevy::{
+    ecs::system::Command, prelude::*, transform::hierarchy::despawn_with_children_recursive,
+};
 
-use crate::{Prefab, PrefabNotInstantiatedTag};
+use crate::{
+    data::PrefabInstanceHooks, registry::PrefabInstanceResources, Prefab, PrefabNotInstantiatedTag,
+};
 
 struct SpawnPrefab<B> {
     prefab_handle: Handle<Prefab>,
@@ -24,12 +28,50 @@ where
     }
 }
 
+struct DespawnPrefab {
+    root: Entity,
+}
+
+impl Command for DespawnPrefab {
+    fn write(self: Box<Self>, world: &mut World) {
+        // from the outermost to the innermost instance, the whole hierarchy is kept alive until the end
+        let mut stack = vec![self.root];
+        let mut visited = vec![];
+        while let Some(entity) = stack.pop() {
+            if let Some(hooks) = world.get::<PrefabInstanceHooks>(entity).copied() {
+                if let Err(err) = (hooks.on_despawn)(world, entity) {
+                    error!("prefab on despawn failed: {}", err);
+                }
+            }
+
+            if let Some(children) = world.get::<Children>(entity) {
+                stack.extend(children.iter().rev());
+            }
+            visited.push(entity);
+        }
+
+        // innermost instances first, so resources replaced by nested instances are restored in order
+        for entity in visited.into_iter().rev() {
+            if let Some(resources) = world.entity_mut(entity).remove::<PrefabInstanceResources>() {
+                resources.despawn(world);
+            }
+        }
+
+        despawn_with_children_recursive(world, self.root);
+    }
+}
+
 pub trait PrefabCommands {
     fn spawn_prefab(self, prefab_handle: Handle<Prefab>) -> Self;
 
     fn spawn_prefab_with_overrides<B>(self, prefab_handle: Handle<Prefab>, overrides: B) -> Self
     where
         B: Bundle + Send + Sync + 'static;
+
+    /// Despawns the prefab instance `root` along with it's children,
+    /// calling [`crate::data::PrefabData::on_despawn`] of every instance first;
+    /// Resources declared by the instances are then handled by their [`crate::registry::ResourceDespawnPolicy`]
+    fn despawn_prefab(self, root: Entity) -> Self;
 }
 
 impl<'a, 'c> PrefabCommands for &'c mut Commands<'a> {
@@ -47,4 +89,9 @@ impl<'a, 'c> PrefabCommands for &'c mut Commands<'a> {
         });
         self
     }
+
+    fn despawn_prefab(self, root: Entity) -> Self {
+        self.add(DespawnPrefab { root });
+        self
+    }
 }
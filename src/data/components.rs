@@ -0,0 +1,465 @@
+use std::any::TypeId;
+
+use bevy::{
+    ecs::{
+        entity::{Entity, EntityMap, MapEntities, MapEntitiesError},
+        world::World,
+    },
+    reflect::{Reflect, Uuid},
+};
+
+use crate::{
+    path::{resolve_entity_path, PrefabEntityPath, PrefabEntityProxy, ResolvedEntity},
+    registry::{ComponentDescriptorRegistry, ComponentPatchFn, ComponentRemoveFn},
+    PrefabNotInstantiatedTag, PrefabTypeUuid,
+};
+
+use super::{
+    BoxedPrefabOverrides, Override, OverrideReporter, PrefabOverrideLayer, PrefabOverrideLayers,
+    PrefabOverrideReport, PrefabOverrideSource,
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Component overrides of a prefab instance, they are applied right after the instance
+/// is spawned and it's construct function is called
+#[derive(Default, Clone)]
+pub struct PrefabComponentOverrides(pub(crate) Vec<ComponentOverride>);
+
+impl PrefabComponentOverrides {
+    /// Applies all overrides in order, overrides targeting entities of nested prefabs
+    /// not yet instantiated are forwarded to the nested prefab instance
+    pub(crate) fn apply(
+        &self,
+        world: &mut World,
+        root: Entity,
+        component_registry: &ComponentDescriptorRegistry,
+        report: &mut PrefabOverrideReport,
+    ) {
+        for component_override in &self.0 {
+            match resolve_entity_path(world, root, &component_override.path) {
+                ResolvedEntity::Found(entity) => {
+                    let mut reporter = report.reporter(
+                        component_override.source.clone(),
+                        component_override.path.clone(),
+                        component_override.op.component(),
+                    );
+                    component_override
+                        .op
+                        .apply(world, entity, component_registry, &mut reporter)
+                }
+                ResolvedEntity::Pending { instance, rest } => {
+                    // reported by the nested prefab instance once applied
+                    let forwarded = ComponentOverride {
+                        source: component_override.source.clone(),
+                        path: rest,
+                        op: component_override.op.clone(),
+                    };
+                    let mut instance = world.entity_mut(instance);
+                    if let Some(mut overrides) = instance.get_mut::<PrefabComponentOverrides>() {
+                        overrides.0.push(forwarded);
+                    } else {
+                        instance.insert(PrefabComponentOverrides(vec![forwarded]));
+                    }
+                }
+                ResolvedEntity::Missing => report
+                    .reporter(
+                        component_override.source.clone(),
+                        component_override.path.clone(),
+                        component_override.op.component(),
+                    )
+                    .ignored("entity not found"),
+            }
+        }
+    }
+}
+
+impl PrefabComponentOverrides {
+    /// Patches `target` with the patches written for the root `component` and takes them out,
+    /// so they aren't applied again along with the other overrides; Stops at the first override
+    /// that could replace or remove the component, the patches after it must wait their turn
+    pub(crate) fn apply_root_patches(
+        &mut self,
+        component: &str,
+        target: &mut dyn Reflect,
+        report: &mut PrefabOverrideReport,
+    ) {
+        let mut blocked = false;
+        self.0.retain(|component_override| {
+            if blocked || !component_override.path.is_root() {
+                return true;
+            }
+            match &component_override.op {
+                ComponentOverrideOp::Patch {
+                    component: name,
+                    value,
+                    ..
+                } if *name == component => {
+                    let mut reporter = report.reporter(
+                        component_override.source.clone(),
+                        component_override.path.clone(),
+                        Some(name),
+                    );
+                    value.apply_override(target, &mut reporter);
+                    false
+                }
+                ComponentOverrideOp::Remove {
+                    component: name, ..
+                } if *name == component => {
+                    blocked = true;
+                    true
+                }
+                ComponentOverrideOp::Insert { .. } => {
+                    blocked = true;
+                    true
+                }
+                _ => true,
+            }
+        });
+    }
+}
+
+impl MapEntities for PrefabComponentOverrides {
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        for component_override in &mut self.0 {
+            component_override.op.map_entities(entity_map)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ComponentOverride {
+    /// Prefab file and instance that defined this override
+    pub source: PrefabOverrideSource,
+    /// Entity targeted by this override
+    pub path: PrefabEntityPath,
+    pub op: ComponentOverrideOp,
+}
+
+#[derive(Clone)]
+pub(crate) enum ComponentOverrideOp {
+    /// Only the fields present in the override are changed, the component is
+    /// inserted with it's default value when missing
+    Patch {
+        component: &'static str,
+        apply: ComponentPatchFn,
+        value: Box<dyn Override>,
+    },
+    /// Removes the component
+    Remove {
+        component: &'static str,
+        remove: ComponentRemoveFn,
+    },
+    /// Moves all components of the `carrier` entity over, replacing the existing ones
+    Insert { carrier: Entity },
+    /// Adds an outer layer of prefab data overrides to a nested prefab instance
+    Overrides {
+        /// Expected prefab type
+        uuid: Uuid,
+        layer: PrefabOverrideLayer,
+    },
+}
+
+impl ComponentOverrideOp {
+    /// Type name of the overridden component, if any
+    fn component(&self) -> Option<&'static str> {
+        match self {
+            ComponentOverrideOp::Patch { component, .. }
+            | ComponentOverrideOp::Remove { component, .. } => Some(*component),
+            ComponentOverrideOp::Insert { .. } | ComponentOverrideOp::Overrides { .. } => None,
+        }
+    }
+
+    fn apply(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        component_registry: &ComponentDescriptorRegistry,
+        report: &mut OverrideReporter,
+    ) {
+        match self {
+            ComponentOverrideOp::Patch { apply, value, .. } => {
+                (apply)(world, entity, &**value, report)
+            }
+            ComponentOverrideOp::Remove { remove, .. } => {
+                (remove)(&mut world.entity_mut(entity));
+                report.applied();
+            }
+            ComponentOverrideOp::Insert { carrier } => {
+                let carrier_entity = world.entity(*carrier);
+                let type_ids = carrier_entity
+                    .archetype()
+                    .components()
+                    .filter_map(|component_id| world.components().get_info(component_id))
+                    .filter_map(|component_info| component_info.type_id())
+                    // carrier still stands for the entity until it's references are resolved
+                    .filter(|type_id| *type_id != TypeId::of::<PrefabEntityProxy>())
+                    .collect::<Vec<_>>();
+
+                for type_id in type_ids {
+                    // components like `Children` can be added to the carrier once it's spawned,
+                    // those aren't part of the override and are left behind
+                    let take = component_registry
+                        .find_by_type(type_id)
+                        .and_then(|descriptor| descriptor.take.as_ref());
+                    if let Some(take) = take {
+                        (take)(world, *carrier, entity);
+                    }
+                }
+            }
+            ComponentOverrideOp::Overrides { uuid, layer } => {
+                let mut instance = world.entity_mut(entity);
+                if !instance.contains::<PrefabNotInstantiatedTag>() {
+                    report.ignored("entity isn't a prefab instance waiting to be spawned");
+                    return;
+                }
+
+                if let Some(PrefabTypeUuid(expected)) = instance.get() {
+                    if expected != uuid {
+                        report.mismatched(&expected.to_string(), &uuid.to_string());
+                        return;
+                    }
+                }
+
+                // layer outcome is reported by the nested prefab instance
+
+                if let Some(mut layers) = instance.get_mut::<PrefabOverrideLayers>() {
+                    layers.0.push(layer.clone());
+                } else {
+                    // instance overrides weren't loaded from a file, so they don't have a source
+                    let mut layers = PrefabOverrideLayers::default();
+                    if let Some(BoxedPrefabOverrides(overrides)) = instance.get() {
+                        layers.0.push(PrefabOverrideLayer {
+                            source: Default::default(),
+                            overrides: overrides.clone(),
+                        });
+                    }
+                    layers.0.push(layer.clone());
+                    instance.insert(layers);
+                }
+            }
+        }
+    }
+
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        match self {
+            ComponentOverrideOp::Patch { value, .. } => value.map_entities(entity_map),
+            ComponentOverrideOp::Remove { .. } => Ok(()),
+            ComponentOverrideOp::Insert { carrier } => {
+                *carrier = entity_map.get(*carrier)?;
+                Ok(())
+            }
+            ComponentOverrideOp::Overrides { layer, .. } => {
+                layer.overrides.map_entities(entity_map)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::reflect::Reflect;
+    use serde::{de::DeserializeSeed, Deserialize};
+
+    use super::*;
+    use crate::{
+        data::{OverrideDescriptor, OverrideRegistry},
+        registry::ComponentDescriptor,
+    };
+
+    #[derive(Default, Debug, Deserialize, PartialEq, Clone, Reflect)]
+    struct Light {
+        intensity: f32,
+        range: f32,
+    }
+
+    fn patch(descriptor: &ComponentDescriptor, input: &str) -> ComponentOverride {
+        let patch = descriptor.patch.as_ref().unwrap();
+        let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+        ComponentOverride {
+            source: Default::default(),
+            path: PrefabEntityPath::root(),
+            op: ComponentOverrideOp::Patch {
+                component: descriptor.name,
+                apply: patch.apply.clone(),
+                value: patch.overrides.deserialize(&mut deserializer).unwrap(),
+            },
+        }
+    }
+
+    #[test]
+    fn patch_after_construct() {
+        let mut overrides = OverrideRegistry::default();
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register_patchable::<Light>("Light".to_string(), &mut overrides)
+            .unwrap();
+        let descriptor = component_registry.find_by_name("Light").unwrap();
+        let component_overrides = PrefabComponentOverrides(vec![patch(descriptor, "( range: 5 )")]);
+
+        // component added by the construct function only has the written fields changed
+        let mut world = World::default();
+        let root = world
+            .spawn()
+            .insert(Light {
+                intensity: 3.0,
+                range: 1.0,
+            })
+            .id();
+        let mut report = PrefabOverrideReport::default();
+        component_overrides.apply(&mut world, root, &component_registry, &mut report);
+        assert_eq!(
+            world.get::<Light>(root),
+            Some(&Light {
+                intensity: 3.0,
+                range: 5.0,
+            })
+        );
+        assert_eq!(report.applied().count(), 1);
+
+        // missing component is patched over it's default value
+        let root = world.spawn().id();
+        component_overrides.apply(&mut world, root, &component_registry, &mut report);
+        assert_eq!(
+            world.get::<Light>(root),
+            Some(&Light {
+                intensity: 0.0,
+                range: 5.0,
+            })
+        );
+    }
+
+    #[derive(Default, Debug, Clone, PartialEq)]
+    struct Highlight;
+
+    #[test]
+    fn insert_from_carrier() {
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register::<Light>("Light".to_string())
+            .unwrap();
+
+        let mut world = World::default();
+        let root = world.spawn().id();
+        let carrier = world
+            .spawn()
+            .insert(Light {
+                intensity: 2.0,
+                range: 3.0,
+            })
+            .insert(Highlight)
+            .insert(PrefabEntityProxy {
+                root: Some(root),
+                path: PrefabEntityPath::root(),
+            })
+            .id();
+
+        let component_overrides = PrefabComponentOverrides(vec![ComponentOverride {
+            source: Default::default(),
+            path: PrefabEntityPath::root(),
+            op: ComponentOverrideOp::Insert { carrier },
+        }]);
+        let mut report = PrefabOverrideReport::default();
+        component_overrides.apply(&mut world, root, &component_registry, &mut report);
+
+        // only registered components are moved over, the carrier keeps the others
+        assert_eq!(
+            world.get::<Light>(root),
+            Some(&Light {
+                intensity: 2.0,
+                range: 3.0,
+            })
+        );
+        assert_eq!(world.get::<Light>(carrier), None);
+        assert_eq!(world.get::<Highlight>(root), None);
+        assert_eq!(world.get::<Highlight>(carrier), Some(&Highlight));
+        assert!(world.get::<PrefabEntityProxy>(carrier).is_some());
+    }
+
+    fn overrides(descriptor: &OverrideDescriptor, input: &str) -> Box<dyn Override> {
+        let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+        descriptor.deserialize(&mut deserializer).unwrap()
+    }
+
+    #[test]
+    fn override_layers() {
+        let mut registry = OverrideRegistry::default();
+        registry.register_struct::<Light>().unwrap();
+        let descriptor = registry.find::<Light>().unwrap();
+
+        let mut world = World::default();
+        let instance = world
+            .spawn()
+            .insert(PrefabNotInstantiatedTag { _marker: () })
+            .insert(BoxedPrefabOverrides(overrides(
+                descriptor,
+                "( intensity: 2, range: 2 )",
+            )))
+            .id();
+
+        // outer prefabs stack their layers in the order they are spawned
+        let layer = |instance: &str, input: &str| ComponentOverride {
+            source: Default::default(),
+            path: PrefabEntityPath::root(),
+            op: ComponentOverrideOp::Overrides {
+                uuid: Uuid::default(),
+                layer: PrefabOverrideLayer {
+                    source: PrefabOverrideSource {
+                        file: None,
+                        instance: PrefabEntityPath::parse(instance),
+                    },
+                    overrides: overrides(descriptor, input),
+                },
+            },
+        };
+        let component_registry = ComponentDescriptorRegistry::default();
+        let mut report = PrefabOverrideReport::default();
+        PrefabComponentOverrides(vec![layer("10/Lamp", "( range: 4 )")]).apply(
+            &mut world,
+            instance,
+            &component_registry,
+            &mut report,
+        );
+        PrefabComponentOverrides(vec![layer("20/Room/Lamp", "( intensity: 5 )")]).apply(
+            &mut world,
+            instance,
+            &component_registry,
+            &mut report,
+        );
+
+        let layers = world.get::<PrefabOverrideLayers>(instance).unwrap();
+        let sources = layers
+            .layers()
+            .iter()
+            .map(|layer| layer.source.instance.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(sources, vec!["", "10/Lamp", "20/Room/Lamp"]);
+
+        let field_source = |field: &str| {
+            layers
+                .find_field_layer(field)
+                .map(|layer| layer.source.instance.to_string())
+        };
+        assert_eq!(field_source("intensity"), Some("20/Room/Lamp".to_string()));
+        assert_eq!(field_source("range"), Some("10/Lamp".to_string()));
+        assert_eq!(field_source("color"), None);
+
+        // instance overrides are left as written
+        assert!(world
+            .get::<BoxedPrefabOverrides>(instance)
+            .unwrap()
+            .0
+            .overrides_field(&["intensity"]));
+
+        let mut light = Light::default();
+        layers.apply_override(&mut light, &mut PrefabOverrideReport::default());
+        assert_eq!(
+            light,
+            Light {
+                intensity: 5.0,
+                range: 4.0,
+            }
+        );
+    }
+}
@@ -1,83 +1,209 @@
-use std::fmt::Debug;
+use std::{borrow::Cow, fmt::Debug};
 
 use anyhow::Result;
-use bevy::{ecs::{component::Component, entity::{Entity, EntityMap}, world::World}, reflect::{Reflect, TypeUuid, Uuid}};
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::{Entity, EntityMap},
+        world::World,
+    },
+    reflect::{Reflect, TypeUuid, Uuid},
+};
 use serde::{Deserialize, Serialize};
 
-use super::BoxedPrefabOverrides;
+use crate::registry::map_reflect_entities;
+
+use super::{
+    BoxedPrefabOverrides, OverrideRules, PrefabOverrideLayer, PrefabOverrideLayers,
+    PrefabOverrideReport,
+};
 
 ///////////////////////////////////////////////////////////////////////////////
 
 pub trait PrefabData: PrefabDataHelper + Debug + Send + Sync + 'static {
-    /// Construct function called once on spawn
+    /// Construct function called once on spawn, before any nested prefab instance is spawned
     fn construct(&self, world: &mut World, root: Entity) -> Result<()>;
-    
-    /// Find entities references
+
+    /// Called once the whole instance is spawned, nested prefab instances included,
+    /// after their own `post_construct`; Nested instances whose source prefab isn't loaded yet
+    /// hold this call until they are spawned, errors are tagged on the root with [`crate::PrefabErrorTag`]
+    /// and skip the call of the parent instances
+    fn post_construct(&self, world: &mut World, root: Entity) -> Result<()> {
+        let _ = world;
+        let _ = root;
+        Ok(())
+    }
+
+    /// Called by [`crate::command::PrefabCommands::despawn_prefab`] before the instance is despawned,
+    /// ahead of any nested prefab instance
+    fn on_despawn(&self, world: &mut World, root: Entity) -> Result<()> {
+        let _ = world;
+        let _ = root;
+        Ok(())
+    }
+
+    /// Find entities references, by default every `Entity` reachable through [`Reflect`] is mapped
     fn map_entities(&mut self, entity_map: &EntityMap) -> Result<()> {
-        let _ = entity_map;
+        map_reflect_entities(self.reflect_data_mut(), entity_map)?;
         Ok(())
     }
+
+    /// Tells if instances of this prefab must have a `source` prefab file, prefabs fully built
+    /// by their construct function don't need one
+    fn source_prefab_required() -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
+
+    /// Rules used when the prefab data is overridden by prefab instances
+    fn override_rules() -> OverrideRules
+    where
+        Self: Sized,
+    {
+        OverrideRules::default()
+    }
 }
 
 #[derive(Debug)]
 pub struct BoxedPrefabData(pub Box<dyn PrefabData>);
 
+pub(crate) type PrefabHookFn = fn(&mut World, Entity) -> Result<()>;
+
+/// Lifecycle hooks of a spawned prefab instance, inserted on it's root
+#[derive(Clone, Copy)]
+pub(crate) struct PrefabInstanceHooks {
+    pub post_construct: PrefabHookFn,
+    pub on_despawn: PrefabHookFn,
+}
+
+impl PrefabInstanceHooks {
+    fn of<T: PrefabData + Component + Clone>() -> Self {
+        Self {
+            post_construct: |world, root| match world.get::<T>(root).cloned() {
+                Some(data) => data.post_construct(world, root),
+                None => Ok(()),
+            },
+            on_despawn: |world, root| match world.get::<T>(root).cloned() {
+                Some(data) => data.on_despawn(world, root),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 /// Helper default functions
 pub trait PrefabDataHelper {
     /// Constructs prefabs instances using the instance data or using self as a default
     /// is also responsible to apply any prefab overrides
-    fn apply_overrides_and_construct_instance(&self, world: &mut World, root: Entity, prefab_to_instance: &EntityMap) -> Result<()>;
+    fn apply_overrides_and_construct_instance(
+        &self,
+        world: &mut World,
+        root: Entity,
+        prefab_to_instance: &EntityMap,
+    ) -> Result<()>;
 
     /// Uuid from [`TypeUuid`]
     fn type_uuid(&self) -> Uuid;
+
+    /// Reflect access to the prefab data
+    fn reflect_data(&self) -> &dyn Reflect;
+
+    fn reflect_data_mut(&mut self) -> &mut dyn Reflect;
+
+    /// Prefab data component of the instance `root`, if any
+    fn reflect_instance_data<'w>(&self, world: &'w World, root: Entity) -> Option<&'w dyn Reflect>;
 }
 
 impl<T> PrefabDataHelper for T
 where
     T: PrefabData + TypeUuid + Reflect + Clone + Component,
 {
-    fn apply_overrides_and_construct_instance(&self, world: &mut World, root: Entity, prefab_to_instance: &EntityMap) -> Result<()> {
-        // TODO: quite bit of cloning is required, maybe there's a better ways but I digress
-        let mut entity = world.entity_mut(root);
-
-        let overrides = entity
-            .get::<BoxedPrefabOverrides>()
-            .map(|overrides| 
-                // SAFETY used to apply overrides in the prefab data,
-                // no changes will be made in the entity archetype so no data will be invalidated
-                unsafe { &*(overrides as *const BoxedPrefabOverrides) }
-            );
-        
-        // create defaults
-        let mut data = self.clone();
-
-        // map data entities to the instance space
-        data.map_entities(prefab_to_instance)?;
-
-        if let Some(overrides) = overrides {
-            // apply overrides
-            overrides.0.apply_override(&mut data);
-
-            // insert missing prefab data component
-            entity.insert(data.clone());
-
-            // run the prefab construct function using it's data
-            data.construct(world, root)
-        } else {
-            // fast code path since no overrides where added less data cloning is required
-            entity.insert(data);
-
-            // run the construct function using the original copy of the data,
-            // this data could be `Default::default` or the data from the source prefab
-            self.construct(world, root)
-        }
+    fn apply_overrides_and_construct_instance(
+        &self,
+        world: &mut World,
+        root: Entity,
+        prefab_to_instance: &EntityMap,
+    ) -> Result<()> {
+        apply_overrides_and_construct(self, world, root, prefab_to_instance)
     }
 
     fn type_uuid(&self) -> Uuid {
         T::TYPE_UUID
     }
+
+    fn reflect_data(&self) -> &dyn Reflect {
+        self
+    }
+
+    fn reflect_data_mut(&mut self) -> &mut dyn Reflect {
+        self
+    }
+
+    fn reflect_instance_data<'w>(&self, world: &'w World, root: Entity) -> Option<&'w dyn Reflect> {
+        world.get::<T>(root).map(|data| data as &dyn Reflect)
+    }
+}
+
+/// Shared by every [`PrefabDataHelper`] implementation
+pub(crate) fn apply_overrides_and_construct<T: PrefabData + Component + Clone>(
+    source: &T,
+    world: &mut World,
+    root: Entity,
+    prefab_to_instance: &EntityMap,
+) -> Result<()> {
+    // TODO: quite bit of cloning is required, maybe there's a better ways but I digress
+    let mut entity = world.entity_mut(root);
+
+    let layers = entity.get::<PrefabOverrideLayers>().map(|layers| {
+        // SAFETY used to apply overrides in the prefab data, the reference is dropped
+        // before any component is inserted so the entity archetype doesn't change while it's alive
+        unsafe { &*(layers as *const PrefabOverrideLayers) }
+    });
+    let layers = match layers {
+        Some(layers) => Some(Cow::Borrowed(layers)),
+        // overrides inserted by code have a single layer without source
+        None => entity.get::<BoxedPrefabOverrides>().map(|overrides| {
+            Cow::Owned(PrefabOverrideLayers(vec![PrefabOverrideLayer {
+                source: Default::default(),
+                overrides: overrides.0.clone(),
+            }]))
+        }),
+    };
+
+    // create defaults
+    let mut data = source.clone();
+
+    // map data entities to the instance space
+    data.map_entities(prefab_to_instance)?;
+
+    if let Some(layers) = layers {
+        // apply overrides layers, from the innermost to the outermost prefab
+        let mut report = PrefabOverrideReport::default();
+        layers.apply_override(data.reflect_data_mut(), &mut report);
+        drop(layers);
+
+        // root components patched while merged with the source prefab were already reported
+        if let Some(mut merged) = entity.get_mut::<PrefabOverrideReport>() {
+            report.entries.append(&mut merged.entries);
+        }
+
+        // insert missing prefab data component
+        entity.insert_bundle((data.clone(), report, PrefabInstanceHooks::of::<T>()));
+
+        // run the prefab construct function using it's data
+        data.construct(world, root)
+    } else {
+        // fast code path since no overrides where added less data cloning is required
+        entity.insert_bundle((data, PrefabInstanceHooks::of::<T>()));
+
+        // run the construct function using the original copy of the data,
+        // this data could be `Default::default` or the data from the source prefab
+        source.construct(world, root)
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -91,3 +217,90 @@ impl PrefabData for BlankPrefab {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::reflect::TypeUuid;
+
+    use super::*;
+    use crate::data::FieldConstraint;
+
+    #[derive(Debug, Clone, TypeUuid, Reflect, crate::prelude::PrefabData)]
+    #[uuid = "1f4fbf8a-0a8e-4d43-9d5b-2a3c1f0e6b7e"]
+    #[prefab(source_prefab_required = false, post_construct = switch_post_construct)]
+    struct Switch {
+        lamp: Entity,
+        wires: Vec<Entity>,
+        fallback: Option<Entity>,
+        #[prefab(range(0, 5))]
+        strength: f32,
+        #[prefab(one_of("on", "off"))]
+        label: String,
+        #[prefab(skip)]
+        toggles: u32,
+    }
+
+    fn switch_post_construct(switch: &Switch, world: &mut World, root: Entity) -> Result<()> {
+        world.entity_mut(root).insert(switch.toggles + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn derived_prefab_data() {
+        let mut entity_map = EntityMap::default();
+        entity_map.insert(Entity::new(0), Entity::new(10));
+        entity_map.insert(Entity::new(1), Entity::new(11));
+
+        let mut switch = Switch {
+            lamp: Entity::new(0),
+            wires: vec![Entity::new(1)],
+            fallback: Some(Entity::new(0)),
+            strength: 1.0,
+            label: "on".to_string(),
+            toggles: 0,
+        };
+        switch.map_entities(&entity_map).unwrap();
+        assert_eq!(switch.lamp, Entity::new(10));
+        assert_eq!(switch.wires, vec![Entity::new(11)]);
+        assert_eq!(switch.fallback, Some(Entity::new(10)));
+
+        let rules = Switch::override_rules();
+        assert!(rules.is_skipped("toggles"));
+        assert_eq!(
+            rules.constraints("strength"),
+            &[FieldConstraint::range(0.0, 5.0)]
+        );
+        assert_eq!(
+            rules.constraints("label"),
+            &[FieldConstraint::one_of(vec![
+                Box::new("on".to_string()),
+                Box::new("off".to_string())
+            ])]
+        );
+        assert!(!Switch::source_prefab_required());
+    }
+
+    #[test]
+    fn instance_hooks() {
+        let mut world = World::default();
+        let root = world
+            .spawn()
+            .insert(Switch {
+                lamp: Entity::new(0),
+                wires: vec![],
+                fallback: None,
+                strength: 1.0,
+                label: "off".to_string(),
+                toggles: 2,
+            })
+            .id();
+
+        let hooks = PrefabInstanceHooks::of::<Switch>();
+        (hooks.post_construct)(&mut world, root).unwrap();
+        assert_eq!(world.get::<u32>(root), Some(&3));
+
+        // default hook does nothing
+        (hooks.on_despawn)(&mut world, root).unwrap();
+        assert!(world.get::<Switch>(root).is_some());
+    }
+}
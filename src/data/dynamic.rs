@@ -0,0 +1,728 @@
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{anyhow, bail, Result};
+use bevy::{
+    ecs::{
+        entity::EntityMap,
+        world::{EntityMut, World},
+    },
+    math::prelude::*,
+    prelude::{Color, Entity},
+    reflect::{DynamicStruct, GetField, Reflect, Struct, Uuid},
+};
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, Visitor},
+    ser::{self, SerializeStruct},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::{
+    apply_overrides_and_construct, static_field_name, FieldConstraint, OverrideRules, PrefabData,
+    PrefabDataHelper,
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Prefab type declared in data instead of Rust code, see [`DynamicPrefabType::from_ron`]
+#[derive(Debug, Clone, Deserialize)]
+pub struct DynamicPrefabType {
+    pub name: String,
+    #[serde(default = "source_prefab_required")]
+    pub source_prefab_required: bool,
+    #[serde(default)]
+    pub fields: Vec<DynamicPrefabField>,
+}
+
+fn source_prefab_required() -> bool {
+    true
+}
+
+impl DynamicPrefabType {
+    /// Reads a list of prefab types, like:
+    ///
+    /// ```json5
+    /// [
+    ///     (
+    ///         name: "Crate",
+    ///         fields: [
+    ///             (name: "health", type: "f32", default: 100, constraints: [Range(min: 0, max: 100)]),
+    ///             (name: "tint", type: "Color"),
+    ///         ],
+    ///     ),
+    /// ]
+    /// ```
+    pub fn from_ron(source: &str) -> Result<Vec<Self>> {
+        Ok(ron::de::from_str(source)?)
+    }
+
+    pub(crate) fn override_rules(&self) -> OverrideRules {
+        fields_override_rules(&self.fields)
+    }
+
+    pub(crate) fn layout(&self, name: &'static str) -> Result<DynamicStructLayout> {
+        DynamicStructLayout::new(name, &self.fields)
+    }
+}
+
+/// Field of a [`DynamicPrefabType`] or [`DynamicComponentType`], the `type` must be written
+/// before the `default` value
+pub struct DynamicPrefabField {
+    pub name: String,
+    /// Field type name, e.g. `"f32"`, `"Vec3"` or `"Color"`
+    pub type_name: String,
+    /// Value used when prefabs don't write this field, the type default is used when `None`
+    pub default: Option<Box<dyn Reflect>>,
+    pub constraints: Vec<FieldConstraint>,
+}
+
+impl Clone for DynamicPrefabField {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            type_name: self.type_name.clone(),
+            default: self.default.as_ref().map(|default| default.clone_value()),
+            constraints: self.constraints.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for DynamicPrefabField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynamicPrefabField")
+            .field("name", &self.name)
+            .field("type_name", &self.type_name)
+            .field("default", &self.default)
+            .field("constraints", &self.constraints)
+            .finish()
+    }
+}
+
+impl<'de> Deserialize<'de> for DynamicPrefabField {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(
+            "DynamicPrefabField",
+            &["name", "type", "default", "constraints"],
+            DynamicPrefabFieldVisitor,
+        )
+    }
+}
+
+struct DynamicPrefabFieldVisitor;
+
+impl<'de> Visitor<'de> for DynamicPrefabFieldVisitor {
+    type Value = DynamicPrefabField;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a prefab field")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "lowercase")]
+        enum Field {
+            Name,
+            Type,
+            Default,
+            Constraints,
+        }
+
+        let mut name = None;
+        let mut type_name: Option<String> = None;
+        let mut default = None;
+        let mut constraints = None;
+
+        while let Some(key) = access.next_key()? {
+            match key {
+                Field::Name => {
+                    if name.is_some() {
+                        return Err(de::Error::duplicate_field("name"));
+                    }
+                    name = Some(access.next_value()?);
+                }
+                Field::Type => {
+                    if type_name.is_some() {
+                        return Err(de::Error::duplicate_field("type"));
+                    }
+                    type_name = Some(access.next_value()?);
+                }
+                Field::Default => {
+                    if default.is_some() {
+                        return Err(de::Error::duplicate_field("default"));
+                    }
+                    let type_name = type_name
+                        .as_deref()
+                        .ok_or_else(|| de::Error::custom("`type` must come before `default`"))?;
+                    let field_type = field_type(type_name).ok_or_else(|| {
+                        de::Error::custom(format!("unsupported field type `{}`", type_name))
+                    })?;
+                    default = Some(access.next_value_seed(FieldValueSeed(field_type))?);
+                }
+                Field::Constraints => {
+                    if constraints.is_some() {
+                        return Err(de::Error::duplicate_field("constraints"));
+                    }
+                    constraints = Some(access.next_value()?);
+                }
+            }
+        }
+
+        Ok(DynamicPrefabField {
+            name: name.ok_or_else(|| de::Error::missing_field("name"))?,
+            type_name: type_name.ok_or_else(|| de::Error::missing_field("type"))?,
+            default,
+            constraints: constraints.unwrap_or_default(),
+        })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy)]
+struct FieldType {
+    de: fn(&mut dyn erased_serde::Deserializer) -> Result<Box<dyn Reflect>>,
+    default: fn() -> Box<dyn Reflect>,
+}
+
+impl FieldType {
+    fn of<T: Reflect + Default + for<'de> Deserialize<'de>>() -> Self {
+        Self {
+            de: |deserializer| {
+                let value: T = Deserialize::deserialize(deserializer)?;
+                Ok(Box::new(value))
+            },
+            default: || Box::new(T::default()),
+        }
+    }
+}
+
+/// Field types available to dynamic prefabs, all of them can be overridden by prefab instances
+fn field_type(name: &str) -> Option<FieldType> {
+    let field_type = match name {
+        "bool" => FieldType::of::<bool>(),
+        "u8" => FieldType::of::<u8>(),
+        "i8" => FieldType::of::<i8>(),
+        "u16" => FieldType::of::<u16>(),
+        "i16" => FieldType::of::<i16>(),
+        "u32" => FieldType::of::<u32>(),
+        "i32" => FieldType::of::<i32>(),
+        "u64" => FieldType::of::<u64>(),
+        "i64" => FieldType::of::<i64>(),
+        "f32" => FieldType::of::<f32>(),
+        "f64" => FieldType::of::<f64>(),
+        "Vec2" => FieldType::of::<Vec2>(),
+        "Vec3" => FieldType::of::<Vec3>(),
+        "Vec4" => FieldType::of::<Vec4>(),
+        "Quat" => FieldType::of::<Quat>(),
+        "Color" => FieldType::of::<Color>(),
+        _ => return None,
+    };
+    Some(field_type)
+}
+
+struct FieldValueSeed(FieldType);
+
+impl<'de> DeserializeSeed<'de> for FieldValueSeed {
+    type Value = Box<dyn Reflect>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        (self.0.de)(&mut deserializer).map_err(de::Error::custom)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+fn fields_override_rules(fields: &[DynamicPrefabField]) -> OverrideRules {
+    fields
+        .iter()
+        .flat_map(|field| {
+            field
+                .constraints
+                .iter()
+                .map(move |constraint| (field.name.as_str(), constraint.clone()))
+        })
+        .fold(OverrideRules::default(), |rules, (name, constraint)| {
+            rules.constrain(name, constraint)
+        })
+}
+
+/// Resolved fields of a dynamic prefab or component type
+pub(crate) struct DynamicStructLayout {
+    name: &'static str,
+    fields: Vec<(String, FieldType)>,
+    default: DynamicStruct,
+}
+
+impl DynamicStructLayout {
+    fn new(name: &'static str, declared: &[DynamicPrefabField]) -> Result<Self> {
+        let mut default = DynamicStruct::default();
+        default.set_name(name.to_string());
+
+        let mut fields = Vec::with_capacity(declared.len());
+        for field in declared {
+            if fields.iter().any(|(other, _)| other == &field.name) {
+                bail!("`{}` has the field `{}` twice", name, field.name);
+            }
+
+            let field_type = field_type(&field.type_name).ok_or_else(|| {
+                anyhow!(
+                    "field `{}` of `{}` has the unsupported type `{}`",
+                    field.name,
+                    name,
+                    field.type_name
+                )
+            })?;
+
+            let value = match &field.default {
+                Some(value) => value.clone_value(),
+                None => (field_type.default)(),
+            };
+            default.insert_boxed(&field.name, value);
+            fields.push((field.name.clone(), field_type));
+        }
+
+        Ok(Self {
+            name,
+            fields,
+            default,
+        })
+    }
+
+    pub fn default_value(&self) -> &DynamicStruct {
+        &self.default
+    }
+
+    /// Missing fields keep their default values
+    pub fn deserialize(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer,
+    ) -> Result<DynamicStruct> {
+        // accepts both `( field: .. )` and `{ "field": .. }` forms
+        Ok(Deserializer::deserialize_any(
+            deserializer,
+            DynamicStructVisitor { layout: self },
+        )?)
+    }
+}
+
+struct DynamicStructVisitor<'a> {
+    layout: &'a DynamicStructLayout,
+}
+
+impl<'a, 'de> Visitor<'de> for DynamicStructVisitor<'a> {
+    type Value = DynamicStruct;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a `{}` struct", self.layout.name)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(self.layout.default.clone_dynamic())
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut value = self.layout.default.clone_dynamic();
+        while let Some(FieldName(name)) = access.next_key()? {
+            let (_, field_type) = self
+                .layout
+                .fields
+                .iter()
+                .find(|(field, _)| field == &name)
+                .ok_or_else(|| de::Error::unknown_field(&name, &[]))?;
+            let field = access.next_value_seed(FieldValueSeed(*field_type))?;
+            value.insert_boxed(&name, field);
+        }
+        Ok(value)
+    }
+}
+
+struct FieldName(String);
+
+impl<'de> Deserialize<'de> for FieldName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // map keys are strings while struct keys are identifiers
+        deserializer.deserialize_any(FieldNameVisitor)
+    }
+}
+
+struct FieldNameVisitor;
+
+impl<'de> Visitor<'de> for FieldNameVisitor {
+    type Value = FieldName;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a field name")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(FieldName(v.to_string()))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Prefab data of the prefab types declared in data, see [`DynamicPrefabType`]
+pub struct DynamicPrefab {
+    uuid: Uuid,
+    data: DynamicStruct,
+}
+
+impl DynamicPrefab {
+    pub(crate) fn new(uuid: Uuid, data: DynamicStruct) -> Self {
+        Self { uuid, data }
+    }
+
+    /// Prefab type name
+    pub fn name(&self) -> &str {
+        self.data.name()
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    pub fn data(&self) -> &DynamicStruct {
+        &self.data
+    }
+
+    pub fn field<T: Reflect>(&self, name: &str) -> Option<&T> {
+        self.data.get_field(name)
+    }
+}
+
+impl Clone for DynamicPrefab {
+    fn clone(&self) -> Self {
+        Self {
+            uuid: self.uuid,
+            data: self.data.clone_dynamic(),
+        }
+    }
+}
+
+impl fmt::Debug for DynamicPrefab {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct(self.data.name());
+        for (i, field) in self.data.iter_fields().enumerate() {
+            debug.field(self.data.name_at(i).unwrap(), &field);
+        }
+        debug.finish()
+    }
+}
+
+impl PrefabData for DynamicPrefab {
+    fn construct(&self, _: &mut World, _: Entity) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl PrefabDataHelper for DynamicPrefab {
+    fn apply_overrides_and_construct_instance(
+        &self,
+        world: &mut World,
+        root: Entity,
+        prefab_to_instance: &EntityMap,
+    ) -> Result<()> {
+        apply_overrides_and_construct(self, world, root, prefab_to_instance)
+    }
+
+    fn type_uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    fn reflect_data(&self) -> &dyn Reflect {
+        &self.data
+    }
+
+    fn reflect_data_mut(&mut self) -> &mut dyn Reflect {
+        &mut self.data
+    }
+
+    fn reflect_instance_data<'w>(&self, world: &'w World, root: Entity) -> Option<&'w dyn Reflect> {
+        world
+            .get::<DynamicPrefab>(root)
+            .map(|data| &data.data as &dyn Reflect)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Component declared in data instead of Rust code, it's kept by the entity [`PrefabDynamicComponents`];
+/// Components without fields work as tags
+#[derive(Debug, Clone, Deserialize)]
+pub struct DynamicComponentType {
+    pub name: String,
+    #[serde(default)]
+    pub fields: Vec<DynamicPrefabField>,
+}
+
+impl DynamicComponentType {
+    /// Reads a list of component types, fields are declared just like in [`DynamicPrefabType::from_ron`]
+    /// and their constraints limit what prefab instances can patch
+    pub fn from_ron(source: &str) -> Result<Vec<Self>> {
+        Ok(ron::de::from_str(source)?)
+    }
+
+    pub(crate) fn override_rules(&self) -> OverrideRules {
+        fields_override_rules(&self.fields)
+    }
+
+    pub(crate) fn layout(&self, name: &'static str) -> Result<DynamicStructLayout> {
+        DynamicStructLayout::new(name, &self.fields)
+    }
+}
+
+/// Dynamic components of an entity by name, see [`DynamicComponentType`]
+#[derive(Default)]
+pub struct PrefabDynamicComponents(BTreeMap<String, DynamicStruct>);
+
+impl PrefabDynamicComponents {
+    pub fn get(&self, name: &str) -> Option<&DynamicStruct> {
+        self.0.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut DynamicStruct> {
+        self.0.get_mut(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Field of the `component`, `None` when either is missing or the type doesn't match
+    pub fn field<T: Reflect>(&self, component: &str, field: &str) -> Option<&T> {
+        self.get(component)?.get_field(field)
+    }
+
+    /// Adds or replaces the component named after the `value`
+    pub fn insert(&mut self, value: DynamicStruct) -> Option<DynamicStruct> {
+        self.0.insert(value.name().to_string(), value)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<DynamicStruct> {
+        self.0.remove(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DynamicStruct> {
+        self.0.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Inserts the component in the `entity`, the [`PrefabDynamicComponents`] itself is added when missing
+    pub(crate) fn insert_into(entity: &mut EntityMut, value: DynamicStruct) {
+        match entity.get_mut::<PrefabDynamicComponents>() {
+            Some(mut components) => {
+                components.insert(value);
+            }
+            None => {
+                let mut components = PrefabDynamicComponents::default();
+                components.insert(value);
+                entity.insert(components);
+            }
+        }
+    }
+
+    /// Serializable copy of the component, written as it would be in a prefab file
+    pub(crate) fn serializable(&self, name: &str) -> Option<Box<dyn erased_serde::Serialize>> {
+        self.get(name)
+            .map(|value| Box::new(OwnedDynamicStruct(value.clone_dynamic())) as Box<_>)
+    }
+}
+
+impl Clone for PrefabDynamicComponents {
+    fn clone(&self) -> Self {
+        Self(
+            self.0
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone_dynamic()))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Debug for PrefabDynamicComponents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.iter().map(|value| value as &dyn Reflect))
+            .finish()
+    }
+}
+
+/// Written as a struct with a field per component name
+impl Serialize for PrefabDynamicComponents {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("PrefabDynamicComponents", self.0.len())?;
+        for (name, value) in &self.0 {
+            state.serialize_field(static_field_name(name), &DynamicStructRef(value))?;
+        }
+        state.end()
+    }
+}
+
+struct OwnedDynamicStruct(DynamicStruct);
+
+impl Serialize for OwnedDynamicStruct {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        DynamicStructRef(&self.0).serialize(serializer)
+    }
+}
+
+struct DynamicStructRef<'a>(&'a DynamicStruct);
+
+impl<'a> Serialize for DynamicStructRef<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // same `( field: .. )` form prefab files use
+        let mut state =
+            serializer.serialize_struct(static_field_name(self.0.name()), self.0.field_len())?;
+        for (i, field) in self.0.iter_fields().enumerate() {
+            let serializable = field.serializable().ok_or_else(|| {
+                ser::Error::custom(format!("`{}` isn't serializable", field.type_name()))
+            })?;
+            state.serialize_field(
+                static_field_name(self.0.name_at(i).unwrap()),
+                serializable.borrow(),
+            )?;
+        }
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::BoxedPrefabOverrides, registry::PrefabDescriptorRegistry, schema::FieldSchema,
+    };
+
+    const TYPES: &str = r#"[
+    (
+        name: "Crate",
+        source_prefab_required: false,
+        fields: [
+            (name: "health", type: "f32", default: 100, constraints: [Range(min: 0, max: 100)]),
+            (name: "tint", type: "Color", default: Rgba(red: 1, green: 0, blue: 0, alpha: 1)),
+            (name: "size", type: "Vec3"),
+        ],
+    ),
+]"#;
+
+    #[test]
+    fn dynamic_prefab_type() {
+        let mut types = DynamicPrefabType::from_ron(TYPES).unwrap();
+        assert_eq!(types.len(), 1);
+        let crate_type = types.pop().unwrap();
+
+        let mut prefab_registry = PrefabDescriptorRegistry::default();
+        prefab_registry
+            .register_dynamic(crate_type.clone())
+            .unwrap();
+        assert!(prefab_registry.register_dynamic(crate_type).is_err());
+
+        let descriptor = prefab_registry.find_by_name("Crate").unwrap();
+        assert!(!descriptor.source_prefab_required);
+
+        // missing fields use their defaults
+        let mut deserializer = ron::de::Deserializer::from_str("(size: (1, 2, 3))").unwrap();
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(&mut deserializer);
+        let data = (descriptor.de)(&mut deserializer).unwrap();
+        let data = data
+            .0
+            .reflect_data()
+            .any()
+            .downcast_ref::<DynamicStruct>()
+            .unwrap();
+        assert_eq!(data.get_field::<f32>("health"), Some(&100.0));
+        assert_eq!(
+            data.get_field::<Color>("tint"),
+            Some(&Color::rgba(1.0, 0.0, 0.0, 1.0))
+        );
+        assert_eq!(
+            data.get_field::<Vec3>("size"),
+            Some(&Vec3::new(1.0, 2.0, 3.0))
+        );
+
+        let mut deserializer = ron::de::Deserializer::from_str("(weight: 2)").unwrap();
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(&mut deserializer);
+        assert!((descriptor.de)(&mut deserializer).is_err());
+
+        // overrides
+        match descriptor.overrides.schema() {
+            FieldSchema::Struct { type_name, fields } => {
+                assert_eq!(type_name, "Crate");
+                assert_eq!(
+                    fields.keys().collect::<Vec<_>>(),
+                    vec!["health", "size", "tint"]
+                );
+                assert!(matches!(
+                    &fields["health"],
+                    FieldSchema::Value { constraints, .. }
+                        if constraints == &[FieldConstraint::range(0.0, 100.0)]
+                ));
+            }
+            _ => panic!("expected a struct schema"),
+        }
+
+        let mut deserializer = ron::de::Deserializer::from_str("(health: 500)").unwrap();
+        assert!(descriptor.overrides.deserialize(&mut deserializer).is_err());
+
+        let mut deserializer = ron::de::Deserializer::from_str("(health: 50)").unwrap();
+        let overrides = descriptor.overrides.deserialize(&mut deserializer).unwrap();
+
+        let mut world = World::default();
+        let root = world.spawn().insert(BoxedPrefabOverrides(overrides)).id();
+        (descriptor.construct)(&mut world, root, &EntityMap::default()).unwrap();
+
+        let data = world.get::<DynamicPrefab>(root).unwrap();
+        assert_eq!(data.name(), "Crate");
+        assert_eq!(data.field::<f32>("health"), Some(&50.0));
+        assert_eq!(data.field::<Vec3>("size"), Some(&Vec3::ZERO));
+    }
+
+    #[test]
+    fn invalid_fields() {
+        let source = r#"[(name: "Crate", fields: [(name: "health", default: 1, type: "f32")])]"#;
+        assert!(DynamicPrefabType::from_ron(source).is_err());
+
+        let source = r#"[(name: "Crate", fields: [(name: "name", type: "String")])]"#;
+        let crate_type = DynamicPrefabType::from_ron(source).unwrap().pop().unwrap();
+        assert!(PrefabDescriptorRegistry::default()
+            .register_dynamic(crate_type)
+            .is_err());
+    }
+}
@@ -1,17 +1,102 @@
-use bevy::ecs::entity::{EntityMap, MapEntities, MapEntitiesError};
+use bevy::{
+    ecs::entity::{EntityMap, MapEntities, MapEntitiesError},
+    reflect::Reflect,
+};
+use serde::{Serialize, Serializer};
 
+use crate::path::PrefabEntityPath;
+
+mod components;
 mod data;
+mod dynamic;
 mod overrides;
+mod report;
+mod rules;
 
+pub use components::*;
 pub use data::*;
+pub use dynamic::*;
 pub use overrides::*;
+pub use report::*;
+pub use rules::*;
 
 #[derive(Clone)]
 pub struct BoxedPrefabOverrides(pub Box<dyn Override>);
 
+impl Serialize for BoxedPrefabOverrides {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
 impl MapEntities for BoxedPrefabOverrides {
     #[inline]
     fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
         self.0.map_entities(entity_map)
     }
 }
+
+/// Every layer of prefab data overrides of a prefab instance, one for each prefab
+/// that overrides this instance; The first layer holds the instance [`BoxedPrefabOverrides`],
+/// the others are stacked by the prefabs this instance is nested in.
+///
+/// Layers are applied in order, starting by the innermost source prefab and ending
+/// with the outermost prefab instance
+#[derive(Default, Clone)]
+pub struct PrefabOverrideLayers(pub(crate) Vec<PrefabOverrideLayer>);
+
+impl PrefabOverrideLayers {
+    pub fn layers(&self) -> &[PrefabOverrideLayer] {
+        &self.0
+    }
+
+    /// Finds the layer that gave the `field` it's final value, nested fields
+    /// are separated by dots like `"light.color"` or `"spawn_points[2].x"`; `None` means the field
+    /// keeps the value defined by the source prefab
+    pub fn find_field_layer(&self, field: &str) -> Option<&PrefabOverrideLayer> {
+        let path = split_field_path(field);
+        self.0
+            .iter()
+            .rev()
+            .find(|layer| layer.overrides.overrides_field(&path))
+    }
+
+    /// Applies all the layers in order, recording their outcome in the `report`
+    pub fn apply_override(&self, target: &mut dyn Reflect, report: &mut PrefabOverrideReport) {
+        for layer in &self.0 {
+            let mut reporter =
+                report.reporter(layer.source.clone(), PrefabEntityPath::root(), None);
+            layer.overrides.apply_override(target, &mut reporter);
+        }
+    }
+
+    /// Combines all layers into a single override with the same end result
+    pub fn flatten(&self) -> Option<Box<dyn Override>> {
+        let mut layers = self.0.iter();
+        let mut overrides = layers.next()?.overrides.clone();
+        for layer in layers {
+            overlay_override(&mut overrides, layer.overrides.clone());
+        }
+        Some(overrides)
+    }
+}
+
+impl MapEntities for PrefabOverrideLayers {
+    #[inline]
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        for layer in &mut self.0 {
+            layer.overrides.map_entities(entity_map)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct PrefabOverrideLayer {
+    /// Prefab file and instance that defined this layer
+    pub source: PrefabOverrideSource,
+    pub overrides: Box<dyn Override>,
+}
@@ -1,28 +1,95 @@
 use std::{
-    any::{type_name, TypeId},
-    collections::hash_map::Entry,
+    any::{type_name, Any, TypeId},
+    cell::RefCell,
+    collections::BTreeMap,
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bevy::{
     asset::Asset,
     ecs::entity::{Entity, EntityMap, MapEntities, MapEntitiesError},
     math::prelude::*,
-    prelude::{warn, Handle, Hsla, LinSrgba, Mesh, Srgba, StandardMaterial},
-    reflect::{Reflect, ReflectMut, ReflectRef, Struct},
-    utils::HashMap,
+    prelude::{warn, Color, Handle, Hsla, LinSrgba, Mesh, Srgba, StandardMaterial},
+    reflect::{DynamicStruct, Reflect, ReflectMut, ReflectRef, Struct},
+    utils::{HashMap, HashSet},
 };
 use serde::{
-    de::{self, DeserializeSeed, MapAccess, SeqAccess},
-    Deserialize,
+    de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess},
+    ser::{self, SerializeMap, SerializeStruct},
+    Deserialize, Serialize,
 };
 
+use crate::{registry::shorten_name, schema::FieldSchema};
+
+use super::{FieldConstraint, OverrideReporter, OverrideRules};
+
 ///////////////////////////////////////////////////////////////////////////////
 
-pub trait Override: Send + Sync + 'static {
-    fn apply_override(&self, target: &mut dyn Reflect);
+/// Downcasting support for [`Override`]s, implemented for every type
+pub trait OverrideAny {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any> OverrideAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+pub trait Override: OverrideAny + erased_serde::Serialize + Send + Sync + 'static {
+    /// Overwrites `target`, the outcome of every overridden value is recorded in the `report`
+    fn apply_override(&self, target: &mut dyn Reflect, report: &mut OverrideReporter);
     fn map_overwritten_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError>;
     fn clone_as_boxed_override(&self) -> Box<dyn Override>;
+
+    /// Tells if the field addressed by `path` is changed by this override,
+    /// overrides that replace the whole value also override all of it's fields
+    fn overrides_field(&self, path: &[&str]) -> bool {
+        let _ = path;
+        true
+    }
+
+    /// Combines `other` on top of this override, giving it back if they can't be combined
+    fn overlay(&mut self, other: Box<dyn Override>) -> Option<Box<dyn Override>> {
+        Some(other)
+    }
+
+    /// Tells if the override holds an empty value, such as a [`Handle`] that points to no asset
+    fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// The whole value this override replaces the target with, `None` when only some fields are overridden
+    fn as_reflect(&self) -> Option<&dyn Reflect> {
+        None
+    }
+
+    /// Tells if values of this type can ever be empty, see [`Override::is_empty`]
+    fn can_be_empty() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+}
+
+erased_serde::serialize_trait_object!(Override);
+
+/// Applies `other` on top of `target`, replacing it when they can't be combined
+pub(crate) fn overlay_override(target: &mut Box<dyn Override>, other: Box<dyn Override>) {
+    if let Some(other) = target.overlay(other) {
+        *target = other;
+    }
 }
 
 impl Clone for Box<dyn Override> {
@@ -40,7 +107,11 @@ impl MapEntities for Box<dyn Override> {
 }
 
 impl Override for () {
-    fn apply_override(&self, _: &mut dyn Reflect) {}
+    fn apply_override(&self, _: &mut dyn Reflect, _: &mut OverrideReporter) {}
+
+    fn overrides_field(&self, _: &[&str]) -> bool {
+        false
+    }
 
     fn map_overwritten_entities(&mut self, _: &EntityMap) -> Result<(), MapEntitiesError> {
         Ok(())
@@ -54,16 +125,12 @@ impl Override for () {
 macro_rules! primitive_data_override {
     ($t:ty) => {
         impl Override for $t {
-            fn apply_override(&self, target: &mut dyn Reflect) {
+            fn apply_override(&self, target: &mut dyn Reflect, report: &mut OverrideReporter) {
                 if let Some(target) = target.downcast_mut::<$t>() {
                     *target = *self;
+                    report.applied();
                 } else {
-                    // TODO: apply_override warnings need a better source identification
-                    warn!(
-                        "`{}` can't be overwritten by `{}`",
-                        target.type_name(),
-                        stringify!($t)
-                    );
+                    report.mismatched(target.type_name(), stringify!($t));
                 }
             }
 
@@ -74,10 +141,15 @@ macro_rules! primitive_data_override {
             fn clone_as_boxed_override(&self) -> Box<dyn Override> {
                 Box::new(*self)
             }
+
+            fn as_reflect(&self) -> Option<&dyn Reflect> {
+                Some(self)
+            }
         }
     };
 }
 
+primitive_data_override!(bool);
 primitive_data_override!(u8);
 primitive_data_override!(i8);
 primitive_data_override!(u16);
@@ -90,11 +162,12 @@ primitive_data_override!(f32);
 primitive_data_override!(f64);
 
 impl Override for Entity {
-    fn apply_override(&self, target: &mut dyn Reflect) {
+    fn apply_override(&self, target: &mut dyn Reflect, report: &mut OverrideReporter) {
         if let Some(target) = target.downcast_mut::<Entity>() {
             *target = *self;
+            report.applied();
         } else {
-            warn!("`{}` can't be overwritten by `Entity`", target.type_name());
+            report.mismatched(target.type_name(), "Entity");
         }
     }
 
@@ -106,18 +179,19 @@ impl Override for Entity {
     fn clone_as_boxed_override(&self) -> Box<dyn Override> {
         Box::new(*self)
     }
+
+    fn as_reflect(&self) -> Option<&dyn Reflect> {
+        Some(self)
+    }
 }
 
 impl<T: Asset> Override for Handle<T> {
-    fn apply_override(&self, target: &mut dyn Reflect) {
+    fn apply_override(&self, target: &mut dyn Reflect, report: &mut OverrideReporter) {
         if let Some(target) = target.downcast_mut::<Handle<T>>() {
             *target = self.clone();
+            report.applied();
         } else {
-            warn!(
-                "`{}` can't be overwritten by `Handle<{}>`",
-                target.type_name(),
-                type_name::<T>()
-            );
+            report.mismatched(target.type_name(), &format!("Handle<{}>", type_name::<T>()));
         }
     }
 
@@ -128,6 +202,70 @@ impl<T: Asset> Override for Handle<T> {
     fn clone_as_boxed_override(&self) -> Box<dyn Override> {
         Box::new(self.clone())
     }
+
+    fn as_reflect(&self) -> Option<&dyn Reflect> {
+        Some(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Handle::default()
+    }
+
+    fn can_be_empty() -> bool {
+        true
+    }
+}
+
+/// Replaces the whole value by a copy of the reflected value, it's used by overrides
+/// created from code, such as [`crate::diff::diff_prefab_instance`], and by the types
+/// registered with [`OverrideRegistry::register_reflect_value`]
+pub struct ReflectValueOverride(pub(crate) Box<dyn Reflect>);
+
+impl Clone for ReflectValueOverride {
+    fn clone(&self) -> Self {
+        ReflectValueOverride(self.0.clone_value())
+    }
+}
+
+impl Serialize for ReflectValueOverride {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.0.serializable() {
+            Some(serializable) => erased_serde::serialize(serializable.borrow(), serializer),
+            None => Err(ser::Error::custom(format!(
+                "`{}` isn't serializable",
+                self.0.type_name()
+            ))),
+        }
+    }
+}
+
+impl Override for ReflectValueOverride {
+    fn apply_override(&self, target: &mut dyn Reflect, report: &mut OverrideReporter) {
+        if target.any().type_id() == self.0.any().type_id() {
+            target.apply(&*self.0);
+            report.applied();
+        } else {
+            report.mismatched(target.type_name(), self.0.type_name());
+        }
+    }
+
+    fn map_overwritten_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        if let Some(entity) = self.0.any_mut().downcast_mut::<Entity>() {
+            *entity = entity_map.get(*entity)?;
+        }
+        Ok(())
+    }
+
+    fn clone_as_boxed_override(&self) -> Box<dyn Override> {
+        Box::new(self.clone())
+    }
+
+    fn as_reflect(&self) -> Option<&dyn Reflect> {
+        Some(&*self.0)
+    }
 }
 
 macro_rules! vector_data_override {
@@ -190,20 +328,35 @@ macro_rules! vector_data_override {
             }
         }
 
+        impl Serialize for $override {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                // only the fields overridden are written
+                let len = 0 $( + self.$field.is_some() as usize )*;
+                let mut state = serializer.serialize_struct(stringify!($override), len)?;
+                $(
+                    match &self.$field {
+                        Some(value) => state.serialize_field(stringify!($field), value)?,
+                        None => state.skip_field(stringify!($field))?,
+                    }
+                )*
+                state.end()
+            }
+        }
+
         impl Override for $override {
-            fn apply_override(&self, target: &mut dyn Reflect) {
+            fn apply_override(&self, target: &mut dyn Reflect, report: &mut OverrideReporter) {
                 if let Some(target) = target.downcast_mut::<$base>() {
                     $(
                         if let Some($field) = self.$field {
                             target.$field = $field;
+                            report.field(stringify!($field), |report| report.applied());
                         }
                     )*
                 } else {
-                    warn!(
-                        "`{}` can't be overwritten by `{}`",
-                        target.type_name(),
-                        stringify!($override)
-                    );
+                    report.mismatched(target.type_name(), stringify!($override));
                 }
             }
 
@@ -214,6 +367,33 @@ macro_rules! vector_data_override {
             fn clone_as_boxed_override(&self) -> Box<dyn Override> {
                 Box::new(self.clone())
             }
+
+            fn overlay(&mut self, other: Box<dyn Override>) -> Option<Box<dyn Override>> {
+                if !(*other).as_any().is::<$override>() {
+                    return Some(other);
+                }
+                let other = other.into_any().downcast::<$override>().unwrap();
+                $(
+                    if other.$field.is_some() {
+                        self.$field = other.$field;
+                    }
+                )*
+                None
+            }
+
+            fn overrides_field(&self, path: &[&str]) -> bool {
+                match path.split_first() {
+                    None => false $( || self.$field.is_some() )*,
+                    Some((name, _)) => {
+                        $(
+                            if *name == stringify!($field) {
+                                return self.$field.is_some();
+                            }
+                        )*
+                        false
+                    }
+                }
+            }
         }
     };
 }
@@ -247,6 +427,7 @@ vector_data_override!(Vec3, Vec3Override, x, y, z,);
 vector_data_override!(Vec4, Vec4Override, x, y, z, w,);
 primitive_data_override!(Quat);
 
+primitive_data_override!(Color);
 primitive_data_override!(LinSrgba);
 primitive_data_override!(Srgba);
 primitive_data_override!(Hsla);
@@ -257,17 +438,71 @@ primitive_data_override!(Hsla);
 pub enum OverrideDescriptor {
     Field(FieldOverrideDescriptor),
     Struct(StructOverrideDescriptor),
+    List(ListOverrideDescriptor),
 }
 
 impl OverrideDescriptor {
     pub fn blank() -> Self {
         OverrideDescriptor::Field(FieldOverrideDescriptor {
+            type_name: "()",
             de: |deserializer| {
                 de::IgnoredAny::deserialize(deserializer)?;
                 Ok(Box::new(()))
             },
+            supports: |_| false,
+            constraints: vec![],
         })
     }
+
+    /// Describes what can be overridden, type names are shortened
+    pub fn schema(&self) -> FieldSchema {
+        match self {
+            OverrideDescriptor::Field(field_descriptor) => FieldSchema::Value {
+                type_name: shorten_name(field_descriptor.type_name),
+                constraints: field_descriptor.constraints.clone(),
+            },
+            OverrideDescriptor::Struct(struct_descriptor) => FieldSchema::Struct {
+                type_name: shorten_name(&struct_descriptor.type_name),
+                fields: struct_descriptor
+                    .fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), field.schema()))
+                    .collect(),
+            },
+            OverrideDescriptor::List(list_descriptor) => FieldSchema::List {
+                type_name: shorten_name(list_descriptor.type_name),
+                element: Box::new(list_descriptor.element.schema()),
+            },
+        }
+    }
+
+    /// Adds `constraints` to the overridden values, fails for structs because their fields
+    /// must be constrained individually and for values the constraints can't check
+    fn constrain(&mut self, constraints: &[FieldConstraint]) -> Result<(), String> {
+        match self {
+            OverrideDescriptor::Field(field_descriptor) => {
+                if let Some(constraint) = constraints
+                    .iter()
+                    .find(|constraint| !(field_descriptor.supports)(constraint))
+                {
+                    return Err(format!(
+                        "`{:?}` can't check values of `{}`",
+                        constraint, field_descriptor.type_name
+                    ));
+                }
+                field_descriptor
+                    .constraints
+                    .extend(constraints.iter().cloned());
+                Ok(())
+            }
+            OverrideDescriptor::Struct(_) => {
+                Err("struct fields must be constrained by their own type".to_string())
+            }
+            OverrideDescriptor::List(list_descriptor) => {
+                list_descriptor.element.constrain(constraints)
+            }
+        }
+    }
 }
 
 impl<'a, 'de> DeserializeSeed<'de> for &'a OverrideDescriptor {
@@ -278,24 +513,78 @@ impl<'a, 'de> DeserializeSeed<'de> for &'a OverrideDescriptor {
         D: serde::Deserializer<'de>,
     {
         match self {
-            OverrideDescriptor::Field(field_overrides) => {
-                let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
-                (field_overrides.de)(&mut deserializer).map_err(de::Error::custom)
-            }
+            OverrideDescriptor::Field(field_overrides) => field_overrides.deserialize(deserializer),
             OverrideDescriptor::Struct(struct_overrides) => {
-                deserializer.deserialize_struct("StructOverrides", &[], struct_overrides)
+                if deserializer.is_human_readable() {
+                    // accepts `( field: .. )`, `{ "field.inner": .. }` and `()` forms
+                    deserializer.deserialize_any(struct_overrides)
+                } else {
+                    deserializer.deserialize_struct("StructOverrides", &[], struct_overrides)
+                }
+            }
+            OverrideDescriptor::List(list_overrides) => {
+                deserializer.deserialize_map(list_overrides)
             }
         }
     }
 }
 
+/// Struct fields must be serialized with `'static` names, but overrides only know
+/// them at runtime; Each name is leaked once per thread and reused from there on
+pub(crate) fn static_field_name(name: &str) -> &'static str {
+    thread_local! {
+        static FIELD_NAMES: RefCell<HashSet<&'static str>> = RefCell::new(HashSet::default());
+    }
+
+    FIELD_NAMES.with(|field_names| {
+        let mut field_names = field_names.borrow_mut();
+        match field_names.get(name) {
+            Some(field_name) => *field_name,
+            None => {
+                let field_name: &'static str = Box::leak(name.to_string().into_boxed_str());
+                field_names.insert(field_name);
+                field_name
+            }
+        }
+    })
+}
+
+/// Splits a field path like `"spawn_points[2].x"` into `["spawn_points", "2", "x"]`
+pub(crate) fn split_field_path(path: &str) -> Vec<&str> {
+    path.split(|c| c == '.' || c == '[' || c == ']')
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct FieldOverrideDescriptor {
+    type_name: &'static str,
     de: fn(&mut dyn erased_serde::Deserializer) -> Result<Box<dyn Override>>,
+    /// Tells which constraints can check the deserialized values
+    supports: fn(&FieldConstraint) -> bool,
+    /// Checked every time a value is deserialized
+    constraints: Vec<FieldConstraint>,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for &'a FieldOverrideDescriptor {
+    type Value = Box<dyn Override>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        let value = (self.de)(&mut deserializer).map_err(de::Error::custom)?;
+        for constraint in &self.constraints {
+            constraint.check(&*value).map_err(de::Error::custom)?;
+        }
+        Ok(value)
+    }
 }
 
 #[derive(Clone)]
 pub struct StructOverrideDescriptor {
+    type_name: String,
     fields: HashMap<String, OverrideDescriptor>,
 }
 
@@ -306,6 +595,16 @@ impl<'a, 'de> de::Visitor<'de> for &'a StructOverrideDescriptor {
         formatter.write_str("an struct")
     }
 
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        // `()` overrides nothing
+        Ok(Box::new(StructOverride {
+            fields: Default::default(),
+        }))
+    }
+
     fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
     where
         A: MapAccess<'de>,
@@ -341,7 +640,9 @@ impl<'a, 'de> de::Visitor<'de> for &'a StructOverrideDescriptor {
             where
                 D: serde::Deserializer<'de>,
             {
-                deserializer.deserialize_identifier(IdentifierVisitor)
+                // map keys are strings while struct keys are identifiers,
+                // identifiers are still deserialized as strings by self describing formats
+                deserializer.deserialize_str(IdentifierVisitor)
             }
         }
 
@@ -350,46 +651,215 @@ impl<'a, 'de> de::Visitor<'de> for &'a StructOverrideDescriptor {
         };
 
         while let Some(Identifier(key)) = map.next_key()? {
-            let descriptor = self
+            let path = split_field_path(&key);
+            let (name, rest) = path
+                .split_first()
+                .ok_or_else(|| de::Error::invalid_length(0, &"a field path"))?;
+
+            let field_descriptor = self
                 .fields
-                .get(&key)
-                .ok_or_else(|| de::Error::unknown_field(key.as_str(), &[]))?;
-
-            match overrides.fields.entry(key) {
-                Entry::Occupied(occupied) => {
-                    return Err(de::Error::custom(format!(
-                        "duplicate field `{}`",
-                        occupied.key()
-                    )));
-                }
-                Entry::Vacant(vacant) => {
-                    vacant.insert(map.next_value_seed(descriptor)?);
-                }
+                .get(*name)
+                .ok_or_else(|| de::Error::unknown_field(name, &[]))?;
+
+            // dotted keys like `"light.color.r"` are resolved against the nested descriptors
+            let mut descriptor = field_descriptor;
+            let mut nested_len = 0;
+            for segment in rest {
+                descriptor = match descriptor {
+                    OverrideDescriptor::Struct(struct_descriptor) => struct_descriptor
+                        .fields
+                        .get(*segment)
+                        .ok_or_else(|| de::Error::unknown_field(segment, &[]))?,
+                    OverrideDescriptor::List(list_descriptor) => {
+                        segment.parse::<usize>().map_err(|_| {
+                            de::Error::custom(format!("expected list index, found `{}`", segment))
+                        })?;
+                        &list_descriptor.element
+                    }
+                    // remaining segments are left to the field override itself, e.g. `"position.x"`
+                    OverrideDescriptor::Field(_) => break,
+                };
+                nested_len += 1;
             }
+            let (nested, field_path) = rest.split_at(nested_len);
+
+            let mut value = map.next_value_seed(FieldPathSeed {
+                descriptor,
+                path: field_path,
+            })?;
+
+            // wrap the value, so it mirrors the nested form
+            for segment in nested.iter().rev() {
+                value = match segment.parse::<usize>() {
+                    Ok(index) => {
+                        let mut elements = BTreeMap::default();
+                        elements.insert(index, value);
+                        Box::new(ListOverride { elements })
+                    }
+                    Err(_) => {
+                        let mut fields = BTreeMap::default();
+                        fields.insert(segment.to_string(), value);
+                        Box::new(StructOverride { fields })
+                    }
+                };
+            }
+
+            overrides
+                .insert_field(name.to_string(), value)
+                .map_err(|field| de::Error::custom(format!("duplicate field `{}`", field)))?;
         }
         Ok(Box::new(overrides))
     }
 }
 
+/// Deserializes the value of a dotted key whose last segments are handled by a field override
+struct FieldPathSeed<'a> {
+    descriptor: &'a OverrideDescriptor,
+    path: &'a [&'a str],
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for FieldPathSeed<'a> {
+    type Value = Box<dyn Override>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match self.descriptor {
+            descriptor if self.path.is_empty() => descriptor.deserialize(deserializer),
+            // only field overrides are left with path segments to handle
+            OverrideDescriptor::Field(field_descriptor) => {
+                field_descriptor.deserialize(FieldPathDeserializer {
+                    path: self.path,
+                    inner: deserializer,
+                })
+            }
+            _ => Err(de::Error::custom("field path can't be resolved")),
+        }
+    }
+}
+
+/// Presents the value of `"x": 4` with the path `["x"]` as it's nested form `{ "x": 4 }`
+struct FieldPathDeserializer<'a, D> {
+    path: &'a [&'a str],
+    inner: D,
+}
+
+impl<'a, 'de, D> serde::Deserializer<'de> for FieldPathDeserializer<'a, D>
+where
+    D: serde::Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_map(FieldPathMapAccess {
+            path: self.path,
+            inner: Some(self.inner),
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct
+        enum identifier ignored_any
+    }
+}
+
+struct FieldPathMapAccess<'a, D> {
+    path: &'a [&'a str],
+    inner: Option<D>,
+}
+
+impl<'a, 'de, D> MapAccess<'de> for FieldPathMapAccess<'a, D>
+where
+    D: serde::Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.inner.is_none() {
+            return Ok(None);
+        }
+        seed.deserialize(self.path[0].into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let inner = self
+            .inner
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+
+        let rest = &self.path[1..];
+        if rest.is_empty() {
+            seed.deserialize(inner)
+        } else {
+            seed.deserialize(FieldPathDeserializer { path: rest, inner })
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct StructOverride {
-    fields: HashMap<String, Box<dyn Override>>,
+    /// Sorted by name, so saved overrides are written in a stable order
+    pub(crate) fields: BTreeMap<String, Box<dyn Override>>,
+}
+
+impl StructOverride {
+    /// Inserts a field override merging it with the existing one, returns
+    /// the path of the field overridden twice in case of conflict
+    pub(crate) fn insert_field(
+        &mut self,
+        name: String,
+        value: Box<dyn Override>,
+    ) -> Result<(), String> {
+        match self.fields.get_mut(&name) {
+            Some(existing) => {
+                merge_override(existing, value).map_err(|path| join_field_path(&name, &path))
+            }
+            None => {
+                self.fields.insert(name, value);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Serialize for StructOverride {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // written just like the `( field: .. )` form read by `StructOverrideDescriptor`
+        let mut state = serializer.serialize_struct("StructOverrides", self.fields.len())?;
+        for (name, value) in &self.fields {
+            state.serialize_field(static_field_name(name), value)?;
+        }
+        state.end()
+    }
 }
 
 impl Override for StructOverride {
-    fn apply_override(&self, target: &mut dyn Reflect) {
+    fn apply_override(&self, target: &mut dyn Reflect, report: &mut OverrideReporter) {
+        let type_name = target.type_name().to_string();
         match target.reflect_mut() {
             ReflectMut::Struct(target) => {
-                for i in 0..target.field_len() {
-                    if let Some(field_override) = self.fields.get(target.name_at(i).unwrap()) {
-                        field_override.apply_override(target.field_at_mut(i).unwrap());
-                    }
+                for (name, field_override) in &self.fields {
+                    report.field(name, |report| match target.field_mut(name) {
+                        Some(field) => field_override.apply_override(field, report),
+                        None => report.ignored(format!("`{}` has no such field", type_name)),
+                    });
                 }
             }
-            _ => warn!(
-                "`{}` can't be overwritten by `StructOverride`, only struct is supported",
-                target.type_name()
-            ),
+            _ => report.mismatched(&type_name, "StructOverride"),
         }
     }
 
@@ -403,23 +873,217 @@ impl Override for StructOverride {
     fn clone_as_boxed_override(&self) -> Box<dyn Override> {
         Box::new(self.clone())
     }
+
+    fn overlay(&mut self, other: Box<dyn Override>) -> Option<Box<dyn Override>> {
+        if !(*other).as_any().is::<StructOverride>() {
+            return Some(other);
+        }
+        let other = other.into_any().downcast::<StructOverride>().unwrap();
+        for (name, value) in other.fields {
+            match self.fields.get_mut(&name) {
+                Some(existing) => overlay_override(existing, value),
+                None => {
+                    self.fields.insert(name, value);
+                }
+            }
+        }
+        None
+    }
+
+    fn overrides_field(&self, path: &[&str]) -> bool {
+        match path.split_first() {
+            None => !self.fields.is_empty(),
+            Some((name, rest)) => self
+                .fields
+                .get(*name)
+                .map_or(false, |field_override| field_override.overrides_field(rest)),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone)]
+pub struct ListOverrideDescriptor {
+    type_name: &'static str,
+    element: Box<OverrideDescriptor>,
+}
+
+impl<'a, 'de> de::Visitor<'de> for &'a ListOverrideDescriptor {
+    type Value = Box<dyn Override>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a map of list indices")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut overrides = ListOverride {
+            elements: Default::default(),
+        };
+
+        while let Some(index) = map.next_key::<usize>()? {
+            let value = map.next_value_seed(&*self.element)?;
+            overrides
+                .insert_element(index, value)
+                .map_err(|path| de::Error::custom(format!("duplicate element `{}`", path)))?;
+        }
+        Ok(Box::new(overrides))
+    }
+}
+
+/// Overrides elements of a list by their index
+#[derive(Clone)]
+pub struct ListOverride {
+    pub(crate) elements: BTreeMap<usize, Box<dyn Override>>,
+}
+
+impl ListOverride {
+    /// Inserts an element override merging it with the existing one, returns
+    /// the path of the element overridden twice in case of conflict
+    pub(crate) fn insert_element(
+        &mut self,
+        index: usize,
+        value: Box<dyn Override>,
+    ) -> Result<(), String> {
+        match self.elements.get_mut(&index) {
+            Some(existing) => merge_override(existing, value)
+                .map_err(|path| join_field_path(&index.to_string(), &path)),
+            None => {
+                self.elements.insert(index, value);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Serialize for ListOverride {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut state = serializer.serialize_map(Some(self.elements.len()))?;
+        for (index, value) in &self.elements {
+            state.serialize_entry(index, value)?;
+        }
+        state.end()
+    }
+}
+
+impl Override for ListOverride {
+    fn apply_override(&self, target: &mut dyn Reflect, report: &mut OverrideReporter) {
+        let type_name = target.type_name().to_string();
+        match target.reflect_mut() {
+            ReflectMut::List(target) => {
+                for (index, element_override) in &self.elements {
+                    let len = target.len();
+                    report.field(&index.to_string(), |report| match target.get_mut(*index) {
+                        Some(element) => element_override.apply_override(element, report),
+                        None => report.ignored(format!(
+                            "out of bounds of `{}` with length `{}`",
+                            type_name, len
+                        )),
+                    });
+                }
+            }
+            _ => report.mismatched(&type_name, "ListOverride"),
+        }
+    }
+
+    fn map_overwritten_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        for (_, v) in &mut self.elements {
+            v.map_entities(entity_map)?;
+        }
+        Ok(())
+    }
+
+    fn clone_as_boxed_override(&self) -> Box<dyn Override> {
+        Box::new(self.clone())
+    }
+
+    fn overlay(&mut self, other: Box<dyn Override>) -> Option<Box<dyn Override>> {
+        if !(*other).as_any().is::<ListOverride>() {
+            return Some(other);
+        }
+        let other = other.into_any().downcast::<ListOverride>().unwrap();
+        for (index, value) in other.elements {
+            match self.elements.get_mut(&index) {
+                Some(existing) => overlay_override(existing, value),
+                None => {
+                    self.elements.insert(index, value);
+                }
+            }
+        }
+        None
+    }
+
+    fn overrides_field(&self, path: &[&str]) -> bool {
+        match path.split_first() {
+            None => !self.elements.is_empty(),
+            Some((index, rest)) => index
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| self.elements.get(&index))
+                .map_or(false, |element_override| {
+                    element_override.overrides_field(rest)
+                }),
+        }
+    }
+}
+
+/// Merges struct and list overrides together, fails with the conflicting path
+/// when both overrides change the same value
+fn merge_override(target: &mut Box<dyn Override>, other: Box<dyn Override>) -> Result<(), String> {
+    if let Some(target) = (**target).as_any_mut().downcast_mut::<StructOverride>() {
+        if (*other).as_any().is::<StructOverride>() {
+            let other = other.into_any().downcast::<StructOverride>().unwrap();
+            for (name, value) in other.fields {
+                target.insert_field(name, value)?;
+            }
+            return Ok(());
+        }
+    } else if let Some(target) = (**target).as_any_mut().downcast_mut::<ListOverride>() {
+        if (*other).as_any().is::<ListOverride>() {
+            let other = other.into_any().downcast::<ListOverride>().unwrap();
+            for (index, value) in other.elements {
+                target.insert_element(index, value)?;
+            }
+            return Ok(());
+        }
+    }
+
+    Err(String::new())
+}
+
+fn join_field_path(name: &str, rest: &str) -> String {
+    if rest.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", name, rest)
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
 /// Creates override descriptors that can be used to deserialize and override structs
+#[derive(Clone)]
 pub struct OverrideRegistry {
     // TODO: also support uuid lookup in order to support scripting, see src/registry/mod.rs to see an impl example
     registry: HashMap<TypeId, OverrideDescriptor>,
+    rules: HashMap<TypeId, OverrideRules>,
 }
 
 impl Default for OverrideRegistry {
     fn default() -> Self {
         let mut registry = Self {
             registry: Default::default(),
+            rules: Default::default(),
         };
 
         // primitive
+        registry.register::<bool, bool>();
         registry.register::<u8, u8>();
         registry.register::<i8, i8>();
         registry.register::<u16, u16>();
@@ -430,6 +1094,7 @@ impl Default for OverrideRegistry {
         registry.register::<i64, i64>();
         registry.register::<f32, f32>();
         registry.register::<f64, f64>();
+        registry.register_reflect_value::<String>();
         // entity
         registry.register::<Entity, Entity>();
         // vector types
@@ -438,12 +1103,21 @@ impl Default for OverrideRegistry {
         registry.register::<Vec4, Vec4Override>();
         registry.register::<Quat, Quat>();
         // color types
+        registry.register::<Color, Color>();
         registry.register::<LinSrgba, LinSrgba>();
         registry.register::<Srgba, Srgba>();
         registry.register::<Hsla, Hsla>();
         // common handle types
         registry.register::<Handle<Mesh>, Handle<Mesh>>();
         registry.register::<Handle<StandardMaterial>, Handle<StandardMaterial>>();
+        // common list types
+        registry.register_vec::<f32>();
+        registry.register_vec::<Entity>();
+        registry.register_vec::<Vec2>();
+        registry.register_vec::<Vec3>();
+        registry.register_vec::<Vec4>();
+        registry.register_vec::<Quat>();
+        registry.register_vec::<Color>();
 
         registry
     }
@@ -465,18 +1139,111 @@ impl OverrideRegistry {
     {
         self.registry.entry(TypeId::of::<K>()).or_insert_with(|| {
             let descriptor = FieldOverrideDescriptor {
+                type_name: type_name::<K>(),
                 de: |deserializer| Ok(Box::new(T::deserialize(deserializer)?)),
+                supports: |constraint| constraint.supports::<K, T>(),
+                constraints: vec![],
             };
             OverrideDescriptor::Field(descriptor)
         });
     }
 
-    pub fn register_struct<T: Default + Struct>(&mut self) {
-        self.register_struct_from_value(&T::default());
+    /// Allows values of `T` to be overridden as a whole, like strings or enums, their
+    /// overrides are written just like the values
+    pub fn register_reflect_value<T>(&mut self)
+    where
+        T: Reflect + for<'de> Deserialize<'de>,
+    {
+        self.registry.entry(TypeId::of::<T>()).or_insert_with(|| {
+            let descriptor = FieldOverrideDescriptor {
+                type_name: type_name::<T>(),
+                de: |deserializer| {
+                    let value = T::deserialize(deserializer)?;
+                    Ok(Box::new(ReflectValueOverride(Box::new(value))))
+                },
+                supports: |constraint| constraint.supports::<T, ReflectValueOverride>(),
+                constraints: vec![],
+            };
+            OverrideDescriptor::Field(descriptor)
+        });
     }
 
-    pub fn register_struct_from_value(&mut self, value: &dyn Struct) {
+    /// Allows elements of `Vec<T>` to be overridden by index, `T` must be registered first
+    pub fn register_vec<T: 'static>(&mut self) {
+        if let Some(element) = self.find::<T>().cloned() {
+            self.registry
+                .entry(TypeId::of::<Vec<T>>())
+                .or_insert_with(|| {
+                    OverrideDescriptor::List(ListOverrideDescriptor {
+                        type_name: type_name::<Vec<T>>(),
+                        element: Box::new(element),
+                    })
+                });
+        } else {
+            warn!(
+                "`Vec<{}>` can't be overridden, because `{}` isn't registered",
+                type_name::<T>(),
+                type_name::<T>()
+            );
+        }
+    }
+
+    /// Sets the rules used by the struct `T`, must be called before `T` is registered
+    pub fn register_rules<T: 'static>(&mut self, rules: OverrideRules) {
+        self.rules.insert(TypeId::of::<T>(), rules);
+    }
+
+    /// Fails when the struct rules can't be followed, like constraints of unknown fields
+    pub fn register_struct<T: Default + Struct>(&mut self) -> Result<()> {
+        self.register_struct_from_value(&T::default())
+    }
+
+    pub fn register_struct_from_value(&mut self, value: &dyn Struct) -> Result<()> {
+        let rules = self
+            .rules
+            .get(&value.type_id())
+            .cloned()
+            .unwrap_or_default();
+
+        let struct_descriptor = self.describe_struct(value, &rules)?;
+        self.registry.insert(
+            value.type_id(),
+            OverrideDescriptor::Struct(struct_descriptor),
+        );
+        Ok(())
+    }
+
+    /// Descriptor of a struct only known at runtime, it isn't kept by the registry
+    /// because every [`DynamicStruct`] shares the same type
+    pub fn describe_dynamic_struct(
+        &mut self,
+        value: &DynamicStruct,
+        rules: &OverrideRules,
+    ) -> Result<OverrideDescriptor> {
+        Ok(OverrideDescriptor::Struct(
+            self.describe_struct(value, rules)?,
+        ))
+    }
+
+    fn describe_struct(
+        &mut self,
+        value: &dyn Struct,
+        rules: &OverrideRules,
+    ) -> Result<StructOverrideDescriptor> {
+        // constraints are matched by field name, so a dotted path like `"light.range"` never matches
+        if let Some((name, _)) = rules
+            .iter_constraints()
+            .find(|(name, _)| value.field(name).is_none())
+        {
+            return Err(anyhow!(
+                "constraints of `{}` target the unknown field `{}`, nested fields must be constrained by their own type",
+                value.type_name(),
+                name,
+            ));
+        }
+
         let mut struct_descriptor = StructOverrideDescriptor {
+            type_name: value.type_name().to_string(),
             fields: Default::default(),
         };
 
@@ -484,17 +1251,20 @@ impl OverrideRegistry {
             let name = value.name_at(i).unwrap();
             let id = field.type_id();
 
-            // TODO: skip private fields
+            // `#[reflect(ignore)]` fields are already hidden from reflection
+            if rules.is_skipped(name) {
+                continue;
+            }
 
             let descriptor = if let Some(descriptor) = self.registry.get(&id) {
                 descriptor
             } else {
                 if let ReflectRef::Struct(inner_value) = field.reflect_ref() {
-                    self.register_struct_from_value(inner_value);
+                    self.register_struct_from_value(inner_value)?;
                     self.registry.get(&id).unwrap()
                 } else {
                     warn!(
-                        "field `{}` of `{}` doesn't support overriding, consider making the field private or registering it's type with `app.register_prefab_override::<{},{}>()`",
+                        "field `{}` of `{}` doesn't support overriding, consider skipping it with `#[reflect(ignore)]` or registering it's type with `app.register_prefab_override::<{},{}>()`",
                         name,
                         value.type_name(),
                         field.type_name(),
@@ -504,14 +1274,313 @@ impl OverrideRegistry {
                 }
             };
 
+            let mut descriptor = descriptor.clone();
+            let constraints = rules.constraints(name);
+            if !constraints.is_empty() {
+                descriptor.constrain(constraints).map_err(|err| {
+                    anyhow!(
+                        "invalid constraints of field `{}` of `{}`, {}",
+                        name,
+                        value.type_name(),
+                        err
+                    )
+                })?;
+            }
+
             struct_descriptor
                 .fields
-                .insert(name.to_string(), descriptor.clone());
+                .insert(name.to_string(), descriptor);
         }
 
-        self.registry.insert(
-            value.type_id(),
-            OverrideDescriptor::Struct(struct_descriptor),
+        Ok(struct_descriptor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        math::Vec3,
+        reflect::{Reflect, ReflectDeserialize},
+    };
+    use serde::{de::DeserializeSeed, Deserialize, Serialize};
+
+    use super::*;
+    use crate::data::{PrefabOverrideReport, PrefabOverrideStatus};
+
+    #[derive(Default, Reflect)]
+    struct Light {
+        intensity: f32,
+        range: f32,
+    }
+
+    #[derive(Default, Reflect)]
+    struct Lamp {
+        light: Light,
+        spawn_points: Vec<Vec3>,
+    }
+
+    fn overrides(input: &str) -> Result<Box<dyn Override>, ron::Error> {
+        let mut registry = OverrideRegistry::default();
+        registry.register_struct::<Lamp>().unwrap();
+        let descriptor = registry.find::<Lamp>().unwrap();
+        let mut deserializer = ron::de::Deserializer::from_str(input)?;
+        descriptor.deserialize(&mut deserializer)
+    }
+
+    fn apply(overrides: &dyn Override, target: &mut dyn Reflect) -> PrefabOverrideReport {
+        let mut report = PrefabOverrideReport::default();
+        overrides.apply_override(
+            target,
+            &mut report.reporter(Default::default(), Default::default(), None),
+        );
+        report
+    }
+
+    #[derive(Default, Reflect)]
+    struct Switch {
+        lamp: Lamp,
+        toggles: usize,
+    }
+
+    #[test]
+    fn skipped_fields() {
+        let mut registry = OverrideRegistry::default();
+        registry.register_rules::<Switch>(OverrideRules::default().skip("toggles"));
+        registry.register_struct::<Switch>().unwrap();
+
+        match registry.find::<Switch>().unwrap() {
+            OverrideDescriptor::Struct(descriptor) => {
+                assert!(descriptor.fields.contains_key("lamp"));
+                assert!(!descriptor.fields.contains_key("toggles"));
+            }
+            _ => panic!("expected a struct descriptor"),
+        }
+    }
+
+    #[test]
+    fn constrained_fields() {
+        let mut registry = OverrideRegistry::default();
+        registry.register_rules::<Light>(
+            OverrideRules::default()
+                .constrain("intensity", FieldConstraint::range(0.0, 10.0))
+                .constrain(
+                    "range",
+                    FieldConstraint::one_of(vec![Box::new(1.0f64), Box::new(2u8)]),
+                ),
+        );
+        registry.register_struct::<Lamp>().unwrap();
+        let descriptor = registry.find::<Lamp>().unwrap();
+        let overrides = |input: &str| {
+            let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+            descriptor.deserialize(&mut deserializer)
+        };
+
+        assert!(overrides(r#"{ "light.intensity": 5, "light.range": 2 }"#).is_ok());
+        assert!(overrides(r#"{ "light.intensity": -1 }"#).is_err());
+        assert!(overrides("( light: ( range: 3 ) )").is_err());
+
+        // constraints that can't be checked are rejected when the struct is registered
+        let rejected = |rules: OverrideRules| {
+            let mut registry = OverrideRegistry::default();
+            registry.register_rules::<Lamp>(rules);
+            registry.register_struct::<Lamp>().is_err()
+        };
+        assert!(rejected(
+            OverrideRules::default().constrain("spawn_points", FieldConstraint::range(0.0, 1.0))
+        ));
+        assert!(rejected(
+            OverrideRules::default().constrain("light", FieldConstraint::range(0.0, 1.0))
+        ));
+        assert!(rejected(
+            OverrideRules::default().constrain("light.range", FieldConstraint::range(0.0, 1.0))
+        ));
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+    #[reflect_value(PartialEq, Serialize, Deserialize)]
+    enum Mode {
+        Steady,
+        Blinking,
+        Off,
+    }
+
+    #[derive(Reflect)]
+    struct Bulb {
+        name: String,
+        mode: Mode,
+    }
+
+    impl Default for Bulb {
+        fn default() -> Self {
+            Bulb {
+                name: String::new(),
+                mode: Mode::Steady,
+            }
+        }
+    }
+
+    #[test]
+    fn constrained_values() {
+        let rules = || {
+            OverrideRules::default()
+                .constrain(
+                    "name",
+                    FieldConstraint::one_of(vec![
+                        Box::new("warm".to_string()),
+                        Box::new("cold".to_string()),
+                    ]),
+                )
+                .constrain(
+                    "mode",
+                    FieldConstraint::one_of(vec![Box::new(Mode::Steady), Box::new(Mode::Blinking)]),
+                )
+        };
+        let mut registry = OverrideRegistry::default();
+        registry.register_reflect_value::<Mode>();
+        registry.register_rules::<Bulb>(rules());
+        registry.register_struct::<Bulb>().unwrap();
+        let descriptor = registry.find::<Bulb>().unwrap();
+        let overrides = |input: &str| {
+            let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+            descriptor.deserialize(&mut deserializer)
+        };
+
+        let bulb_overrides = overrides(r#"( name: "cold", mode: Blinking )"#).unwrap();
+        let mut bulb = Bulb::default();
+        apply(&*bulb_overrides, &mut bulb);
+        assert_eq!(bulb.name, "cold");
+        assert_eq!(bulb.mode, Mode::Blinking);
+
+        let err = overrides(r#"( name: "hot" )"#).err().unwrap().to_string();
+        assert!(err.contains(r#"`"hot"` isn't allowed, expected one of `["warm", "cold"]`"#));
+        assert!(overrides("( mode: Off )").is_err());
+
+        // values of another type can't be checked
+        let mut registry = OverrideRegistry::default();
+        registry.register_reflect_value::<Mode>();
+        registry.register_rules::<Bulb>(
+            OverrideRules::default()
+                .constrain("name", FieldConstraint::one_of(vec![Box::new(Mode::Off)])),
+        );
+        assert!(registry.register_struct::<Bulb>().is_err());
+    }
+
+    #[test]
+    fn dotted_paths() {
+        let lamp_overrides = overrides(
+            r#"{
+                "light": ( intensity: 2 ),
+                "light.range": 5,
+                "spawn_points[1].x": 4,
+            }"#,
+        )
+        .unwrap();
+
+        let mut lamp = Lamp {
+            spawn_points: vec![Vec3::ZERO, Vec3::ZERO],
+            ..Default::default()
+        };
+        apply(&*lamp_overrides, &mut lamp);
+
+        assert_eq!(lamp.light.intensity, 2.0);
+        assert_eq!(lamp.light.range, 5.0);
+        assert_eq!(lamp.spawn_points[1], Vec3::new(4.0, 0.0, 0.0));
+        assert!(lamp_overrides.overrides_field(&split_field_path("spawn_points[1].x")));
+        assert!(!lamp_overrides.overrides_field(&split_field_path("spawn_points[0]")));
+
+        // nested form is still supported
+        assert!(overrides("( light: ( range: 1 ) )").is_ok());
+        assert!(overrides(r#"{ "light.range": 1, "light": ( range: 2 ) }"#).is_err());
+    }
+
+    #[test]
+    fn unit_overrides() {
+        let lamp_overrides = overrides("()").unwrap();
+        assert!(!lamp_overrides.overrides_field(&[]));
+
+        let mut lamp = Lamp::default();
+        let report = apply(&*lamp_overrides, &mut lamp);
+        assert_eq!(report.applied().count(), 0);
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let round_trip = |input: &str| {
+            let output = ron::to_string(&overrides(input).unwrap()).unwrap();
+            assert!(
+                overrides(&output).is_ok(),
+                "`{}` can't be read back",
+                output
+            );
+            output
+        };
+
+        // written in the same form prefab files use
+        assert_eq!(round_trip("()"), "()");
+        assert_eq!(round_trip("( light: ( range: 5 ) )"), "(light:(range:5))");
+        assert_eq!(
+            round_trip("( spawn_points: { 1: ( y: 2 ) } )"),
+            "(spawn_points:{1:(y:2)})"
+        );
+
+        // fields are sorted by name, so the output doesn't change between runs
+        assert_eq!(
+            round_trip("( spawn_points: { 1: ( y: 2 ) }, light: ( range: 5, intensity: 1 ) )"),
+            "(light:(intensity:1,range:5),spawn_points:{1:(y:2)})"
+        );
+
+        let input = overrides(
+            r#"{
+                "light.range": 5,
+                "spawn_points[1]": ( y: 2 ),
+            }"#,
+        )
+        .unwrap();
+
+        let output = ron::to_string(&input).unwrap();
+        let output = overrides(&output).unwrap();
+
+        let mut lamp = Lamp {
+            spawn_points: vec![Vec3::ZERO, Vec3::ONE],
+            ..Default::default()
+        };
+        apply(&*output, &mut lamp);
+
+        assert_eq!(lamp.light.intensity, 0.0);
+        assert_eq!(lamp.light.range, 5.0);
+        assert_eq!(lamp.spawn_points[1], Vec3::new(1.0, 2.0, 1.0));
+    }
+
+    #[test]
+    fn report_outcome() {
+        let mut light = BTreeMap::default();
+        light.insert("range".to_string(), Box::new(5.0f32) as Box<dyn Override>);
+        let mut fields = BTreeMap::default();
+        fields.insert(
+            "light".to_string(),
+            Box::new(StructOverride { fields: light }) as Box<dyn Override>,
         );
+        fields.insert("spawn_points".to_string(), Box::new(1.0f32));
+        fields.insert("color".to_string(), Box::new(true));
+
+        let mut lamp = Lamp::default();
+        let report = apply(&StructOverride { fields }, &mut lamp);
+        assert_eq!(lamp.light.range, 5.0);
+
+        let applied = report.applied().collect::<Vec<_>>();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].field, "light.range");
+
+        let ignored = report.ignored().collect::<Vec<_>>();
+        assert_eq!(ignored.len(), 1);
+        assert_eq!(ignored[0].field, "color");
+
+        let mismatched = report.mismatched().collect::<Vec<_>>();
+        assert_eq!(mismatched.len(), 1);
+        assert_eq!(mismatched[0].field, "spawn_points");
+        assert!(matches!(
+            &mismatched[0].status,
+            PrefabOverrideStatus::Mismatched { found, .. } if found == "f32"
+        ));
     }
 }
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use bevy::prelude::warn;
+
+use crate::path::PrefabEntityPath;
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Where an override was written
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct PrefabOverrideSource {
+    /// Prefab file, when known
+    pub file: Option<String>,
+    /// Path to the overridden instance starting from the root of the prefab that defined the override
+    pub instance: PrefabEntityPath,
+}
+
+impl fmt::Display for PrefabOverrideSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "`{}` instance `{}`", file, self.instance),
+            None => write!(f, "instance `{}`", self.instance),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrefabOverrideStatus {
+    Applied,
+    /// Override had nothing to be applied to, e.g. a missing entity or field
+    Ignored(String),
+    /// Override type doesn't match the overridden value type
+    Mismatched {
+        expected: String,
+        found: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct PrefabOverrideReportEntry {
+    pub source: PrefabOverrideSource,
+    /// Overridden entity, relative to the instance root
+    pub entity: PrefabEntityPath,
+    /// Overridden component type name, `None` for prefab data overrides
+    pub component: Option<&'static str>,
+    /// Overridden field like `"light.intensity"`, empty when the whole value was overridden
+    pub field: String,
+    pub status: PrefabOverrideStatus,
+}
+
+/// Outcome of every override applied to a prefab instance, inserted on the root
+/// of instances that have any overrides
+#[derive(Default, Debug, Clone)]
+pub struct PrefabOverrideReport {
+    pub entries: Vec<PrefabOverrideReportEntry>,
+}
+
+impl PrefabOverrideReport {
+    pub fn applied(&self) -> impl Iterator<Item = &PrefabOverrideReportEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == PrefabOverrideStatus::Applied)
+    }
+
+    pub fn ignored(&self) -> impl Iterator<Item = &PrefabOverrideReportEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.status, PrefabOverrideStatus::Ignored(_)))
+    }
+
+    pub fn mismatched(&self) -> impl Iterator<Item = &PrefabOverrideReportEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.status, PrefabOverrideStatus::Mismatched { .. }))
+    }
+
+    /// Records the outcome of overrides targeting the `entity` (or one of it's `component`)
+    pub fn reporter(
+        &mut self,
+        source: PrefabOverrideSource,
+        entity: PrefabEntityPath,
+        component: Option<&'static str>,
+    ) -> OverrideReporter<'_> {
+        OverrideReporter {
+            report: self,
+            source,
+            entity,
+            component,
+            field: vec![],
+        }
+    }
+}
+
+/// Keeps track of the field being overridden, passed along to [`super::Override::apply_override`]
+pub struct OverrideReporter<'a> {
+    report: &'a mut PrefabOverrideReport,
+    source: PrefabOverrideSource,
+    entity: PrefabEntityPath,
+    component: Option<&'static str>,
+    field: Vec<String>,
+}
+
+impl<'a> OverrideReporter<'a> {
+    /// Reports everything done inside `f` under the field `name`
+    pub fn field<R>(&mut self, name: &str, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.field.push(name.to_string());
+        let r = f(self);
+        self.field.pop();
+        r
+    }
+
+    pub fn applied(&mut self) {
+        self.push(PrefabOverrideStatus::Applied);
+    }
+
+    pub fn ignored(&mut self, reason: impl Into<String>) {
+        let reason = reason.into();
+        warn!("override {} ignored, {}", self.location(), reason);
+        self.push(PrefabOverrideStatus::Ignored(reason));
+    }
+
+    pub fn mismatched(&mut self, expected: &str, found: &str) {
+        warn!(
+            "override {} ignored, `{}` can't be overwritten by `{}`",
+            self.location(),
+            expected,
+            found
+        );
+        self.push(PrefabOverrideStatus::Mismatched {
+            expected: expected.to_string(),
+            found: found.to_string(),
+        });
+    }
+
+    fn location(&self) -> String {
+        let mut location = self.source.to_string();
+        if !self.entity.is_root() {
+            location += &format!(" entity `{}`", self.entity);
+        }
+        if let Some(component) = self.component {
+            location += &format!(" component `{}`", component);
+        }
+        if !self.field.is_empty() {
+            location += &format!(" field `{}`", self.field.join("."));
+        }
+        location
+    }
+
+    fn push(&mut self, status: PrefabOverrideStatus) {
+        self.report.entries.push(PrefabOverrideReportEntry {
+            source: self.source.clone(),
+            entity: self.entity.clone(),
+            component: self.component,
+            field: self.field.join("."),
+            status,
+        });
+    }
+}
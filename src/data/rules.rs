@@ -0,0 +1,261 @@
+use std::{
+    any::{Any, TypeId},
+    fmt,
+};
+
+use bevy::{
+    reflect::Reflect,
+    utils::{HashMap, HashSet},
+};
+use serde::{
+    de::{self, Visitor},
+    ser, Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::Override;
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Tells how the fields of a struct can be overridden by prefab instances,
+/// see [`super::PrefabData::override_rules`]; Rules are serializable so tools
+/// can read the same constraints enforced when prefabs are loaded
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct OverrideRules {
+    skipped: HashSet<String>,
+    constraints: HashMap<String, Vec<FieldConstraint>>,
+}
+
+impl OverrideRules {
+    /// Leaves the `field` out of the override descriptor, meant for internal bookkeeping
+    /// fields that shouldn't be touched by prefab instances; Same as `#[reflect(ignore)]`
+    /// but the field is still visible to reflection
+    pub fn skip(mut self, field: &str) -> Self {
+        self.skipped.insert(field.to_string());
+        self
+    }
+
+    /// Rejects any override of `field` that doesn't respect the `constraint`,
+    /// constraints of list fields are checked against each element
+    pub fn constrain(mut self, field: &str, constraint: FieldConstraint) -> Self {
+        self.constraints
+            .entry(field.to_string())
+            .or_default()
+            .push(constraint);
+        self
+    }
+
+    pub fn is_skipped(&self, field: &str) -> bool {
+        self.skipped.contains(field)
+    }
+
+    pub fn constraints(&self, field: &str) -> &[FieldConstraint] {
+        self.constraints
+            .get(field)
+            .map_or(&[], |constraints| constraints.as_slice())
+    }
+
+    /// Every constrained field along with it's constraints
+    pub fn iter_constraints(&self) -> impl Iterator<Item = (&str, &[FieldConstraint])> {
+        self.constraints
+            .iter()
+            .map(|(field, constraints)| (field.as_str(), constraints.as_slice()))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FieldConstraint {
+    /// Number within `min..=max`
+    Range { min: f64, max: f64 },
+    /// Value equal to one of the values, compared with [`Reflect::reflect_partial_eq`],
+    /// numbers of any type are compared by value
+    OneOf(Vec<AllowedValue>),
+    /// Asset handle that points to an asset
+    NonEmptyHandle,
+}
+
+impl FieldConstraint {
+    pub fn range(min: f64, max: f64) -> Self {
+        FieldConstraint::Range { min, max }
+    }
+
+    pub fn one_of(values: Vec<Box<dyn Reflect>>) -> Self {
+        FieldConstraint::OneOf(values.into_iter().map(AllowedValue).collect())
+    }
+
+    /// Tells if overrides of type `T` for values of type `K` can be checked by this constraint
+    pub(crate) fn supports<K: 'static, T: Override>(&self) -> bool {
+        match self {
+            FieldConstraint::Range { .. } => is_number::<T>(),
+            FieldConstraint::OneOf(values) => values.iter().all(|AllowedValue(value)| {
+                if is_number::<K>() {
+                    reflect_number(&**value).is_some()
+                } else {
+                    value.any().type_id() == TypeId::of::<K>()
+                        && value.reflect_partial_eq(&**value).is_some()
+                }
+            }),
+            FieldConstraint::NonEmptyHandle => T::can_be_empty(),
+        }
+    }
+
+    /// Checks the override `value`, returning a message explaining why it was rejected
+    pub(crate) fn check(&self, value: &dyn Override) -> Result<(), String> {
+        match self {
+            FieldConstraint::Range { min, max } => {
+                let number = override_number(value)?;
+                if number < *min || number > *max {
+                    return Err(format!(
+                        "`{}` is out of range, expected a value within `{}..={}`",
+                        number, min, max
+                    ));
+                }
+            }
+            FieldConstraint::OneOf(values) => {
+                let value = value
+                    .as_reflect()
+                    .ok_or_else(|| "constraint only supports whole values".to_string())?;
+                if !values.iter().any(|allowed| allowed.matches(value)) {
+                    return Err(format!(
+                        "`{}` isn't allowed, expected one of `{:?}`",
+                        display_value(value),
+                        values
+                    ));
+                }
+            }
+            FieldConstraint::NonEmptyHandle => {
+                if value.is_empty() {
+                    return Err("asset handle can't be empty".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Value accepted by [`FieldConstraint::OneOf`], it's written as a plain value; Values read
+/// from data can only be booleans, numbers or strings
+pub struct AllowedValue(pub Box<dyn Reflect>);
+
+impl AllowedValue {
+    fn matches(&self, value: &dyn Reflect) -> bool {
+        match (reflect_number(&*self.0), reflect_number(value)) {
+            (Some(allowed), Some(number)) => allowed == number,
+            _ => self.0.reflect_partial_eq(value).unwrap_or(false),
+        }
+    }
+}
+
+impl Clone for AllowedValue {
+    fn clone(&self) -> Self {
+        AllowedValue(self.0.clone_value())
+    }
+}
+
+impl PartialEq for AllowedValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.matches(&*other.0)
+    }
+}
+
+impl fmt::Debug for AllowedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&display_value(&*self.0))
+    }
+}
+
+impl Serialize for AllowedValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0.serializable() {
+            Some(serializable) => erased_serde::serialize(serializable.borrow(), serializer),
+            None => Err(ser::Error::custom(format!(
+                "`{}` isn't serializable",
+                self.0.type_name()
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AllowedValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(AllowedValueVisitor)
+    }
+}
+
+struct AllowedValueVisitor;
+
+impl<'de> Visitor<'de> for AllowedValueVisitor {
+    type Value = AllowedValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a boolean, number or string")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(AllowedValue(Box::new(v)))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(AllowedValue(Box::new(v)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(AllowedValue(Box::new(v)))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(AllowedValue(Box::new(v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(AllowedValue(Box::new(v.to_string())))
+    }
+}
+
+/// Value written as ron, when it's serializable
+fn display_value(value: &dyn Reflect) -> String {
+    value
+        .serializable()
+        .and_then(|serializable| ron::ser::to_string(&serializable.borrow()).ok())
+        .unwrap_or_else(|| value.type_name().to_string())
+}
+
+macro_rules! number_from_any {
+    ($value:expr, $($t:ty),*) => {
+        $(
+            if let Some(number) = $value.downcast_ref::<$t>() {
+                return Some(*number as f64);
+            }
+        )*
+    };
+}
+
+macro_rules! is_number {
+    ($t:ident, $($number:ty),*) => {
+        false $( || TypeId::of::<$t>() == TypeId::of::<$number>() )*
+    };
+}
+
+fn is_number<T: 'static>() -> bool {
+    is_number!(T, f32, f64, u8, i8, u16, i16, u32, i32, u64, i64)
+}
+
+fn any_number(value: &dyn Any) -> Option<f64> {
+    number_from_any!(value, f32, f64, u8, i8, u16, i16, u32, i32, u64, i64);
+    None
+}
+
+fn reflect_number(value: &dyn Reflect) -> Option<f64> {
+    any_number(value.any())
+}
+
+fn override_number(value: &dyn Override) -> Result<f64, String> {
+    any_number(value.as_any()).ok_or_else(|| "constraint only supports numbers".to_string())
+}
@@ -7,16 +7,28 @@ use serde::{
     Deserializer,
 };
 
-use crate::registry::{ComponentDescriptor, ComponentDescriptorRegistry};
+use crate::{
+    data::{ComponentOverride, ComponentOverrideOp, PrefabComponentOverrides},
+    de::unknown_name,
+    path::PrefabEntityPath,
+    registry::{ComponentDescriptor, ComponentDescriptorRegistry, COMPONENT_REMOVAL},
+};
 
 ///////////////////////////////////////////////////////////////////////////////
 
+enum Identifier {
+    Component(ComponentDescriptor),
+    /// Component removal, only available for prefab instances; It's name is reserved
+    /// so no component can be registered with it
+    Remove,
+}
+
 struct ComponentIdentifier<'a> {
     component_registry: &'a ComponentDescriptorRegistry,
 }
 
 impl<'a, 'de> DeserializeSeed<'de> for ComponentIdentifier<'a> {
-    type Value = ComponentDescriptor;
+    type Value = Identifier;
 
     #[inline]
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
@@ -28,7 +40,7 @@ impl<'a, 'de> DeserializeSeed<'de> for ComponentIdentifier<'a> {
 }
 
 impl<'a, 'de> Visitor<'de> for ComponentIdentifier<'a> {
-    type Value = ComponentDescriptor;
+    type Value = Identifier;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         formatter.write_str("a registered `Component`")
@@ -38,17 +50,22 @@ impl<'a, 'de> Visitor<'de> for ComponentIdentifier<'a> {
     where
         E: de::Error,
     {
-        let ComponentIdentifier { component_registry } = self;
-        component_registry
+        if v == COMPONENT_REMOVAL {
+            return Ok(Identifier::Remove);
+        }
+
+        self.component_registry
             .find_by_name(v)
             .cloned()
-            .ok_or_else(|| de::Error::unknown_variant(v, &[]))
+            .map(Identifier::Component)
+            .ok_or_else(|| unknown_name(v))
     }
 }
 
 struct ComponentData<'a, 'w> {
     descriptor: ComponentDescriptor,
     entity_builder: &'a mut EntityMut<'w>,
+    component_overrides: Option<&'a mut PrefabComponentOverrides>,
 }
 
 impl<'a, 'w, 'de> DeserializeSeed<'de> for ComponentData<'a, 'w> {
@@ -61,18 +78,86 @@ impl<'a, 'w, 'de> DeserializeSeed<'de> for ComponentData<'a, 'w> {
         let ComponentData {
             descriptor,
             entity_builder,
+            component_overrides,
         } = self;
+
+        if let (Some(patch), Some(component_overrides)) = (&descriptor.patch, component_overrides) {
+            // only override the fields written
+            let value = patch.overrides.deserialize(deserializer)?;
+            component_overrides.0.push(ComponentOverride {
+                source: Default::default(),
+                path: PrefabEntityPath::root(),
+                op: ComponentOverrideOp::Patch {
+                    component: descriptor.name,
+                    apply: patch.apply.clone(),
+                    value,
+                },
+            });
+            return Ok(());
+        }
+
         let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
         (descriptor.de)(&mut deserializer, entity_builder).map_err(de::Error::custom)?;
         Ok(())
     }
 }
 
+/// `Remove(Component)` or `Remove(Component, "Path/To/Entity")`
+struct ComponentRemoval<'a> {
+    component_registry: &'a ComponentDescriptorRegistry,
+    component_overrides: &'a mut PrefabComponentOverrides,
+}
+
+impl<'a, 'de> Visitor<'de> for ComponentRemoval<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a registered `Component` followed by an optional entity path")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let descriptor = match seq.next_element_seed(ComponentIdentifier {
+            component_registry: self.component_registry,
+        })? {
+            Some(Identifier::Component(descriptor)) => descriptor,
+            _ => return Err(de::Error::invalid_length(0, &self)),
+        };
+
+        let path = seq.next_element::<PrefabEntityPath>()?.unwrap_or_default();
+
+        if seq.next_element::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(3, &self));
+        }
+
+        let remove = descriptor.remove.clone().ok_or_else(|| {
+            de::Error::custom(format!(
+                "`{}` only registered through reflection can't be removed",
+                descriptor.name
+            ))
+        })?;
+
+        self.component_overrides.0.push(ComponentOverride {
+            source: Default::default(),
+            path,
+            op: ComponentOverrideOp::Remove {
+                component: descriptor.name,
+                remove,
+            },
+        });
+
+        Ok(())
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 struct IdentifiedComponent<'a, 'w> {
     entity_builder: &'a mut EntityMut<'w>,
     component_registry: &'a ComponentDescriptorRegistry,
+    component_overrides: Option<&'a mut PrefabComponentOverrides>,
 }
 
 impl<'a, 'w, 'de> DeserializeSeed<'de> for IdentifiedComponent<'a, 'w> {
@@ -101,15 +186,30 @@ impl<'a, 'w, 'de> Visitor<'de> for IdentifiedComponent<'a, 'w> {
         let IdentifiedComponent {
             entity_builder,
             component_registry,
+            component_overrides,
         } = self;
-        let (descriptor, variant) =
+        let (identifier, variant) =
             data.variant_seed(ComponentIdentifier { component_registry })?;
 
-        // Should only be used if the Component is a enum
-        variant.newtype_variant_seed(ComponentData {
-            descriptor,
-            entity_builder,
-        })
+        match (identifier, component_overrides) {
+            (Identifier::Remove, Some(component_overrides)) => variant.tuple_variant(
+                2,
+                ComponentRemoval {
+                    component_registry,
+                    component_overrides,
+                },
+            ),
+            // Should only be used if the Component is a enum
+            (Identifier::Component(descriptor), component_overrides) => variant
+                .newtype_variant_seed(ComponentData {
+                    descriptor,
+                    entity_builder,
+                    component_overrides,
+                }),
+            (Identifier::Remove, None) => Err(de::Error::custom(
+                "components can only be removed by prefab instances",
+            )),
+        }
     }
 }
 
@@ -118,6 +218,9 @@ impl<'a, 'w, 'de> Visitor<'de> for IdentifiedComponent<'a, 'w> {
 pub(crate) struct IdentifiedComponentSeq<'a, 'w> {
     pub entity_builder: &'a mut EntityMut<'w>,
     pub component_registry: &'a ComponentDescriptorRegistry,
+    /// When available components that support partial overrides will be
+    /// deserialized as patches instead of being inserted
+    pub component_overrides: Option<&'a mut PrefabComponentOverrides>,
 }
 
 impl<'a, 'w, 'de> DeserializeSeed<'de> for IdentifiedComponentSeq<'a, 'w> {
@@ -146,11 +249,13 @@ impl<'a, 'w, 'de> Visitor<'de> for IdentifiedComponentSeq<'a, 'w> {
         let IdentifiedComponentSeq {
             entity_builder,
             component_registry,
+            mut component_overrides,
         } = self;
 
         while let Some(_) = seq.next_element_seed(IdentifiedComponent {
             entity_builder,
             component_registry,
+            component_overrides: component_overrides.as_deref_mut(),
         })? {
             // Do nothing, just deserialize all elements in the sequence
         }
@@ -161,15 +266,26 @@ impl<'a, 'w, 'de> Visitor<'de> for IdentifiedComponentSeq<'a, 'w> {
 
 #[cfg(test)]
 mod tests {
-    use bevy::ecs::world::World;
+    use std::any::type_name;
+
+    use bevy::{
+        ecs::{reflect::ReflectComponent, world::World},
+        reflect::{Reflect, ReflectDeserialize, TypeRegistry},
+    };
     use serde::Deserialize;
 
     use super::*;
-    use crate::registry::ComponentDescriptorRegistry;
+    use crate::registry::{ComponentDescriptorRegistry, ComponentEntityMapperRegistry};
 
     #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
     struct Name(String);
 
+    #[derive(Default, Debug, Deserialize, Clone, Reflect)]
+    #[reflect(Component, Deserialize)]
+    struct Tinted {
+        r: f32,
+    }
+
     #[test]
     fn read() {
         let mut component_registry = ComponentDescriptorRegistry::default();
@@ -185,6 +301,7 @@ mod tests {
         let visitor = IdentifiedComponent {
             entity_builder: &mut entity_builder,
             component_registry: &component_registry,
+            component_overrides: None,
         };
         visitor.deserialize(&mut deserializer).unwrap();
 
@@ -194,4 +311,72 @@ mod tests {
             Some(Name("Root".to_string()))
         );
     }
+
+    #[test]
+    fn removals() {
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register::<Name>("Name".to_string())
+            .unwrap();
+
+        // removal name can't be taken by a component
+        #[derive(Debug, Deserialize, Clone)]
+        struct Remove;
+        assert!(component_registry
+            .register::<Remove>("Remove".to_string())
+            .is_err());
+
+        let mut world = World::default();
+        let mut entity_builder = world.spawn();
+        let mut component_overrides = PrefabComponentOverrides::default();
+        let input = r#"[Remove(Name), Remove(Name, "Body/Light")]"#;
+
+        let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+        IdentifiedComponentSeq {
+            entity_builder: &mut entity_builder,
+            component_registry: &component_registry,
+            component_overrides: Some(&mut component_overrides),
+        }
+        .deserialize(&mut deserializer)
+        .unwrap();
+
+        assert_eq!(component_overrides.0.len(), 2);
+        assert!(component_overrides.0[0].path.is_root());
+        assert_eq!(
+            component_overrides.0[1].path,
+            PrefabEntityPath::parse("Body/Light")
+        );
+        for component_override in &component_overrides.0 {
+            assert!(matches!(
+                component_override.op,
+                ComponentOverrideOp::Remove { component, .. } if component == type_name::<Name>()
+            ));
+        }
+
+        // only prefab instances can remove components
+        let mut deserializer = ron::de::Deserializer::from_str("Remove(Name)").unwrap();
+        assert!(IdentifiedComponent {
+            entity_builder: &mut entity_builder,
+            component_registry: &component_registry,
+            component_overrides: None,
+        }
+        .deserialize(&mut deserializer)
+        .is_err());
+
+        // components only known through reflection can't be removed
+        let mut type_registry = TypeRegistry::default();
+        type_registry.register::<Tinted>();
+        component_registry.register_reflected(
+            &type_registry,
+            &mut ComponentEntityMapperRegistry::default(),
+        );
+        let mut deserializer = ron::de::Deserializer::from_str("Remove(Tinted)").unwrap();
+        assert!(IdentifiedComponent {
+            entity_builder: &mut entity_builder,
+            component_registry: &component_registry,
+            component_overrides: Some(&mut component_overrides),
+        }
+        .deserialize(&mut deserializer)
+        .is_err());
+    }
 }
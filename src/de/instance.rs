@@ -2,8 +2,12 @@ use std::fmt;
 
 use anyhow::Result;
 use bevy::{
-    ecs::{entity::EntityMap, world::World},
+    ecs::{
+        entity::{Entity, EntityMap},
+        world::World,
+    },
     prelude::{Handle, Parent},
+    reflect::Uuid,
 };
 use serde::{
     de::{self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor},
@@ -11,8 +15,12 @@ use serde::{
 };
 
 use crate::{
-    data::BoxedPrefabOverrides,
-    de::{component::IdentifiedComponentSeq, IdValidation},
+    data::{
+        BoxedPrefabOverrides, ComponentOverride, ComponentOverrideOp, Override,
+        PrefabComponentOverrides, PrefabOverrideLayer, PrefabOverrideLayers, PrefabOverrideSource,
+    },
+    de::{component::IdentifiedComponentSeq, unknown_name, IdValidation, PrefabVariant},
+    path::{PrefabEntityPath, PrefabEntityProxy},
     registry::{ComponentDescriptorRegistry, PrefabDescriptor, PrefabDescriptorRegistry},
     Prefab, PrefabConstruct, PrefabNotInstantiatedTag, PrefabTransformOverride, PrefabTypeUuid,
 };
@@ -61,7 +69,7 @@ impl<'a, 'de> Visitor<'de> for InstanceIdentifier<'a> {
                 if v == "Entity" {
                     Ok(Identifier::Entity)
                 } else {
-                    return Err(de::Error::unknown_variant(v, &[]));
+                    return Err(unknown_name(v));
                 }
             }
         }
@@ -70,8 +78,15 @@ impl<'a, 'de> Visitor<'de> for InstanceIdentifier<'a> {
 
 ///////////////////////////////////////////////////////////////////////////////
 
-const PREFAB_INSTANCE_FIELDS: &'static [&'static str] =
-    &["id", "source", "transform", "parent", "data"];
+const PREFAB_INSTANCE_FIELDS: &'static [&'static str] = &[
+    "id",
+    "source",
+    "parent",
+    "transform",
+    "overrides",
+    "components",
+    "entities",
+];
 
 struct PrefabInstanceDeserializer<'a> {
     id_validation: &'a mut IdValidation,
@@ -79,6 +94,8 @@ struct PrefabInstanceDeserializer<'a> {
     source_to_prefab: &'a mut EntityMap,
     descriptor: PrefabDescriptor,
     component_registry: &'a ComponentDescriptorRegistry,
+    prefab_registry: &'a PrefabDescriptorRegistry,
+    version: u32,
 }
 
 impl<'a, 'de> Visitor<'de> for PrefabInstanceDeserializer<'a> {
@@ -101,13 +118,16 @@ impl<'a, 'de> Visitor<'de> for PrefabInstanceDeserializer<'a> {
             Transform,
             Overrides,
             Components,
+            Entities,
         }
 
         let mut id = None;
         let mut source: Option<Handle<Prefab>> = None;
         let mut transform_override = None;
-        let mut parent = None;
+        let mut parent: Option<Option<PrefabEntityPath>> = None;
         let mut overrides = None;
+        let mut component_overrides = PrefabComponentOverrides::default();
+        let mut entity_overrides = vec![];
 
         let PrefabInstanceDeserializer {
             id_validation,
@@ -115,12 +135,17 @@ impl<'a, 'de> Visitor<'de> for PrefabInstanceDeserializer<'a> {
             source_to_prefab,
             descriptor,
             component_registry,
+            prefab_registry,
+            version,
         } = self;
 
-        let data_seed = PrefabInstanceDataOverrides { descriptor };
+        let data_seed = PrefabInstanceDataOverrides {
+            descriptor,
+            version,
+        };
 
         // spawn nested prefab instance entity
-        let mut prefab_instance = world.spawn();
+        let prefab_instance = world.spawn().id();
 
         while let Some(key) = access.next_key()? {
             match key {
@@ -160,8 +185,18 @@ impl<'a, 'de> Visitor<'de> for PrefabInstanceDeserializer<'a> {
                     overrides = Some(access.next_value_seed(&data_seed)?);
                 }
                 Field::Components => access.next_value_seed(IdentifiedComponentSeq {
-                    entity_builder: &mut prefab_instance,
+                    entity_builder: &mut world.entity_mut(prefab_instance),
+                    component_registry,
+                    component_overrides: Some(&mut component_overrides),
+                })?,
+                Field::Entities => access.next_value_seed(EntityOverrideMap {
+                    id_validation,
+                    world,
+                    source_to_prefab,
                     component_registry,
+                    prefab_registry,
+                    entity_overrides: &mut entity_overrides,
+                    version,
                 })?,
             }
         }
@@ -182,9 +217,63 @@ impl<'a, 'de> Visitor<'de> for PrefabInstanceDeserializer<'a> {
         let parent = parent.unwrap_or_default();
         let transform_override: PrefabTransformOverride = transform_override.unwrap_or_default();
 
-        let blank_entity = prefab_instance.id();
-        source_to_prefab.insert(id, blank_entity);
+        source_to_prefab.insert(id, prefab_instance);
+
+        for EntityOverride {
+            path,
+            carrier,
+            carrier_id,
+            data_overrides,
+            overrides,
+        } in entity_overrides
+        {
+            // carrier stands for the overridden entity in this prefab file
+            world.entity_mut(carrier).insert(PrefabEntityProxy {
+                root: None,
+                path: PrefabEntityPath::from_id(id, &path),
+            });
+
+            component_overrides.0.push(ComponentOverride {
+                source: Default::default(),
+                path: path.clone(),
+                op: ComponentOverrideOp::Insert {
+                    carrier: carrier_id,
+                },
+            });
+            if let Some((uuid, overrides)) = data_overrides {
+                // nested prefab instances will stack this layer on top of their own overrides
+                component_overrides.0.push(ComponentOverride {
+                    source: Default::default(),
+                    path: path.clone(),
+                    op: ComponentOverrideOp::Overrides {
+                        uuid,
+                        layer: PrefabOverrideLayer {
+                            source: PrefabOverrideSource {
+                                file: None,
+                                instance: PrefabEntityPath::from_id(id, &path),
+                            },
+                            overrides,
+                        },
+                    },
+                });
+            }
+            component_overrides
+                .0
+                .extend(
+                    overrides
+                        .0
+                        .into_iter()
+                        .map(|component_override| ComponentOverride {
+                            source: component_override.source,
+                            path: path.join(&component_override.path),
+                            op: component_override.op,
+                        }),
+                );
+        }
+
+        let parent = parent.map(|path| parent_id(id_validation, world, source_to_prefab, path));
 
+        let mut prefab_instance = world.entity_mut(prefab_instance);
         prefab_instance.insert_bundle((
             source.clone().unwrap_or_default(),
             transform_override,
@@ -193,14 +282,30 @@ impl<'a, 'de> Visitor<'de> for PrefabInstanceDeserializer<'a> {
 
         if !data_seed.descriptor.source_prefab_required {
             // source isn't available, insert construct function definition
-            prefab_instance.insert(PrefabConstruct(data_seed.descriptor.construct));
+            prefab_instance.insert(PrefabConstruct(data_seed.descriptor.construct.clone()));
         } else {
             // validate source type
             prefab_instance.insert(PrefabTypeUuid(data_seed.descriptor.uuid));
         }
 
         if let Some(overrides) = overrides {
-            prefab_instance.insert(overrides);
+            // first and innermost overrides layer
+            prefab_instance.insert(PrefabOverrideLayers(vec![PrefabOverrideLayer {
+                source: PrefabOverrideSource {
+                    file: None,
+                    instance: PrefabEntityPath::from_id(id, &PrefabEntityPath::root()),
+                },
+                overrides: overrides.clone(),
+            }]));
+            prefab_instance.insert(BoxedPrefabOverrides(overrides));
+        }
+
+        if !component_overrides.0.is_empty() {
+            for component_override in &mut component_overrides.0 {
+                component_override.source.instance =
+                    PrefabEntityPath::from_id(id, &PrefabEntityPath::root());
+            }
+            prefab_instance.insert(component_overrides);
         }
 
         // parent all nested prefabs (when needed)
@@ -214,23 +319,316 @@ impl<'a, 'de> Visitor<'de> for PrefabInstanceDeserializer<'a> {
     }
 }
 
+/// Source id of the parent entity, paths other than a single id are replaced
+/// by a [`PrefabEntityProxy`] that will be resolved once the prefab is spawned
+fn parent_id(
+    id_validation: &mut IdValidation,
+    world: &mut World,
+    source_to_prefab: &mut EntityMap,
+    path: PrefabEntityPath,
+) -> Entity {
+    if let Some(id) = path.single_id() {
+        return id;
+    }
+
+    let id = id_validation.generate_unique();
+    let proxy = world
+        .spawn()
+        .insert(PrefabEntityProxy { root: None, path })
+        .id();
+    source_to_prefab.insert(id, proxy);
+    id
+}
+
 struct PrefabInstanceDataOverrides {
     descriptor: PrefabDescriptor,
+    /// Prefab version the file was saved with
+    version: u32,
 }
 
 impl<'a, 'de> DeserializeSeed<'de> for &'a PrefabInstanceDataOverrides {
-    type Value = BoxedPrefabOverrides;
+    type Value = Box<dyn Override>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let PrefabInstanceDataOverrides { descriptor } = self;
+        let PrefabInstanceDataOverrides {
+            descriptor,
+            version,
+        } = self;
+
+        // overrides only have a few fields, migrations expect the whole data
+        if let Some((current, _)) = descriptor.migrations.last() {
+            if current > version {
+                return Err(de::Error::custom(format!(
+                    "overrides of `{}` instances saved with version `{}` can't be migrated to version `{}`, update them and the `version` by hand",
+                    descriptor.name, version, current
+                )));
+            }
+        }
+
         descriptor
             .overrides
             .deserialize(deserializer)
             .map_err(de::Error::custom)
-            .map(BoxedPrefabOverrides)
+    }
+}
+
+/// Prefab data overrides of a nested prefab instance, written with the prefab variant
+/// they are meant for like `LampPrefab(( light_strength: 2 ))`
+struct NestedDataOverrides<'a> {
+    prefab_registry: &'a PrefabDescriptorRegistry,
+    version: u32,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for NestedDataOverrides<'a> {
+    type Value = (Uuid, Box<dyn Override>);
+
+    #[inline]
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_enum("Prefab", &[], self)
+    }
+}
+
+impl<'a, 'de> Visitor<'de> for NestedDataOverrides<'a> {
+    type Value = (Uuid, Box<dyn Override>);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("prefab data overrides of a registered `Prefab`")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        let NestedDataOverrides {
+            prefab_registry,
+            version,
+        } = self;
+        let (descriptor, variant) = data.variant_seed(PrefabVariant { prefab_registry })?;
+        let overrides = variant.newtype_variant_seed(&PrefabInstanceDataOverrides {
+            descriptor: descriptor.clone(),
+            version,
+        })?;
+        Ok((descriptor.uuid, overrides))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Overrides of an entity inside the nested prefab instance
+struct EntityOverride {
+    /// Entity path relative to the nested prefab instance
+    path: PrefabEntityPath,
+    /// Holds the components to be inserted
+    carrier: Entity,
+    carrier_id: Entity,
+    /// Prefab data overrides and the prefab type they were written for
+    data_overrides: Option<(Uuid, Box<dyn Override>)>,
+    overrides: PrefabComponentOverrides,
+}
+
+struct EntityOverrideMap<'a> {
+    id_validation: &'a mut IdValidation,
+    world: &'a mut World,
+    source_to_prefab: &'a mut EntityMap,
+    component_registry: &'a ComponentDescriptorRegistry,
+    prefab_registry: &'a PrefabDescriptorRegistry,
+    entity_overrides: &'a mut Vec<EntityOverride>,
+    version: u32,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for EntityOverrideMap<'a> {
+    type Value = ();
+
+    #[inline]
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'a, 'de> Visitor<'de> for EntityOverrideMap<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of entity paths to `Entity` overrides")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let EntityOverrideMap {
+            id_validation,
+            world,
+            source_to_prefab,
+            component_registry,
+            prefab_registry,
+            entity_overrides,
+            version,
+        } = self;
+
+        while let Some(path) = access.next_key::<PrefabEntityPath>()? {
+            if path.is_root() {
+                return Err(de::Error::custom(
+                    "entity overrides can't target the prefab instance itself",
+                ));
+            }
+
+            let entity_override = access.next_value_seed(EntityOverrideDeserializer {
+                id_validation,
+                world,
+                source_to_prefab,
+                component_registry,
+                prefab_registry,
+                path,
+                version,
+            })?;
+            entity_overrides.push(entity_override);
+        }
+
+        Ok(())
+    }
+}
+
+const ENTITY_OVERRIDE_FIELDS: &'static [&'static str] =
+    &["id", "parent", "overrides", "components"];
+
+struct EntityOverrideDeserializer<'a> {
+    id_validation: &'a mut IdValidation,
+    world: &'a mut World,
+    source_to_prefab: &'a mut EntityMap,
+    component_registry: &'a ComponentDescriptorRegistry,
+    prefab_registry: &'a PrefabDescriptorRegistry,
+    path: PrefabEntityPath,
+    version: u32,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for EntityOverrideDeserializer<'a> {
+    type Value = EntityOverride;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("Entity", ENTITY_OVERRIDE_FIELDS, self)
+    }
+}
+
+impl<'a, 'de> Visitor<'de> for EntityOverrideDeserializer<'a> {
+    type Value = EntityOverride;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an `Entity` override")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "lowercase")]
+        enum Field {
+            Id,
+            Parent,
+            Overrides,
+            Components,
+        }
+
+        let EntityOverrideDeserializer {
+            id_validation,
+            world,
+            source_to_prefab,
+            component_registry,
+            prefab_registry,
+            path,
+            version,
+        } = self;
+
+        let mut id = None;
+        let mut parent: Option<Option<PrefabEntityPath>> = None;
+        let mut data_overrides = None;
+        let mut overrides = PrefabComponentOverrides::default();
+
+        let carrier = world.spawn().id();
+
+        while let Some(key) = access.next_key()? {
+            match key {
+                Field::Id => {
+                    if id.is_some() {
+                        return Err(de::Error::duplicate_field("id"));
+                    }
+                    let temp = access.next_value()?;
+                    if id_validation.validate(temp) {
+                        id = Some(temp);
+                    } else {
+                        return Err(de::Error::custom(format!("conflicting id `{}`", temp.id())));
+                    }
+                }
+                Field::Parent => {
+                    if parent.is_some() {
+                        return Err(de::Error::duplicate_field("parent"));
+                    }
+                    parent = Some(access.next_value()?);
+                }
+                Field::Overrides => {
+                    if data_overrides.is_some() {
+                        return Err(de::Error::duplicate_field("overrides"));
+                    }
+                    data_overrides = Some(access.next_value_seed(NestedDataOverrides {
+                        prefab_registry,
+                        version,
+                    })?);
+                }
+                Field::Components => access.next_value_seed(IdentifiedComponentSeq {
+                    entity_builder: &mut world.entity_mut(carrier),
+                    component_registry,
+                    component_overrides: Some(&mut overrides),
+                })?,
+            }
+        }
+
+        // carrier components are moved over the overridden entity once it's spawned
+        let carrier_entity = world.entity(carrier);
+        for component_id in carrier_entity.archetype().components() {
+            let descriptor = world
+                .components()
+                .get_info(component_id)
+                .and_then(|component_info| component_info.type_id())
+                .and_then(|type_id| component_registry.find_by_type(type_id));
+            if let Some(descriptor) = descriptor {
+                if descriptor.take.is_none() {
+                    return Err(de::Error::custom(format!(
+                        "`{}` only registered through reflection can't be written in entity overrides",
+                        descriptor.name
+                    )));
+                }
+            }
+        }
+
+        // the id can be used by other entities of this prefab to refer to the overridden entity
+        let carrier_id = id.unwrap_or_else(|| id_validation.generate_unique());
+        source_to_prefab.insert(carrier_id, carrier);
+
+        if let Some(parent) = parent.flatten() {
+            let parent = parent_id(id_validation, world, source_to_prefab, parent);
+            world.entity_mut(carrier).insert(Parent(parent));
+        }
+
+        Ok(EntityOverride {
+            path,
+            carrier,
+            carrier_id,
+            data_overrides,
+            overrides,
+        })
     }
 }
 
@@ -289,6 +687,7 @@ impl<'a, 'de> Visitor<'de> for EntityInstanceDeserializer<'a> {
                 Field::Components => access.next_value_seed(IdentifiedComponentSeq {
                     entity_builder: &mut entity_builder,
                     component_registry,
+                    component_overrides: None,
                 })?,
             }
         }
@@ -319,6 +718,7 @@ struct IdentifiedInstance<'a> {
     world: &'a mut World,
     component_registry: &'a ComponentDescriptorRegistry,
     prefab_registry: &'a PrefabDescriptorRegistry,
+    version: u32,
 }
 
 impl<'a, 'de> DeserializeSeed<'de> for IdentifiedInstance<'a> {
@@ -350,6 +750,7 @@ impl<'a, 'de> Visitor<'de> for IdentifiedInstance<'a> {
             world,
             component_registry,
             prefab_registry,
+            version,
         } = self;
 
         let (instance, variant) = data.variant_seed(InstanceIdentifier { prefab_registry })?;
@@ -372,6 +773,8 @@ impl<'a, 'de> Visitor<'de> for IdentifiedInstance<'a> {
                     source_to_prefab,
                     descriptor,
                     component_registry,
+                    prefab_registry,
+                    version,
                 },
             ),
         }
@@ -386,6 +789,8 @@ pub(crate) struct IdentifiedInstanceSeq<'a> {
     pub world: &'a mut World,
     pub component_registry: &'a ComponentDescriptorRegistry,
     pub prefab_registry: &'a PrefabDescriptorRegistry,
+    /// Prefab version the file was saved with
+    pub version: u32,
 }
 
 impl<'a, 'de> DeserializeSeed<'de> for IdentifiedInstanceSeq<'a> {
@@ -417,6 +822,7 @@ impl<'a, 'de> Visitor<'de> for IdentifiedInstanceSeq<'a> {
             world,
             component_registry,
             prefab_registry,
+            version,
         } = self;
 
         while let Some(_) = seq.next_element_seed(IdentifiedInstance {
@@ -425,6 +831,7 @@ impl<'a, 'de> Visitor<'de> for IdentifiedInstanceSeq<'a> {
             world,
             component_registry,
             prefab_registry,
+            version,
         })? {
             // Do nothing, just deserialize all elements in the sequence
         }
@@ -442,7 +849,7 @@ mod tests {
         },
         reflect::{Reflect, TypeUuid},
     };
-    use serde::Deserialize;
+    use serde::{Deserialize, Serialize};
 
     use super::*;
     use crate::{
@@ -453,7 +860,7 @@ mod tests {
     #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Reflect)]
     struct Name(String);
 
-    #[derive(Default, Debug, Deserialize, Clone, TypeUuid, Reflect)]
+    #[derive(Default, Debug, Serialize, Deserialize, Clone, TypeUuid, Reflect)]
     #[uuid = "8c24e0d1-98cc-4865-b27a-c776f5ba614d"]
     struct Lamp {
         light_strength: f32,
@@ -483,7 +890,7 @@ mod tests {
 
         let mut prefab_registry = PrefabDescriptorRegistry::default();
         prefab_registry
-            .register_aliased::<Lamp>("Lamp".to_string(), true)
+            .register_aliased::<Lamp>("Lamp".to_string())
             .unwrap();
 
         let id_validation = &mut IdValidation::empty();
@@ -512,6 +919,7 @@ mod tests {
             world: &mut world,
             component_registry: &component_registry,
             prefab_registry: &prefab_registry,
+            version: 0,
         };
         visitor.deserialize(&mut deserializer).unwrap();
 
@@ -528,7 +936,142 @@ mod tests {
             world: &mut world,
             component_registry: &component_registry,
             prefab_registry: &prefab_registry,
+            version: 0,
+        };
+        visitor.deserialize(&mut deserializer).unwrap();
+    }
+
+    #[test]
+    fn entity_overrides() {
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register::<Name>("Name".to_string())
+            .unwrap();
+
+        let mut prefab_registry = PrefabDescriptorRegistry::default();
+        prefab_registry
+            .register_aliased::<Lamp>("Lamp".to_string())
+            .unwrap();
+
+        let id_validation = &mut IdValidation::empty();
+        let mut source_to_prefab = EntityMap::default();
+        let mut world = World::default();
+
+        let input = r#"Lamp(
+            id: 95649,
+            source: External("prefabs/lamp.prefab"),
+            parent: Some(67234),
+            entities: {
+                "Body/Light": (
+                    id: 500,
+                    parent: Some("Body"),
+                    components: [
+                        Name(("Bulb")),
+                        Remove(Name, "Glass"),
+                    ],
+                ),
+                "Body/Switch": (
+                    overrides: Lamp((
+                        light_strength: 3,
+                    )),
+                ),
+            },
+        )"#;
+
+        let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+        let visitor = IdentifiedInstance {
+            id_validation,
+            source_to_prefab: &mut source_to_prefab,
+            world: &mut world,
+            component_registry: &component_registry,
+            prefab_registry: &prefab_registry,
+            version: 0,
         };
         visitor.deserialize(&mut deserializer).unwrap();
+
+        // instance, entity override carriers and parent proxy
+        assert_eq!(world.entities().len(), 4);
+        let carrier = source_to_prefab.get(Entity::new(500)).unwrap();
+        assert_eq!(world.get::<Name>(carrier), Some(&Name("Bulb".to_string())));
+        let proxy = world.get::<PrefabEntityProxy>(carrier).unwrap();
+        assert_eq!(proxy.path, PrefabEntityPath::parse("95649/Body/Light"));
+
+        let overrides = world
+            .get::<PrefabComponentOverrides>(source_to_prefab.get(Entity::new(95649)).unwrap())
+            .unwrap();
+        assert_eq!(overrides.0.len(), 4);
+        assert_eq!(
+            overrides.0[1].path,
+            PrefabEntityPath::parse("Body/Light/Glass")
+        );
+        match &overrides.0[3].op {
+            ComponentOverrideOp::Overrides { uuid, layer } => {
+                assert_eq!(*uuid, Lamp::TYPE_UUID);
+                assert_eq!(
+                    layer.source.instance,
+                    PrefabEntityPath::parse("95649/Body/Switch")
+                );
+                assert!(layer.overrides.overrides_field(&["light_strength"]));
+            }
+            _ => panic!("expected prefab data overrides"),
+        }
+    }
+
+    #[test]
+    fn overrides_with_pending_migrations() {
+        fn light_strength_in_candelas(_: &mut ron::Value) -> Result<()> {
+            Ok(())
+        }
+
+        let component_registry = ComponentDescriptorRegistry::default();
+        let mut prefab_registry = PrefabDescriptorRegistry::default();
+        prefab_registry
+            .register_aliased::<Lamp>("Lamp".to_string())
+            .unwrap();
+        prefab_registry
+            .register_migration::<Lamp>(1, light_strength_in_candelas)
+            .unwrap();
+
+        let load = |input: &str, version: u32| {
+            let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+            IdentifiedInstance {
+                id_validation: &mut IdValidation::empty(),
+                source_to_prefab: &mut EntityMap::default(),
+                world: &mut World::default(),
+                component_registry: &component_registry,
+                prefab_registry: &prefab_registry,
+                version,
+            }
+            .deserialize(&mut deserializer)
+        };
+
+        let input = r#"Lamp(
+            source: External("prefabs/lamp.prefab"),
+            overrides: (
+                light_strength: 2,
+            ),
+        )"#;
+        let err = load(input, 0).unwrap_err().to_string();
+        assert!(err.contains("Lamp` instances saved with version `0`"));
+        assert!(load(input, 1).is_ok());
+
+        let input = r#"Lamp(
+            source: External("prefabs/lamp.prefab"),
+            entities: {
+                "Body/Switch": (
+                    overrides: Lamp((
+                        light_strength: 3,
+                    )),
+                ),
+            },
+        )"#;
+        assert!(load(input, 0).is_err());
+        assert!(load(input, 1).is_ok());
+
+        // nothing to migrate
+        let input = r#"Lamp(
+            source: External("prefabs/lamp.prefab"),
+        )"#;
+        assert!(load(input, 0).is_ok());
     }
 }
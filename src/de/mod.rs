@@ -1,4 +1,9 @@
-use std::{fmt, sync::Arc};
+use std::{
+    cell::Cell,
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+};
 
 use bevy::{
     ecs::{
@@ -16,16 +21,18 @@ use serde::{
 use crate::{
     registry::{
         ComponentDescriptorRegistry, ComponentEntityMapperRegistry, PrefabDescriptor,
-        PrefabDescriptorRegistry,
+        PrefabDescriptorRegistry, ResourceDescriptorRegistry,
     },
     BoxedPrefabData, Prefab,
 };
 
 mod component;
 mod instance;
+mod resource;
 
 use component::IdentifiedComponentSeq;
 use instance::IdentifiedInstanceSeq;
+use resource::IdentifiedResourceSeq;
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -61,11 +68,24 @@ impl<'a, 'de> Visitor<'de> for PrefabVariant<'a> {
         } = self;
         match registry.find_by_name(v).cloned() {
             Some(descriptor) => Ok(descriptor),
-            None => Err(de::Error::unknown_variant(v, &[])),
+            None => Err(unknown_name(v)),
         }
     }
 }
 
+thread_local! {
+    /// Set once a prefab, component or resource name isn't found, the error itself
+    /// goes through the deserializer as a plain message, that can't be told apart
+    /// from unknown variants of enums inside the prefab data or components
+    static UNKNOWN_NAME: Cell<bool> = Cell::new(false);
+}
+
+/// Rejects a prefab, component or resource name not registered (yet)
+pub(crate) fn unknown_name<E: de::Error>(name: &str) -> E {
+    UNKNOWN_NAME.with(|unknown_name| unknown_name.set(true));
+    E::unknown_variant(name, &[])
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 pub(crate) struct IdValidation {
@@ -97,19 +117,38 @@ impl IdValidation {
 
 ///////////////////////////////////////////////////////////////////////////////
 
-struct PrefabDataDeserializer {
-    descriptor: PrefabDescriptor,
+/// Deserializes the prefab data saved with the prefab `version`, older data
+/// goes through the [`PrefabDescriptor`] migrations first
+pub(crate) struct PrefabDataDeserializer<'a> {
+    pub descriptor: &'a PrefabDescriptor,
+    pub version: u32,
 }
 
-impl<'a, 'de> DeserializeSeed<'de> for &'a PrefabDataDeserializer {
+impl<'a, 'de> DeserializeSeed<'de> for PrefabDataDeserializer<'a> {
     type Value = BoxedPrefabData;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let PrefabDataDeserializer { descriptor } = self;
-        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        let PrefabDataDeserializer {
+            descriptor,
+            version,
+        } = self;
+
+        let mut migrations = descriptor.migrations_since(version).peekable();
+        if migrations.peek().is_none() {
+            let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+            return (descriptor.de)(&mut deserializer).map_err(de::Error::custom);
+        }
+
+        // ? NOTE: `ron::Value` doesn't keep enum variants, so data with enums can't be migrated
+        let mut value = ron::Value::deserialize(deserializer)?;
+        for migrate in migrations {
+            migrate(&mut value).map_err(de::Error::custom)?;
+        }
+
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(value);
         (descriptor.de)(&mut deserializer).map_err(de::Error::custom)
     }
 }
@@ -121,6 +160,7 @@ struct PrefabBody<'a> {
     component_entity_mapper: &'a ComponentEntityMapperRegistry,
     component_registry: &'a ComponentDescriptorRegistry,
     prefab_registry: &'a PrefabDescriptorRegistry,
+    resource_registry: &'a ResourceDescriptorRegistry,
 }
 
 impl<'a, 'de> Visitor<'de> for PrefabBody<'a> {
@@ -137,17 +177,22 @@ impl<'a, 'de> Visitor<'de> for PrefabBody<'a> {
         #[derive(Deserialize)]
         #[serde(field_identifier, rename_all = "lowercase")]
         enum Field {
+            Version,
             Id,
             Transform,
             Data,
             Components,
+            Resources,
             Scene,
         }
 
+        let mut version = None;
+        let mut scene = false;
         let mut id = None;
         let mut source_to_prefab = EntityMap::default();
         let mut data = None;
         let mut transform = None;
+        let mut resources = vec![];
         let mut world = World::default();
         let root_entity = world.spawn().id();
 
@@ -156,15 +201,24 @@ impl<'a, 'de> Visitor<'de> for PrefabBody<'a> {
             descriptor,
             component_registry,
             prefab_registry,
+            resource_registry,
         } = self;
 
         let id_validation = &mut IdValidation::empty();
 
-        // root entity is used hold component data and
-        let data_seed = PrefabDataDeserializer { descriptor };
-
         while let Some(key) = access.next_key()? {
             match key {
+                Field::Version => {
+                    if version.is_some() {
+                        return Err(de::Error::duplicate_field("version"));
+                    }
+                    if data.is_some() || scene {
+                        return Err(de::Error::custom(
+                            "`version` must come before `data` and `scene`",
+                        ));
+                    }
+                    version = Some(access.next_value()?);
+                }
                 Field::Id => {
                     if id.is_some() {
                         return Err(de::Error::duplicate_field("id"));
@@ -186,19 +240,30 @@ impl<'a, 'de> Visitor<'de> for PrefabBody<'a> {
                     if data.is_some() {
                         return Err(de::Error::duplicate_field("data"));
                     }
-                    data = Some(access.next_value_seed(&data_seed)?);
+                    data = Some(access.next_value_seed(PrefabDataDeserializer {
+                        descriptor: &descriptor,
+                        version: version.unwrap_or(0),
+                    })?);
                 }
                 Field::Components => access.next_value_seed(IdentifiedComponentSeq {
                     entity_builder: &mut world.entity_mut(root_entity),
                     component_registry,
+                    component_overrides: None,
+                })?,
+                Field::Resources => access.next_value_seed(IdentifiedResourceSeq {
+                    world: &mut world,
+                    resource_registry,
+                    resources: &mut resources,
                 })?,
                 Field::Scene => {
+                    scene = true;
                     access.next_value_seed(IdentifiedInstanceSeq {
                         id_validation,
                         source_to_prefab: &mut source_to_prefab,
                         world: &mut world,
                         component_registry,
                         prefab_registry,
+                        version: version.unwrap_or(0),
                     })?;
                 }
             }
@@ -213,36 +278,56 @@ impl<'a, 'de> Visitor<'de> for PrefabBody<'a> {
             .map_err(de::Error::custom)?;
 
         let transform = transform.unwrap_or_default();
-        let mut data = data.unwrap_or_else(|| (data_seed.descriptor.default)());
+        let mut data = data.unwrap_or_else(|| (descriptor.default)());
 
         // map entities inside the data
         data.0
             .map_entities(&source_to_prefab)
             .map_err(de::Error::custom)?;
 
+        let source_to_prefab = source_to_prefab
+            .keys()
+            .map(|id| (id, source_to_prefab.get(id).unwrap()))
+            .collect();
+
         Ok(Prefab {
             root_entity,
             data,
             transform,
             world,
+            source_to_prefab,
+            resources,
         })
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
-const PREFAB_FIELDS: &'static [&'static str] = &["id", "transform", "data", "components", "scene"];
+const PREFAB_FIELDS: &'static [&'static str] = &[
+    "version",
+    "id",
+    "transform",
+    "data",
+    "components",
+    "resources",
+    "scene",
+];
 
+#[derive(Clone)]
 pub(crate) struct PrefabDeserializerInner {
     pub component_entity_mapper: ComponentEntityMapperRegistry,
     pub component_registry: ComponentDescriptorRegistry,
     pub prefab_registry: PrefabDescriptorRegistry,
+    pub resource_registry: ResourceDescriptorRegistry,
 }
 
+/// Swap-on-write handle to the prefab registries, every load uses the latest version
+/// published while the ones already running keep the version they started with
 #[derive(Clone)]
 pub(crate) struct PrefabDeserializer {
-    // TODO: change to Arc<AtomicCell<...>> to support scripting hot-reloading
-    pub inner: Arc<PrefabDeserializerInner>,
+    current: Arc<RwLock<Arc<PrefabDeserializerInner>>>,
+    /// Prefab files that failed to load because of a unknown name
+    failed: Arc<Mutex<Vec<PathBuf>>>,
 }
 
 impl PrefabDeserializer {
@@ -250,13 +335,35 @@ impl PrefabDeserializer {
         component_entity_mapper: ComponentEntityMapperRegistry,
         component_registry: ComponentDescriptorRegistry,
         prefab_registry: PrefabDescriptorRegistry,
+        resource_registry: ResourceDescriptorRegistry,
     ) -> Self {
         Self {
-            inner: Arc::new(PrefabDeserializerInner {
+            current: Arc::new(RwLock::new(Arc::new(PrefabDeserializerInner {
                 component_entity_mapper,
                 component_registry,
                 prefab_registry,
-            }),
+                resource_registry,
+            }))),
+            failed: Default::default(),
+        }
+    }
+
+    /// Latest registries version
+    pub fn load(&self) -> Arc<PrefabDeserializerInner> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Publishes a new registries version, returns the files that should be loaded again
+    pub fn store(&self, inner: PrefabDeserializerInner) -> Vec<PathBuf> {
+        *self.current.write().unwrap() = Arc::new(inner);
+        std::mem::take(&mut *self.failed.lock().unwrap())
+    }
+
+    /// Keeps track of a prefab `file` that just failed to load in this thread, when it was
+    /// caused by a prefab, component or resource name not registered (yet)
+    pub fn failed(&self, file: PathBuf) {
+        if UNKNOWN_NAME.with(|unknown_name| unknown_name.replace(false)) {
+            self.failed.lock().unwrap().push(file);
         }
     }
 }
@@ -268,6 +375,7 @@ impl<'a, 'de> DeserializeSeed<'de> for &'a PrefabDeserializer {
     where
         D: Deserializer<'de>,
     {
+        UNKNOWN_NAME.with(|unknown_name| unknown_name.set(false));
         deserializer.deserialize_enum("Prefab", &[], self)
     }
 }
@@ -283,11 +391,13 @@ impl<'a, 'de> Visitor<'de> for &'a PrefabDeserializer {
     where
         A: EnumAccess<'de>,
     {
+        let inner = self.load();
         let PrefabDeserializerInner {
             component_entity_mapper,
             component_registry,
             prefab_registry,
-        } = &*self.inner;
+            resource_registry,
+        } = &*inner;
 
         let (descriptor, variant) = data.variant_seed(PrefabVariant { prefab_registry })?;
         variant.struct_variant(
@@ -297,7 +407,65 @@ impl<'a, 'de> Visitor<'de> for &'a PrefabDeserializer {
                 component_entity_mapper,
                 component_registry,
                 prefab_registry,
+                resource_registry,
             },
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_unknown_names() {
+        #[derive(Debug, Deserialize, Clone)]
+        enum Shape {
+            Cube,
+        }
+
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register::<Shape>("Shape".to_string())
+            .unwrap();
+        let prefab_deserializer = PrefabDeserializer::new(
+            Default::default(),
+            component_registry,
+            Default::default(),
+            Default::default(),
+        );
+
+        let load = |file: &str, input: &str| {
+            let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+            assert!((&prefab_deserializer)
+                .deserialize(&mut deserializer)
+                .is_err());
+            prefab_deserializer.failed(file.into());
+        };
+        load("lamp.prefab", "LampPrefab()");
+        load(
+            "wire.prefab",
+            "Prefab(scene: [Entity(components: [Wire()])])",
+        );
+        // unknown variant, but not an unknown name
+        load(
+            "box.prefab",
+            "Prefab(scene: [Entity(components: [Shape(Sphere)])])",
+        );
+        load(
+            "cube.prefab",
+            "Prefab(scene: [Entity(id: 7), Entity(id: 7)])",
+        );
+
+        let first = prefab_deserializer.load();
+        let failed = prefab_deserializer.store((*first).clone());
+        assert_eq!(
+            failed,
+            vec![PathBuf::from("lamp.prefab"), PathBuf::from("wire.prefab")]
+        );
+        assert!(!Arc::ptr_eq(&first, &prefab_deserializer.load()));
+
+        // already retried
+        assert!(prefab_deserializer.store((*first).clone()).is_empty());
+    }
+}
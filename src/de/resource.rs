@@ -0,0 +1,220 @@
+use std::fmt;
+
+use anyhow::Result;
+use bevy::ecs::world::World;
+use serde::{
+    de::{self, DeserializeSeed, EnumAccess, SeqAccess, VariantAccess, Visitor},
+    Deserializer,
+};
+
+use crate::{
+    de::unknown_name,
+    registry::{ResourceDescriptor, ResourceDescriptorRegistry},
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+struct ResourceIdentifier<'a> {
+    resource_registry: &'a ResourceDescriptorRegistry,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for ResourceIdentifier<'a> {
+    type Value = ResourceDescriptor;
+
+    #[inline]
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'a, 'de> Visitor<'de> for ResourceIdentifier<'a> {
+    type Value = ResourceDescriptor;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a registered `Resource`")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.resource_registry
+            .find_by_name(v)
+            .cloned()
+            .ok_or_else(|| unknown_name(v))
+    }
+}
+
+struct ResourceData<'a> {
+    descriptor: &'a ResourceDescriptor,
+    world: &'a mut World,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for ResourceData<'a> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        (self.descriptor.de)(&mut deserializer, self.world).map_err(de::Error::custom)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+struct IdentifiedResource<'a> {
+    world: &'a mut World,
+    resource_registry: &'a ResourceDescriptorRegistry,
+    resources: &'a mut Vec<ResourceDescriptor>,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for IdentifiedResource<'a> {
+    type Value = ();
+
+    #[inline]
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_enum("Resource", &[], self)
+    }
+}
+
+impl<'a, 'de> Visitor<'de> for IdentifiedResource<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a registered `Resource`")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        let IdentifiedResource {
+            world,
+            resource_registry,
+            resources,
+        } = self;
+        let (descriptor, variant) = data.variant_seed(ResourceIdentifier { resource_registry })?;
+
+        // the prefab world only keeps one value per resource
+        if resources.iter().any(|other| other.name == descriptor.name) {
+            return Err(de::Error::custom(format!(
+                "duplicate resource `{}`",
+                descriptor.name
+            )));
+        }
+
+        variant.newtype_variant_seed(ResourceData {
+            descriptor: &descriptor,
+            world,
+        })?;
+        resources.push(descriptor);
+        Ok(())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Resources are inserted in the prefab `world`, their descriptors are kept in `resources`
+pub(crate) struct IdentifiedResourceSeq<'a> {
+    pub world: &'a mut World,
+    pub resource_registry: &'a ResourceDescriptorRegistry,
+    pub resources: &'a mut Vec<ResourceDescriptor>,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for IdentifiedResourceSeq<'a> {
+    type Value = ();
+
+    #[inline]
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'a, 'de> Visitor<'de> for IdentifiedResourceSeq<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a `Resource` sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let IdentifiedResourceSeq {
+            world,
+            resource_registry,
+            resources,
+        } = self;
+
+        while let Some(_) = seq.next_element_seed(IdentifiedResource {
+            world,
+            resource_registry,
+            resources,
+        })? {
+            // Do nothing, just deserialize all elements in the sequence
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::registry::ResourceDespawnPolicy;
+
+    #[derive(Debug, Deserialize, PartialEq, Clone)]
+    struct AmbientLight {
+        brightness: f32,
+    }
+
+    #[test]
+    fn read() {
+        let mut resource_registry = ResourceDescriptorRegistry::default();
+        resource_registry
+            .register::<AmbientLight>("AmbientLight".to_string(), ResourceDespawnPolicy::Keep)
+            .unwrap();
+
+        let mut world = World::default();
+        let mut resources = vec![];
+        let input = r#"[AmbientLight(( brightness: 0.3 ))]"#;
+        let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+        IdentifiedResourceSeq {
+            world: &mut world,
+            resource_registry: &resource_registry,
+            resources: &mut resources,
+        }
+        .deserialize(&mut deserializer)
+        .unwrap();
+
+        assert_eq!(resources.len(), 1);
+        assert_eq!(
+            world.get_resource::<AmbientLight>(),
+            Some(&AmbientLight { brightness: 0.3 })
+        );
+
+        let input = r#"[AmbientLight(( brightness: 0.3 )), AmbientLight(( brightness: 1 ))]"#;
+        let mut deserializer = ron::de::Deserializer::from_str(input).unwrap();
+        assert!(IdentifiedResourceSeq {
+            world: &mut World::default(),
+            resource_registry: &resource_registry,
+            resources: &mut vec![],
+        }
+        .deserialize(&mut deserializer)
+        .is_err());
+    }
+}
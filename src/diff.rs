@@ -0,0 +1,424 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{entity::Entity, world::World},
+    prelude::warn,
+    reflect::{Reflect, ReflectRef},
+    utils::HashMap,
+};
+use thiserror::Error;
+
+use crate::{
+    data::{
+        BoxedPrefabOverrides, ComponentOverride, ComponentOverrideOp, ListOverride, Override,
+        PrefabComponentOverrides, ReflectValueOverride, StructOverride,
+    },
+    de::PrefabDeserializer,
+    path::{PrefabEntityPath, PrefabInstanceEntities},
+    registry::ComponentDescriptorRegistry,
+    Prefab, PrefabNotInstantiatedTag,
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Error, Debug)]
+pub enum PrefabDiffError {
+    #[error("entity isn't a spawned prefab instance")]
+    NotAnInstance,
+    #[error("source prefab not loaded")]
+    MissingPrefab(Handle<Prefab>),
+    #[error("prefab data component not found")]
+    MissingPrefabData,
+}
+
+/// Changes made to a prefab instance since it was spawned from his source prefab
+#[derive(Default, Clone)]
+pub struct PrefabInstanceDiff {
+    /// Prefab data overrides, made of a single layer
+    pub overrides: Option<BoxedPrefabOverrides>,
+    /// Component patches and removals of the instance entities
+    pub components: PrefabComponentOverrides,
+}
+
+impl PrefabInstanceDiff {
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_none() && self.components.0.is_empty()
+    }
+}
+
+/// Compares the instance `root` with his source prefab and returns the minimal set of overrides
+/// required to get from one to the other;
+///
+/// Only patchable components are compared, see [`crate::app::PrefabAppBuilder::register_prefab_patchable_component`],
+/// other components are only checked for removal; Components missing from the source prefab
+/// are ignored because they might be added by the prefab construct function; Nested prefab instances should be diffed from their own root
+/// and entities are kept in the instance space
+pub fn diff_prefab_instance(
+    world: &World,
+    root: Entity,
+) -> Result<PrefabInstanceDiff, PrefabDiffError> {
+    let handle = world
+        .get::<Handle<Prefab>>(root)
+        .ok_or(PrefabDiffError::NotAnInstance)?;
+    let instance_entities = world
+        .get::<PrefabInstanceEntities>(root)
+        .ok_or(PrefabDiffError::NotAnInstance)?;
+
+    let prefabs = world.get_resource::<Assets<Prefab>>().unwrap();
+    let prefab = prefabs
+        .get(handle)
+        .ok_or_else(|| PrefabDiffError::MissingPrefab(handle.clone_weak()))?;
+
+    let prefab_deserializer = world.get_resource::<PrefabDeserializer>().unwrap().load();
+    let component_registry = &prefab_deserializer.component_registry;
+
+    let prefab_to_instance = prefab
+        .source_to_prefab
+        .iter()
+        .filter_map(|(id, prefab_entity)| {
+            instance_entities
+                .get(*id)
+                .map(|instance_entity| (*prefab_entity, instance_entity))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut diff = PrefabInstanceDiff::default();
+
+    // prefab data
+    let source_data = prefab.data.0.reflect_data();
+    let instance_data = prefab
+        .data
+        .0
+        .reflect_instance_data(world, root)
+        .ok_or(PrefabDiffError::MissingPrefabData)?;
+    if let Some(overrides) = diff_reflect(source_data, instance_data, &prefab_to_instance) {
+        diff.overrides = Some(BoxedPrefabOverrides(overrides));
+    }
+
+    // components
+    for (id, prefab_entity) in &prefab.source_to_prefab {
+        let instance_entity = match instance_entities.get(*id) {
+            Some(instance_entity) => instance_entity,
+            None => continue,
+        };
+
+        let prefab_entity = prefab.world.entity(*prefab_entity);
+        if prefab_entity.contains::<PrefabNotInstantiatedTag>() {
+            // nested prefab instance
+            continue;
+        }
+
+        let path = if prefab_entity.id() == prefab.root_entity {
+            PrefabEntityPath::root()
+        } else {
+            PrefabEntityPath::from_id(*id, &PrefabEntityPath::root())
+        };
+
+        diff.components.0.extend(diff_entity_components(
+            &prefab.world,
+            prefab_entity.id(),
+            world,
+            instance_entity,
+            &path,
+            component_registry,
+            &prefab_to_instance,
+        ));
+    }
+
+    Ok(diff)
+}
+
+/// Overrides of a single entity; Patchable components are compared field by field,
+/// the others can only be found missing since they don't have any means to be compared
+fn diff_entity_components(
+    prefab_world: &World,
+    prefab_entity: Entity,
+    world: &World,
+    instance_entity: Entity,
+    path: &PrefabEntityPath,
+    component_registry: &ComponentDescriptorRegistry,
+    prefab_to_instance: &HashMap<Entity, Entity>,
+) -> Vec<ComponentOverride> {
+    let mut overrides = vec![];
+    let prefab_entity = prefab_world.entity(prefab_entity);
+    for component_id in prefab_entity.archetype().components() {
+        let type_id = match prefab_world
+            .components()
+            .get_info(component_id)
+            .and_then(|component_info| component_info.type_id())
+        {
+            Some(type_id) => type_id,
+            None => continue,
+        };
+
+        let descriptor = match component_registry.find_by_type(type_id) {
+            // private and prefab data components aren't written by prefab instances
+            Some(descriptor) if !descriptor.hidden => descriptor,
+            _ => continue,
+        };
+
+        let op = if !world.entity(instance_entity).contains_type_id(type_id) {
+            match &descriptor.remove {
+                Some(remove) => ComponentOverrideOp::Remove {
+                    component: descriptor.name,
+                    remove: remove.clone(),
+                },
+                None => {
+                    // wouldn't load back, see `ComponentDescriptorRegistry::register_reflected`
+                    warn!(
+                        "`{}` only registered through reflection can't be removed, left out",
+                        descriptor.name
+                    );
+                    continue;
+                }
+            }
+        } else if let Some(patch) = &descriptor.patch {
+            let source = (patch.reflect)(prefab_world, prefab_entity.id()).unwrap();
+            let instance = (patch.reflect)(world, instance_entity).unwrap();
+            match diff_reflect(source, instance, prefab_to_instance) {
+                Some(value) => ComponentOverrideOp::Patch {
+                    component: descriptor.name,
+                    apply: patch.apply.clone(),
+                    value,
+                },
+                None => continue,
+            }
+        } else {
+            // TODO: changes made to components that aren't patchable are left out
+            continue;
+        };
+
+        overrides.push(ComponentOverride {
+            source: Default::default(),
+            path: path.clone(),
+            op,
+        });
+    }
+    overrides
+}
+
+/// Builds the override that turns `source` into `instance`, `None` when they are equal;
+/// Source entities are mapped to the instance space before being compared
+fn diff_reflect(
+    source: &dyn Reflect,
+    instance: &dyn Reflect,
+    prefab_to_instance: &HashMap<Entity, Entity>,
+) -> Option<Box<dyn Override>> {
+    match (source.reflect_ref(), instance.reflect_ref()) {
+        (ReflectRef::Struct(source), ReflectRef::Struct(instance)) => {
+            let mut fields = BTreeMap::default();
+            for (i, instance_field) in instance.iter_fields().enumerate() {
+                let name = instance.name_at(i).unwrap();
+                if let Some(source_field) = source.field(name) {
+                    if let Some(field_override) =
+                        diff_reflect(source_field, instance_field, prefab_to_instance)
+                    {
+                        fields.insert(name.to_string(), field_override);
+                    }
+                }
+            }
+
+            if fields.is_empty() {
+                None
+            } else {
+                Some(Box::new(StructOverride { fields }))
+            }
+        }
+        (ReflectRef::List(source), ReflectRef::List(instance))
+            if source.len() == instance.len() =>
+        {
+            let mut elements = BTreeMap::default();
+            for (i, instance_element) in instance.iter().enumerate() {
+                if let Some(element_override) =
+                    diff_reflect(source.get(i).unwrap(), instance_element, prefab_to_instance)
+                {
+                    elements.insert(i, element_override);
+                }
+            }
+
+            if elements.is_empty() {
+                None
+            } else {
+                Some(Box::new(ListOverride { elements }))
+            }
+        }
+        (ReflectRef::Value(_), ReflectRef::Value(_)) => {
+            let mapped;
+            let source = match source.any().downcast_ref::<Entity>() {
+                Some(entity) => {
+                    mapped = prefab_to_instance.get(entity).copied().unwrap_or(*entity);
+                    &mapped as &dyn Reflect
+                }
+                None => source,
+            };
+
+            if value_eq(source, instance) {
+                None
+            } else {
+                Some(Box::new(ReflectValueOverride(instance.clone_value())))
+            }
+        }
+        _ => {
+            if source.reflect_partial_eq(instance) != Some(true) {
+                // TODO: lists with different lengths, maps and tuples
+                warn!(
+                    "changes made to `{}` can't be represented as prefab overrides",
+                    instance.type_name()
+                );
+            }
+            None
+        }
+    }
+}
+
+/// Same as [`Reflect::reflect_partial_eq`] but also compares floats, that don't implement it
+fn value_eq(source: &dyn Reflect, instance: &dyn Reflect) -> bool {
+    if let Some(eq) = source.reflect_partial_eq(instance) {
+        return eq;
+    }
+
+    let (source, instance) = (source.any(), instance.any());
+    if let (Some(source), Some(instance)) =
+        (source.downcast_ref::<f32>(), instance.downcast_ref::<f32>())
+    {
+        source == instance
+    } else if let (Some(source), Some(instance)) =
+        (source.downcast_ref::<f64>(), instance.downcast_ref::<f64>())
+    {
+        source == instance
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::data::{OverrideRegistry, PrefabOverrideReport};
+
+    #[derive(Default, Clone, Deserialize, Reflect)]
+    struct Light {
+        intensity: f32,
+        range: f32,
+    }
+
+    #[derive(Clone, Reflect)]
+    struct Lamp {
+        light: Light,
+        target: Entity,
+        intensities: Vec<f32>,
+    }
+
+    #[test]
+    fn minimal_overrides() {
+        let mut prefab_to_instance = HashMap::default();
+        prefab_to_instance.insert(Entity::new(0), Entity::new(10));
+
+        let source = Lamp {
+            light: Light::default(),
+            target: Entity::new(0),
+            intensities: vec![1.0, 2.0],
+        };
+
+        let mut instance = source.clone();
+        instance.target = Entity::new(10);
+        assert!(diff_reflect(&source, &instance, &prefab_to_instance).is_none());
+
+        instance.light.range = 5.0;
+        instance.intensities[1] = 3.0;
+        let overrides = diff_reflect(&source, &instance, &prefab_to_instance).unwrap();
+        assert!(overrides.overrides_field(&["light", "range"]));
+        assert!(!overrides.overrides_field(&["light", "intensity"]));
+        assert!(!overrides.overrides_field(&["target"]));
+        assert!(overrides.overrides_field(&["intensities", "1"]));
+        assert!(!overrides.overrides_field(&["intensities", "0"]));
+
+        // applying the overrides on the source brings back the instance values
+        let mut target = source.clone();
+        target.target = Entity::new(10);
+        let mut report = PrefabOverrideReport::default();
+        overrides.apply_override(
+            &mut target,
+            &mut report.reporter(Default::default(), PrefabEntityPath::root(), None),
+        );
+        assert_eq!(report.applied().count(), 2);
+        assert_eq!(target.light.range, 5.0);
+        assert_eq!(target.intensities, vec![1.0, 3.0]);
+    }
+
+    #[derive(Default, Clone, Deserialize)]
+    struct Tag(u32);
+
+    #[test]
+    fn component_overrides() {
+        let mut overrides = OverrideRegistry::default();
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register_patchable::<Light>("Light".to_string(), &mut overrides)
+            .unwrap();
+        component_registry
+            .register::<Tag>("Tag".to_string())
+            .unwrap();
+
+        let mut prefab_world = World::default();
+        let prefab_entity = prefab_world
+            .spawn()
+            .insert(Light::default())
+            .insert(Tag(1))
+            .id();
+
+        let mut world = World::default();
+        let light = Light {
+            intensity: 0.0,
+            range: 5.0,
+        };
+        let patched = world.spawn().insert(light).insert(Tag(2)).id();
+        let removed = world.spawn().id();
+
+        let diff = |instance_entity| {
+            diff_entity_components(
+                &prefab_world,
+                prefab_entity,
+                &world,
+                instance_entity,
+                &PrefabEntityPath::root(),
+                &component_registry,
+                &HashMap::default(),
+            )
+        };
+
+        // changes made to components that aren't patchable can't be found
+        assert_ne!(
+            prefab_world.get::<Tag>(prefab_entity).unwrap().0,
+            world.get::<Tag>(patched).unwrap().0
+        );
+        let overrides = diff(patched);
+        assert_eq!(overrides.len(), 1);
+        match &overrides[0].op {
+            ComponentOverrideOp::Patch {
+                component, value, ..
+            } => {
+                assert_eq!(*component, std::any::type_name::<Light>());
+                assert!(value.overrides_field(&["range"]));
+                assert!(!value.overrides_field(&["intensity"]));
+            }
+            _ => panic!("expected a patch"),
+        }
+
+        // but every registered component can be found missing
+        let mut removals = diff(removed)
+            .into_iter()
+            .map(|component_override| match component_override.op {
+                ComponentOverrideOp::Remove { component, .. } => component,
+                _ => panic!("expected a removal"),
+            })
+            .collect::<Vec<_>>();
+        removals.sort_unstable();
+        let mut expected = vec![std::any::type_name::<Light>(), std::any::type_name::<Tag>()];
+        expected.sort_unstable();
+        assert_eq!(removals, expected);
+    }
+}
@@ -5,6 +5,7 @@ use bevy::{
     math::{Quat, Vec3},
     prelude::Transform,
     reflect::{TypeUuid, Uuid},
+    utils::HashMap,
 };
 use serde::{Deserialize, Serialize};
 
@@ -13,20 +14,50 @@ pub mod builtin;
 pub mod command;
 pub mod data;
 pub mod de;
+pub mod diff;
 pub mod loader;
 pub mod manager;
+pub mod path;
 pub mod registry;
+pub mod rename;
+pub mod schema;
+pub mod upgrade;
+
+// lets the derive macros refer to `::bevy_prefab` from inside this crate
+extern crate self as bevy_prefab;
+
+/// Used by the code generated by `bevy_prefab_derive`
+#[doc(hidden)]
+pub mod __macro {
+    pub use anyhow::Result;
+    pub use bevy::{
+        ecs::{entity::Entity, world::World},
+        reflect::Reflect,
+    };
+}
 
-use crate::data::{BoxedPrefabData, PrefabData};
+use crate::data::{
+    BoxedPrefabData, ComponentOverrideOp, PrefabComponentOverrides, PrefabData,
+    PrefabOverrideLayers, PrefabOverrideSource,
+};
 
 pub mod prelude {
+    pub use bevy_prefab_derive::PrefabData;
+
     pub use crate::app::*;
     pub use crate::command::PrefabCommands;
-    pub use crate::data::{BoxedPrefabData, PrefabData};
+    pub use crate::data::{
+        BoxedPrefabData, FieldConstraint, OverrideRules, PrefabData, PrefabDynamicComponents,
+        PrefabOverrideReport,
+    };
+    pub use crate::diff::{diff_prefab_instance, PrefabInstanceDiff};
+    pub use crate::path::{PrefabEntityPath, PrefabInstanceEntities};
+    pub use crate::registry::{ComponentMergePolicy, ResourceDespawnPolicy};
+    pub use crate::schema::PrefabSchema;
     pub use crate::Prefab;
 }
 
-use crate::registry::PrefabConstructFn;
+use crate::registry::{PrefabConstructFn, ResourceDescriptor};
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -37,6 +68,44 @@ pub struct Prefab {
     data: BoxedPrefabData,
     transform: Transform,
     world: World,
+    /// Maps the ids used in the source file to the prefab world entities
+    source_to_prefab: HashMap<Entity, Entity>,
+    /// Resources kept in the prefab world, inserted by every instance
+    resources: Vec<ResourceDescriptor>,
+}
+
+impl Prefab {
+    /// Tags every override defined by this prefab with the `file` it was loaded from
+    pub(crate) fn set_source_file(&mut self, file: &str) {
+        let tag = |source: &mut PrefabOverrideSource| {
+            if source.file.is_none() {
+                source.file = Some(file.to_string());
+            }
+        };
+
+        for mut overrides in self
+            .world
+            .query::<&mut PrefabOverrideLayers>()
+            .iter_mut(&mut self.world)
+        {
+            for layer in &mut overrides.0 {
+                tag(&mut layer.source);
+            }
+        }
+
+        for mut overrides in self
+            .world
+            .query::<&mut PrefabComponentOverrides>()
+            .iter_mut(&mut self.world)
+        {
+            for component_override in &mut overrides.0 {
+                tag(&mut component_override.source);
+                if let ComponentOverrideOp::Overrides { layer, .. } = &mut component_override.op {
+                    tag(&mut layer.source);
+                }
+            }
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -63,9 +132,13 @@ pub struct PrefabNotInstantiatedTag {
 pub enum PrefabError {
     Missing,
     WrongExpectedSourcePrefab,
+    /// `PrefabData::post_construct` returned an error
+    PostConstructFailed,
+    /// A nested prefab instance failed, so `PrefabData::post_construct` wasn't called
+    NestedInstanceFailed,
 }
 
-/// Tags a prefab as missing
+/// Tags a prefab as missing or failed
 #[derive(Debug, Clone)]
 pub struct PrefabErrorTag(PrefabError);
 
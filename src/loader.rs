@@ -33,10 +33,20 @@ impl AssetLoader for PrefabLoader {
     ) -> BoxedFuture<'a, Result<()>> {
         Box::pin(async move {
             let mut deserializer = ron::de::Deserializer::from_bytes(&bytes)?;
-            let prefab = self.asset_server.with_asset_refs_serialization(|| {
+            let result = self.asset_server.with_asset_refs_serialization(|| {
                 // ? NOTE: Keep this scope as lean as possible
                 self.prefab_deserializer.deserialize(&mut deserializer)
-            })?;
+            });
+            let mut prefab = match result {
+                Ok(prefab) => prefab,
+                Err(err) => {
+                    // might be loaded again once new prefabs or components are registered
+                    self.prefab_deserializer
+                        .failed(load_context.path().to_path_buf());
+                    return Err(err.into());
+                }
+            };
+            prefab.set_source_file(&load_context.path().to_string_lossy());
             load_context.set_default_asset(LoadedAsset::new(prefab));
             Ok(())
         })
@@ -1,11 +1,14 @@
-use bevy::{ecs::entity::EntityMap, prelude::*, utils::HashSet};
+use bevy::{ecs::entity::EntityMap, prelude::*, reflect::TypeRegistryArc, utils::HashSet};
 use thiserror::Error;
 
 use crate::{
-    de::PrefabDeserializer,
+    data::{PrefabComponentOverrides, PrefabInstanceHooks, PrefabOverrideReport},
+    de::{PrefabDeserializer, PrefabDeserializerInner},
     loader::PrefabLoader,
+    path::{resolve_entity_proxies, PrefabEntityProxy, PrefabInstanceEntities},
     registry::{
         ComponentDescriptorRegistry, ComponentEntityMapperRegistry, PrefabDescriptorRegistry,
+        PrefabInstanceResources, ResourceDescriptorRegistry,
     },
     Prefab, PrefabConstruct, PrefabError, PrefabErrorTag, PrefabNotInstantiatedTag,
     PrefabTransformOverride, PrefabTypeUuid,
@@ -23,6 +26,12 @@ pub enum PrefabSpawnError {
 
 struct Instantiate(Entity, Handle<Prefab>);
 
+/// Nested prefab instances yet to finish spawning, the instance `post_construct` waits for them
+struct PrefabPendingInstances(usize);
+
+/// Instance waiting on this nested instance before calling its own `post_construct`
+struct PrefabParentInstance(Entity);
+
 fn enqueue_prefab_not_instantiated(world: &mut World, queue: &mut Vec<Instantiate>) {
     for (entity, handle, _) in world
         .query::<(Entity, &Handle<Prefab>, &PrefabNotInstantiatedTag)>()
@@ -40,6 +49,7 @@ fn prefab_spawner(
     component_registry: &ComponentDescriptorRegistry,
 ) {
     let mut blacklist = HashSet::default();
+    let mut spawned = vec![];
 
     loop {
         while let Some(Instantiate(root_entity, source_prefab)) = prefabs_queue.pop() {
@@ -67,6 +77,8 @@ fn prefab_spawner(
                         "prefab expected type `{}` but got source of type `{}`",
                         uuid, source
                     );
+                    // the parent instance fails with it
+                    post_construct_instance(world, root_entity);
                     continue;
                 }
             }
@@ -89,9 +101,9 @@ fn prefab_spawner(
                             if let Some(descriptor) =
                                 component_registry.find_by_type(component_info.type_id().unwrap())
                             {
-                                // copy prefab from his world over the current active world
-                                // but don't override any component, a bit slower but needed since the
-                                (descriptor.copy_without_overriding)(
+                                // merge prefab root component with the instance root component
+                                // (when present) as defined by the component merge policy
+                                (descriptor.merge)(
                                     &prefab.world,
                                     world,
                                     *prefab_entity,
@@ -141,9 +153,17 @@ fn prefab_spawner(
                 let mut instance = world.entity_mut(instance_entity);
 
                 // map entities components to instance space
-                component_entity_mapper
+                if let Err(err) = component_entity_mapper
                     .map_entity_components(&mut instance, &prefab_to_instance)
-                    .unwrap();
+                {
+                    warn!("prefab entity references couldn't be mapped: {}", err);
+                }
+
+                // entity proxies are resolved relative to this instance, later on
+                if let Some(mut proxy) = instance.get_mut::<PrefabEntityProxy>() {
+                    proxy.root = Some(root_entity);
+                    continue;
+                }
 
                 // parent all root prefab entities under the instance root
                 if instance.get::<Parent>().is_none() {
@@ -151,10 +171,51 @@ fn prefab_spawner(
                 }
             }
 
+            // prefab resources are inserted before the construct function, so it can already use them
+            if !prefab.resources.is_empty() {
+                let resources =
+                    PrefabInstanceResources::insert(&prefab.resources, &prefab.world, world);
+                if !resources.is_empty() {
+                    world.entity_mut(root_entity).insert(resources);
+                }
+            }
+
+            // keep the source file ids, so entities can be addressed by path
+            let instance_entities = PrefabInstanceEntities(
+                prefab
+                    .source_to_prefab
+                    .iter()
+                    .filter_map(|(id, prefab_entity)| {
+                        prefab_to_instance
+                            .get(*prefab_entity)
+                            .ok()
+                            .map(|instance_entity| (*id, instance_entity))
+                    })
+                    .collect(),
+            );
+
+            // nested instances are spawned later on, maybe frames later if their source prefab
+            // isn't loaded yet, the `post_construct` waits until all of them are done
+            let mut pending = 0;
+            for instance_entity in prefab_to_instance.values() {
+                if instance_entity != root_entity
+                    && world
+                        .get::<PrefabNotInstantiatedTag>(instance_entity)
+                        .is_some()
+                {
+                    world
+                        .entity_mut(instance_entity)
+                        .insert(PrefabParentInstance(root_entity));
+                    pending += 1;
+                }
+            }
+
             let mut root = world.entity_mut(root_entity);
 
             // clear not instantiated tag
             root.remove::<PrefabNotInstantiatedTag>();
+            root.insert(instance_entities);
+            root.insert(PrefabPendingInstances(pending));
 
             // override prefab transformations with instance's transform
             let mut transform = prefab.transform.clone();
@@ -173,7 +234,7 @@ fn prefab_spawner(
             root.insert_bundle((GlobalTransform::default(), transform, Children::default()));
 
             // apply overrides and run construct function
-            if let Some(prefab_construct) = root.get::<PrefabConstruct>() {
+            if let Some(prefab_construct) = root.get::<PrefabConstruct>().cloned() {
                 // prefab doesn't require a valid source (fully procedural)
                 (prefab_construct.0)(world, root_entity, &prefab_to_instance).unwrap();
             } else {
@@ -183,8 +244,28 @@ fn prefab_spawner(
                     .apply_overrides_and_construct_instance(world, root_entity, &prefab_to_instance)
                     .unwrap();
             }
+
+            // apply instance component overrides last, so components added by the construct function
+            // can also be overridden
+            if let Some(component_overrides) = world
+                .entity_mut(root_entity)
+                .remove::<PrefabComponentOverrides>()
+            {
+                // appended to the report of the prefab data overrides
+                let mut report = world
+                    .entity_mut(root_entity)
+                    .remove::<PrefabOverrideReport>()
+                    .unwrap_or_default();
+                component_overrides.apply(world, root_entity, component_registry, &mut report);
+                world.entity_mut(root_entity).insert(report);
+            }
+
+            spawned.push(root_entity);
         }
 
+        // replace references to entities addressed by path
+        resolve_entity_proxies(world, component_entity_mapper);
+
         enqueue_prefab_not_instantiated(world, prefabs_queue);
 
         // TODO: very hacky and expensive, we don't know when a prefab was finished loading
@@ -195,19 +276,82 @@ fn prefab_spawner(
             break;
         }
     }
+
+    // instances without nested instances left are done, their parents follow
+    // once the last of their nested instances is done
+    for root_entity in spawned {
+        if let Some(PrefabPendingInstances(0)) = world.get(root_entity) {
+            post_construct_instance(world, root_entity);
+        }
+    }
+}
+
+/// Calls `post_construct` of a fully spawned instance, then of every parent instance left
+/// without pending nested instances; Instances with errors skip the call and fail their parent
+fn post_construct_instance(world: &mut World, mut root_entity: Entity) {
+    loop {
+        let mut root = world.entity_mut(root_entity);
+        root.remove::<PrefabPendingInstances>();
+        let parent = root.remove::<PrefabParentInstance>();
+
+        let mut failed = root.get::<PrefabErrorTag>().is_some();
+        if !failed {
+            if let Some(hooks) = root.get::<PrefabInstanceHooks>().copied() {
+                if let Err(err) = (hooks.post_construct)(world, root_entity) {
+                    error!("prefab post construct failed: {}", err);
+                    world
+                        .entity_mut(root_entity)
+                        .insert(PrefabErrorTag(PrefabError::PostConstructFailed));
+                    failed = true;
+                }
+            }
+        }
+
+        let parent_entity = match parent {
+            Some(PrefabParentInstance(parent_entity)) => parent_entity,
+            None => return,
+        };
+        // parent might be despawned by now
+        let done = match world.get_mut::<PrefabPendingInstances>(parent_entity) {
+            Some(mut pending) => {
+                pending.0 -= 1;
+                pending.0 == 0
+            }
+            None => return,
+        };
+        if failed {
+            world
+                .entity_mut(parent_entity)
+                .insert(PrefabErrorTag(PrefabError::NestedInstanceFailed));
+        }
+        if !done {
+            return;
+        }
+        root_entity = parent_entity;
+    }
 }
 
 pub(crate) fn prefab_commit_startup_system(world: &mut World) {
-    // commits to registered prefab and components on startup
-    let prefab_registry = world.remove_resource::<PrefabDescriptorRegistry>().unwrap();
-    let component_registry = world
-        .remove_resource::<ComponentDescriptorRegistry>()
-        .unwrap();
-    let component_entity_mapper = world
-        .remove_resource::<ComponentEntityMapperRegistry>()
-        .unwrap();
-    let prefab_deserializer =
-        PrefabDeserializer::new(component_entity_mapper, component_registry, prefab_registry);
+    // commits to registered prefab and components on startup, registries are kept
+    // in the world so they can still be changed later on
+    let prefab_deserializer = PrefabDeserializer::new(
+        world
+            .get_resource::<ComponentEntityMapperRegistry>()
+            .unwrap()
+            .clone(),
+        world
+            .get_resource::<ComponentDescriptorRegistry>()
+            .unwrap()
+            .clone(),
+        world
+            .get_resource::<PrefabDescriptorRegistry>()
+            .unwrap()
+            .clone(),
+        world
+            .get_resource::<ResourceDescriptorRegistry>()
+            .unwrap()
+            .clone(),
+    );
     world.insert_resource(prefab_deserializer);
 
     // create prefab loader
@@ -218,6 +362,58 @@ pub(crate) fn prefab_commit_startup_system(world: &mut World) {
         .add_loader(loader);
 }
 
+/// Fills the component registry with every reflected component in bevy's type registry
+pub(crate) fn prefab_register_reflected_components_system(world: &mut World) {
+    let type_registry = world.get_resource::<TypeRegistryArc>().unwrap().clone();
+    let count = world.resource_scope(
+        |world, mut component_entity_mapper: Mut<ComponentEntityMapperRegistry>| {
+            world
+                .get_resource_mut::<ComponentDescriptorRegistry>()
+                .unwrap()
+                .register_reflected(&type_registry.read(), &mut component_entity_mapper)
+        },
+    );
+    info!(
+        "{} reflected components registered as prefab components",
+        count
+    );
+
+    prefab_commit_registries(world);
+}
+
+/// Publishes the current registries to loaders and spawner, does nothing before startup
+pub(crate) fn prefab_commit_registries(world: &mut World) {
+    let prefab_deserializer = match world.get_resource::<PrefabDeserializer>() {
+        Some(prefab_deserializer) => prefab_deserializer.clone(),
+        None => return,
+    };
+
+    let failed = prefab_deserializer.store(PrefabDeserializerInner {
+        component_entity_mapper: world
+            .get_resource::<ComponentEntityMapperRegistry>()
+            .unwrap()
+            .clone(),
+        component_registry: world
+            .get_resource::<ComponentDescriptorRegistry>()
+            .unwrap()
+            .clone(),
+        prefab_registry: world
+            .get_resource::<PrefabDescriptorRegistry>()
+            .unwrap()
+            .clone(),
+        resource_registry: world
+            .get_resource::<ResourceDescriptorRegistry>()
+            .unwrap()
+            .clone(),
+    });
+
+    // retry prefabs that failed on a name that might be known by now
+    let asset_server = world.get_resource::<AssetServer>().unwrap();
+    for file in failed {
+        asset_server.reload_asset(file.as_path());
+    }
+}
+
 pub fn prefab_managing_system(world: &mut World) {
     let mut prefabs_queue = vec![];
 
@@ -228,15 +424,77 @@ pub fn prefab_managing_system(world: &mut World) {
         return;
     }
 
-    let prefab_registry = world.get_resource::<PrefabDeserializer>().unwrap().clone();
+    let prefab_registry = world.get_resource::<PrefabDeserializer>().unwrap().load();
 
     world.resource_scope(|world, prefabs: Mut<Assets<Prefab>>| {
         prefab_spawner(
             world,
             &*prefabs,
             &mut prefabs_queue,
-            &prefab_registry.inner.component_entity_mapper,
-            &prefab_registry.inner.component_registry,
+            &prefab_registry.component_entity_mapper,
+            &prefab_registry.component_registry,
         )
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    fn instance(world: &mut World, pending: usize, parent: Option<Entity>, fails: bool) -> Entity {
+        let post_construct = if fails {
+            |_: &mut World, _: Entity| Err(anyhow!("failed"))
+        } else {
+            |world: &mut World, root: Entity| {
+                world.get_resource_mut::<Vec<Entity>>().unwrap().push(root);
+                Ok(())
+            }
+        };
+        let mut entity = world.spawn();
+        entity
+            .insert(PrefabPendingInstances(pending))
+            .insert(PrefabInstanceHooks {
+                post_construct,
+                on_despawn: |_, _| Ok(()),
+            });
+        if let Some(parent) = parent {
+            entity.insert(PrefabParentInstance(parent));
+        }
+        entity.id()
+    }
+
+    #[test]
+    fn post_construct_waits_nested_instances() {
+        let mut world = World::default();
+        world.insert_resource(Vec::<Entity>::new());
+
+        let root = instance(&mut world, 2, None, false);
+        let first = instance(&mut world, 0, Some(root), false);
+        let second = instance(&mut world, 0, Some(root), false);
+
+        post_construct_instance(&mut world, first);
+        assert_eq!(world.get_resource::<Vec<Entity>>().unwrap(), &vec![first]);
+        post_construct_instance(&mut world, second);
+        assert_eq!(
+            world.get_resource::<Vec<Entity>>().unwrap(),
+            &vec![first, second, root]
+        );
+        assert!(world.get::<PrefabPendingInstances>(root).is_none());
+
+        // errors are tagged on the parents, which aren't called
+        let root = instance(&mut world, 1, None, false);
+        let nested = instance(&mut world, 0, Some(root), true);
+        post_construct_instance(&mut world, nested);
+        assert!(matches!(
+            world.get::<PrefabErrorTag>(nested).unwrap().error(),
+            PrefabError::PostConstructFailed
+        ));
+        assert!(matches!(
+            world.get::<PrefabErrorTag>(root).unwrap().error(),
+            PrefabError::NestedInstanceFailed
+        ));
+        assert_eq!(world.get_resource::<Vec<Entity>>().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,352 @@
+use std::fmt;
+
+use bevy::{
+    ecs::{
+        entity::{Entity, EntityMap},
+        world::World,
+    },
+    prelude::{warn, Name, Parent},
+    utils::{HashMap, HashSet},
+};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer,
+};
+
+use crate::{registry::ComponentEntityMapperRegistry, PrefabNotInstantiatedTag};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Maps the ids used in the prefab source file to their instance entities,
+/// it's inserted in every prefab instance root
+#[derive(Default, Debug, Clone)]
+pub struct PrefabInstanceEntities(pub(crate) HashMap<Entity, Entity>);
+
+impl PrefabInstanceEntities {
+    /// Finds the instance entity of a given source file id
+    pub fn get(&self, id: Entity) -> Option<Entity> {
+        self.0.get(&id).copied()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PrefabEntityPathSegment {
+    /// Id of the entity in the prefab source file
+    Id(Entity),
+    /// Entity [`Name`]
+    Name(String),
+}
+
+/// Path to an entity inside a prefab instance, made of ids or [`Name`]s separated by `/`
+/// such as `"Body/Light"` or `"100/Light"`, a single id can also be written as a number;
+///
+/// Ids are always relative to the prefab file of the last nested prefab
+/// instance the path went through, an empty path refers to the instance root
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct PrefabEntityPath(pub(crate) Vec<PrefabEntityPathSegment>);
+
+impl PrefabEntityPath {
+    /// Path to the instance root
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Path made of a single id, if any
+    pub fn single_id(&self) -> Option<Entity> {
+        match self.0.as_slice() {
+            [PrefabEntityPathSegment::Id(id)] => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Appends `other` at the end of this path
+    pub fn join(&self, other: &PrefabEntityPath) -> PrefabEntityPath {
+        let mut path = self.clone();
+        path.0.extend(other.0.iter().cloned());
+        path
+    }
+
+    /// Path starting from the entity `id`
+    pub(crate) fn from_id(id: Entity, rest: &PrefabEntityPath) -> PrefabEntityPath {
+        PrefabEntityPath(vec![PrefabEntityPathSegment::Id(id)]).join(rest)
+    }
+
+    pub fn parse(path: &str) -> Self {
+        PrefabEntityPath(
+            path.split('/')
+                .filter(|segment| !segment.is_empty())
+                .map(|segment| match segment.parse::<u32>() {
+                    Ok(id) => PrefabEntityPathSegment::Id(Entity::new(id)),
+                    Err(_) => PrefabEntityPathSegment::Name(segment.to_string()),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for PrefabEntityPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("/")?;
+            }
+            match segment {
+                PrefabEntityPathSegment::Id(id) => write!(f, "{}", id.id())?,
+                PrefabEntityPathSegment::Name(name) => f.write_str(name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for PrefabEntityPath {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PathVisitor;
+
+        impl<'de> Visitor<'de> for PathVisitor {
+            type Value = PrefabEntityPath;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an entity id or a path like `\"Body/Light\"`")
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                if v > u32::MAX as u64 {
+                    return Err(de::Error::invalid_value(de::Unexpected::Unsigned(v), &self));
+                }
+                Ok(PrefabEntityPath(vec![PrefabEntityPathSegment::Id(
+                    Entity::new(v as u32),
+                )]))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                if v < 0 {
+                    return Err(de::Error::invalid_value(de::Unexpected::Signed(v), &self));
+                }
+                self.visit_u64(v as u64)
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(PrefabEntityPath::parse(v))
+            }
+        }
+
+        deserializer.deserialize_any(PathVisitor)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Placeholder entity for an entity addressed by path, used to refer to entities
+/// inside nested prefabs before they are instantiated; Once the path is resolved
+/// every reference to the placeholder is replaced and the placeholder despawned
+#[derive(Debug, Clone)]
+pub(crate) struct PrefabEntityProxy {
+    /// Prefab instance root the path is relative to, assigned when the prefab is spawned
+    pub root: Option<Entity>,
+    pub path: PrefabEntityPath,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+pub(crate) enum ResolvedEntity {
+    Found(Entity),
+    /// Path goes through a nested prefab instance that wasn't instantiated yet
+    Pending {
+        instance: Entity,
+        rest: PrefabEntityPath,
+    },
+    Missing,
+}
+
+/// Finds the entity addressed by `path` starting from an instantiated prefab `root`
+pub(crate) fn resolve_entity_path(
+    world: &mut World,
+    root: Entity,
+    path: &PrefabEntityPath,
+) -> ResolvedEntity {
+    let mut scope = root;
+    let mut current = root;
+
+    for (i, segment) in path.0.iter().enumerate() {
+        if current != root {
+            if world.get::<PrefabNotInstantiatedTag>(current).is_some() {
+                return ResolvedEntity::Pending {
+                    instance: current,
+                    rest: PrefabEntityPath(path.0[i..].to_vec()),
+                };
+            }
+
+            // entering a nested prefab instance, ids are now relative to it
+            if world.get::<PrefabInstanceEntities>(current).is_some() {
+                scope = current;
+            }
+        }
+
+        let next = match segment {
+            PrefabEntityPathSegment::Id(id) => world
+                .get::<PrefabInstanceEntities>(scope)
+                .and_then(|entities| entities.get(*id)),
+            PrefabEntityPathSegment::Name(name) => world
+                .query::<(Entity, &Parent, &Name)>()
+                .iter(world)
+                .find(|(_, parent, entity_name)| {
+                    parent.0 == current && entity_name.as_str() == name
+                })
+                .map(|(entity, _, _)| entity),
+        };
+
+        current = match next {
+            Some(entity) => entity,
+            None => return ResolvedEntity::Missing,
+        };
+    }
+
+    ResolvedEntity::Found(current)
+}
+
+/// Replaces every reference to a [`PrefabEntityProxy`] by the entity it points to,
+/// proxies that go through nested prefabs still loading are kept for later
+pub(crate) fn resolve_entity_proxies(
+    world: &mut World,
+    component_entity_mapper: &ComponentEntityMapperRegistry,
+) {
+    let proxies = world
+        .query::<(Entity, &PrefabEntityProxy)>()
+        .iter(world)
+        .filter_map(|(entity, proxy)| proxy.root.map(|root| (entity, root, proxy.path.clone())))
+        .collect::<Vec<_>>();
+
+    if proxies.is_empty() {
+        return;
+    }
+
+    let mut entity_map = EntityMap::default();
+    let mut owners = HashSet::default();
+    let mut missing = vec![];
+    for (proxy, root, path) in proxies {
+        match resolve_entity_path(world, root, &path) {
+            ResolvedEntity::Found(entity) => {
+                // the proxy source id now addresses the resolved entity
+                if let Some(mut entities) = world.get_mut::<PrefabInstanceEntities>(root) {
+                    for instance_entity in entities.0.values_mut() {
+                        if *instance_entity == proxy {
+                            *instance_entity = entity;
+                        } else {
+                            // only entities of the same instance can reference the proxy
+                            owners.insert(*instance_entity);
+                        }
+                    }
+                }
+                entity_map.insert(proxy, entity);
+            }
+            ResolvedEntity::Pending { .. } => {}
+            ResolvedEntity::Missing => {
+                warn!(
+                    "prefab entity `{}` not found, other references to it are left dangling",
+                    path
+                );
+                missing.push((proxy, root));
+            }
+        }
+    }
+
+    for (proxy, root) in missing {
+        // the proxy source id doesn't address anything
+        if let Some(mut entities) = world.get_mut::<PrefabInstanceEntities>(root) {
+            entities
+                .0
+                .retain(|_, instance_entity| *instance_entity != proxy);
+        }
+
+        // children are kept under the instance root, just like the prefab root entities
+        let children = world
+            .query::<(Entity, &Parent)>()
+            .iter(world)
+            .filter(|(_, parent)| parent.0 == proxy)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        for child in children {
+            world.entity_mut(child).insert(Parent(root));
+        }
+
+        world.despawn(proxy);
+    }
+
+    let resolved = entity_map.keys().collect::<Vec<_>>();
+    if resolved.is_empty() {
+        return;
+    }
+
+    for owner in owners {
+        // resolved proxies aren't owners and owners might have been despawned since
+        if entity_map.get(owner).is_ok() || world.get_entity(owner).is_none() {
+            continue;
+        }
+        if let Err(err) =
+            component_entity_mapper.map_entity_components(&mut world.entity_mut(owner), &entity_map)
+        {
+            warn!("prefab entity references couldn't be resolved: {}", err);
+        }
+    }
+
+    for proxy in resolved {
+        world.despawn(proxy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unresolved_proxies() {
+        let mut world = World::default();
+        let root = world.spawn().id();
+        let proxy = world
+            .spawn()
+            .insert(PrefabEntityProxy {
+                root: Some(root),
+                path: PrefabEntityPath::parse("5"),
+            })
+            .id();
+        let child = world.spawn().insert(Parent(proxy)).id();
+
+        let mut entities = HashMap::default();
+        entities.insert(Entity::new(1), proxy);
+        entities.insert(Entity::new(2), child);
+        world
+            .entity_mut(root)
+            .insert(PrefabInstanceEntities(entities));
+
+        resolve_entity_proxies(&mut world, &ComponentEntityMapperRegistry::default());
+
+        // the placeholder isn't left behind and nothing refers to it anymore
+        assert!(world.get_entity(proxy).is_none());
+        assert_eq!(
+            world.get::<Parent>(child).map(|parent| parent.0),
+            Some(root)
+        );
+        let entities = world.get::<PrefabInstanceEntities>(root).unwrap();
+        assert_eq!(entities.get(Entity::new(1)), None);
+        assert_eq!(entities.get(Entity::new(2)), Some(child));
+    }
+}
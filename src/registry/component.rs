@@ -1,4 +1,8 @@
-use std::any::{type_name, TypeId};
+use std::{
+    any::{type_name, TypeId},
+    marker::PhantomData,
+    sync::Arc,
+};
 
 use anyhow::Result;
 use bevy::{
@@ -6,33 +10,117 @@ use bevy::{
         //bundle::Bundle,
         component::Component,
         entity::Entity,
+        reflect::ReflectComponent,
         world::{EntityMut, World},
     },
-    reflect::Uuid,
+    prelude::warn,
+    reflect::{Reflect, ReflectDeserialize, ReflectRef, Struct, TypeRegistry},
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use super::Registry;
+use crate::data::{
+    DynamicComponentType, Override, OverrideDescriptor, OverrideRegistry, OverrideReporter,
+    PrefabComponentOverrides, PrefabDynamicComponents, PrefabOverrideReport,
+};
+
+use super::{shorten_name, ComponentEntityMapperRegistry, Registry, RegistryError};
+
+// functions are shared behind an `Arc` so components registered at runtime
+// (without a concrete type) can carry their reflection data along
 
 pub(crate) type ComponentDeserializerFn =
-    fn(&mut dyn erased_serde::Deserializer, &mut EntityMut) -> Result<()>;
+    Arc<dyn Fn(&mut dyn erased_serde::Deserializer, &mut EntityMut) -> Result<()> + Send + Sync>;
+
+pub(crate) type ComponentCopyFn = Arc<dyn Fn(&World, &mut World, Entity, Entity) + Send + Sync>;
+
+pub(crate) type ComponentPatchFn =
+    Arc<dyn Fn(&mut World, Entity, &dyn Override, &mut OverrideReporter) + Send + Sync>;
+
+pub(crate) type ComponentRemoveFn = Arc<dyn Fn(&mut EntityMut) + Send + Sync>;
+
+pub(crate) type ComponentTakeFn = Arc<dyn Fn(&mut World, Entity, Entity) + Send + Sync>;
+
+pub(crate) type ComponentSerializerFn =
+    Arc<dyn Fn(&World, Entity) -> Option<Box<dyn erased_serde::Serialize>> + Send + Sync>;
 
-pub(crate) type ComponentCopyFn = fn(&World, &mut World, Entity, Entity) -> ();
+pub(crate) type ComponentReflectFn =
+    Arc<dyn Fn(&World, Entity) -> Option<&dyn Reflect> + Send + Sync>;
+
+type DeserializerFn = fn(&mut dyn erased_serde::Deserializer, &mut EntityMut) -> Result<()>;
+
+type CopyFn = fn(&World, &mut World, Entity, Entity);
+
+type ComponentConstructorFn<T> = Arc<dyn Fn(&mut World, Entity) -> T + Send + Sync>;
+
+/// How a component of the source prefab root is merged with the same component
+/// already present in the instance root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentMergePolicy {
+    /// Source prefab component always replaces the instance one
+    Replace,
+    /// Instance component wins, the source prefab component is only added when missing (default)
+    KeepInstance,
+    /// Only the fields the instance wrote in it's patch, e.g. `PointLight(( range: 10 ))`,
+    /// replace the source prefab ones and they do so before the construct function runs;
+    /// A component inserted as a whole in the instance root, like from code, still wins entirely
+    Reflect,
+}
+
+impl Default for ComponentMergePolicy {
+    fn default() -> Self {
+        ComponentMergePolicy::KeepInstance
+    }
+}
+
+/// Reflect based partial deserialization, used by prefab instances to only override
+/// some fields of a component
+#[derive(Clone)]
+pub(crate) struct ComponentPatchDescriptor {
+    pub overrides: OverrideDescriptor,
+    pub apply: ComponentPatchFn,
+    pub reflect: ComponentReflectFn,
+}
 
 #[derive(Clone)]
 pub struct ComponentDescriptor {
+    /// Component type name
+    pub(crate) name: &'static str,
     pub(crate) de: ComponentDeserializerFn,
+    /// Only available for dynamic components and components serialized through a proxy type
+    pub(crate) ser: Option<ComponentSerializerFn>,
+    pub(crate) patch: Option<ComponentPatchDescriptor>,
     pub(crate) copy: ComponentCopyFn,
-    pub(crate) copy_without_overriding: ComponentCopyFn,
+    /// Copy used on the instance root, respects the component [`ComponentMergePolicy`]
+    pub(crate) merge: ComponentCopyFn,
+    /// `None` for components only known through reflection, they can't be removed without their type
+    pub(crate) remove: Option<ComponentRemoveFn>,
+    /// Moves the component from one entity to another of the same world, `None` just like `remove`
+    pub(crate) take: Option<ComponentTakeFn>,
+    /// Private and prefab data components can't be written in prefab files
+    pub(crate) hidden: bool,
+}
+
+impl ComponentDescriptor {
+    /// Serializable version of the `entity` component, as it would be written in a prefab file
+    pub fn serializable(
+        &self,
+        world: &World,
+        entity: Entity,
+    ) -> Option<Box<dyn erased_serde::Serialize>> {
+        self.ser.as_ref().and_then(|ser| (ser)(world, entity))
+    }
 }
 
 pub(crate) type ComponentDescriptorRegistry = Registry<ComponentDescriptor>;
 
+/// Written by prefab instances to remove components, e.g. `Remove(Visible)`
+pub(crate) const COMPONENT_REMOVAL: &str = "Remove";
+
 impl Default for ComponentDescriptorRegistry {
     #[inline(always)]
     fn default() -> Self {
-        Self::empty()
+        Self::with_reserved(&[COMPONENT_REMOVAL])
     }
 }
 
@@ -48,9 +136,13 @@ impl ComponentDescriptorRegistry {
                 serde::de::IgnoredAny::deserialize(deserializer)?;
                 Ok(())
             },
+            None,
+            None,
             copy::<T>,
-            copy_without_overriding::<T>,
-        )
+            keep_instance::<T>,
+        )?;
+        self.hide::<T>();
+        Ok(())
     }
 
     /// Components that aren't serialized but must also be inserted
@@ -65,11 +157,49 @@ impl ComponentDescriptorRegistry {
                 entity.insert(T::default());
                 Ok(())
             },
+            None,
+            None,
             copy::<T>,
-            copy_without_overriding::<T>,
+            keep_instance::<T>,
         )
     }
 
+    /// Components that aren't serialized, built by the `constructor` for every entity copied out of
+    /// the prefab world; The prefab world only keeps a marker, so the `constructor` always runs with
+    /// the instance world resources
+    pub fn register_with_constructor<T, F>(&mut self, alias: String, constructor: F) -> Result<()>
+    where
+        T: Component,
+        F: Fn(&mut World, Entity) -> T + Send + Sync + 'static,
+    {
+        let constructor: ComponentConstructorFn<T> = Arc::new(constructor);
+
+        self.register_inner::<T>(
+            alias,
+            |deserializer, entity| {
+                serde::de::IgnoredAny::deserialize(deserializer)?;
+                entity.insert(ConstructedComponent::<T>(PhantomData));
+                Ok(())
+            },
+            None,
+            None,
+            |_, _, _, _| {},
+            |_, _, _, _| {},
+        )?;
+        self.register_private::<ConstructedComponent<T>>(format!(
+            "ConstructedComponent<{}>",
+            type_name::<T>()
+        ))?;
+
+        // `T` itself only shows up in the prefab world when inserted by hand
+        for type_id in &[TypeId::of::<T>(), TypeId::of::<ConstructedComponent<T>>()] {
+            let descriptor = self.find_by_type_mut(*type_id).unwrap();
+            descriptor.copy = construct_copy(constructor.clone());
+            descriptor.merge = construct_merge(constructor.clone());
+        }
+        Ok(())
+    }
+
     pub fn register<T>(&mut self, alias: String) -> Result<()>
     where
         T: Component + Clone + for<'de> Deserialize<'de> + 'static,
@@ -81,11 +211,116 @@ impl ComponentDescriptorRegistry {
                 entity.insert(value);
                 Ok(())
             },
+            None,
+            None,
+            copy::<T>,
+            keep_instance::<T>,
+        )
+    }
+
+    /// Component deserialized through the `P` proxy type, meant for components that can't be
+    /// deserialized or have an awkward shape on disk; See [`ComponentDescriptorRegistry::register_serialize_from`]
+    /// to also serialize it
+    pub fn register_from<P, T>(&mut self, alias: String) -> Result<()>
+    where
+        T: Component + Clone,
+        P: for<'de> Deserialize<'de> + Into<T> + 'static,
+    {
+        self.register_inner::<T>(
+            alias,
+            deserialize_from::<P, T>,
+            None,
+            None,
+            copy::<T>,
+            keep_instance::<T>,
+        )
+    }
+
+    /// Serializes the already registered component `T` through the `P` proxy type,
+    /// so it can be written back to prefab files
+    pub fn register_serialize_from<P, T>(&mut self) -> Result<(), RegistryError>
+    where
+        T: Component,
+        P: Serialize + for<'a> From<&'a T> + 'static,
+    {
+        let descriptor = self
+            .find_by_type_mut(TypeId::of::<T>())
+            .ok_or_else(|| RegistryError::TypeNotRegistered(type_name::<T>()))?;
+        descriptor.ser = Some(Arc::new(serialize_from::<P, T>));
+        Ok(())
+    }
+
+    /// Same as [`ComponentDescriptorRegistry::register`] but struct components can also be
+    /// partially overridden by prefab instances, only the fields written are changed
+    pub fn register_patchable<T>(
+        &mut self,
+        alias: String,
+        overrides: &mut OverrideRegistry,
+    ) -> Result<()>
+    where
+        T: Component + Clone + Reflect + Default + for<'de> Deserialize<'de> + 'static,
+    {
+        let default = T::default();
+        let patch = if let ReflectRef::Struct(value) = default.reflect_ref() {
+            overrides.register_struct_from_value(value)?;
+            overrides
+                .find::<T>()
+                .cloned()
+                .map(|overrides| ComponentPatchDescriptor {
+                    overrides,
+                    apply: Arc::new(apply_patch::<T>),
+                    reflect: Arc::new(reflect_component::<T>),
+                })
+        } else {
+            None
+        };
+
+        self.register_inner::<T>(
+            alias,
+            |deserializer, entity| {
+                let value: T = Deserialize::deserialize(deserializer)?;
+                entity.insert(value);
+                Ok(())
+            },
+            None,
+            patch,
             copy::<T>,
-            copy_without_overriding::<T>,
+            keep_instance::<T>,
         )
     }
 
+    /// Same as [`ComponentDescriptorRegistry::register`] but with a custom [`ComponentMergePolicy`];
+    /// Only [`ComponentMergePolicy::Reflect`] makes the component patchable, because it merges
+    /// the fields patched by the instance
+    pub fn register_with_merge_policy<T>(
+        &mut self,
+        alias: String,
+        merge_policy: ComponentMergePolicy,
+        overrides: &mut OverrideRegistry,
+    ) -> Result<()>
+    where
+        T: Component + Clone + Reflect + Default + for<'de> Deserialize<'de> + 'static,
+    {
+        let merge: CopyFn = match merge_policy {
+            ComponentMergePolicy::Replace => {
+                self.register::<T>(alias)?;
+                copy::<T>
+            }
+            ComponentMergePolicy::KeepInstance => {
+                self.register::<T>(alias)?;
+                keep_instance::<T>
+            }
+            ComponentMergePolicy::Reflect => {
+                self.register_patchable::<T>(alias, overrides)?;
+                reflect_merge::<T>
+            }
+        };
+
+        let descriptor = self.find_by_type_mut(TypeId::of::<T>()).unwrap();
+        descriptor.merge = Arc::new(merge);
+        Ok(())
+    }
+
     /// Prefab data is added component, but shouldn't be inserted as a normal component
     pub(crate) fn register_prefab_data<T>(&mut self, alias: String) -> Result<()>
     where
@@ -108,39 +343,362 @@ impl ComponentDescriptorRegistry {
                     .into(),
                 )
             },
+            None,
+            None,
             copy::<T>,
-            copy_without_overriding::<T>,
-        )
+            keep_instance::<T>,
+        )?;
+        self.hide::<T>();
+        Ok(())
     }
 
-    #[inline]
-    fn register_inner<T>(
+    /// Component declared in data, kept by the entity [`PrefabDynamicComponents`]; Prefab instances
+    /// can patch it's fields or remove it like any other patchable component
+    pub fn register_dynamic(
         &mut self,
-        alias: String,
-        de: ComponentDeserializerFn,
-        copy: ComponentCopyFn,
-        copy_without_overriding: ComponentCopyFn,
-    ) -> Result<()>
-    where
-        T: 'static,
-    {
-        // Make sure the uuid is unique
-        let mut uuid;
-        loop {
-            uuid = Uuid::new_v4();
-            if !self.by_uuid.contains_key(&uuid) {
-                break;
-            }
+        component_type: DynamicComponentType,
+        overrides: &mut OverrideRegistry,
+    ) -> Result<()> {
+        if self
+            .find_by_type(TypeId::of::<PrefabDynamicComponents>())
+            .is_none()
+        {
+            self.register_private::<PrefabDynamicComponents>(
+                "PrefabDynamicComponents".to_string(),
+            )?;
+            let descriptor = self
+                .find_by_type_mut(TypeId::of::<PrefabDynamicComponents>())
+                .unwrap();
+            descriptor.merge = Arc::new(merge_dynamic_components);
+            descriptor.take = Some(Arc::new(take_dynamic_components));
         }
 
-        let type_info = (TypeId::of::<T>(), uuid, type_name::<T>());
+        // registered once per type for the whole application lifetime
+        let name: &'static str = Box::leak(component_type.name.clone().into_boxed_str());
+
+        let layout = Arc::new(component_type.layout(name)?);
+        let patch_layout = layout.clone();
+        let patch = ComponentPatchDescriptor {
+            overrides: overrides.describe_dynamic_struct(
+                layout.default_value(),
+                &component_type.override_rules(),
+            )?,
+            apply: Arc::new(
+                move |world: &mut World,
+                      entity: Entity,
+                      patch: &dyn Override,
+                      report: &mut OverrideReporter| {
+                    let mut entity = world.entity_mut(entity);
+                    let missing = entity
+                        .get::<PrefabDynamicComponents>()
+                        .map_or(true, |components| !components.contains(name));
+                    if missing {
+                        // patch over the default value
+                        let default = patch_layout.default_value().clone_dynamic();
+                        PrefabDynamicComponents::insert_into(&mut entity, default);
+                    }
+
+                    let mut components = entity.get_mut::<PrefabDynamicComponents>().unwrap();
+                    patch.apply_override(components.get_mut(name).unwrap(), report);
+                },
+            ),
+            reflect: Arc::new(move |world: &World, entity: Entity| {
+                world
+                    .get::<PrefabDynamicComponents>(entity)
+                    .and_then(|components| components.get(name))
+                    .map(|value| value as &dyn Reflect)
+            }),
+        };
+
+        let uuid = self.unique_uuid();
+        self.register_untyped(name.to_string(), uuid, || ComponentDescriptor {
+            name,
+            de: Arc::new(
+                move |deserializer: &mut dyn erased_serde::Deserializer, entity: &mut EntityMut| {
+                    let value = layout.deserialize(deserializer)?;
+                    PrefabDynamicComponents::insert_into(entity, value);
+                    Ok(())
+                },
+            ),
+            ser: Some(Arc::new(move |world: &World, entity: Entity| {
+                world
+                    .get::<PrefabDynamicComponents>(entity)?
+                    .serializable(name)
+            })),
+            patch: Some(patch),
+            copy: Arc::new(
+                move |from_world: &World, to_world: &mut World, from_entity, to_entity| {
+                    copy_dynamic_component(name, from_world, to_world, from_entity, to_entity)
+                },
+            ),
+            merge: Arc::new(
+                move |from_world: &World, to_world: &mut World, from_entity, to_entity| {
+                    // keep instance
+                    let missing = to_world
+                        .get::<PrefabDynamicComponents>(to_entity)
+                        .map_or(true, |components| !components.contains(name));
+                    if missing {
+                        copy_dynamic_component(name, from_world, to_world, from_entity, to_entity)
+                    }
+                },
+            ),
+            remove: Some(Arc::new(move |entity: &mut EntityMut| {
+                if let Some(mut components) = entity.get_mut::<PrefabDynamicComponents>() {
+                    components.remove(name);
+                }
+            })),
+            take: Some(Arc::new(
+                move |world: &mut World, from_entity, to_entity| {
+                    let value = world
+                        .get_mut::<PrefabDynamicComponents>(from_entity)
+                        .and_then(|mut components| components.remove(name));
+                    if let Some(value) = value {
+                        PrefabDynamicComponents::insert_into(
+                            &mut world.entity_mut(to_entity),
+                            value,
+                        );
+                    }
+                },
+            )),
+            hidden: false,
+        })?;
+        Ok(())
+    }
+
+    #[inline]
+    fn register_inner<T: Component>(
+        &mut self,
+        alias: String,
+        de: DeserializerFn,
+        ser: Option<ComponentSerializerFn>,
+        patch: Option<ComponentPatchDescriptor>,
+        copy: CopyFn,
+        merge: CopyFn,
+    ) -> Result<()> {
+        let type_info = (TypeId::of::<T>(), self.unique_uuid(), type_name::<T>());
         self.register_internal(alias, type_info, || ComponentDescriptor {
-            de,
-            copy,
-            copy_without_overriding,
+            name: type_name::<T>(),
+            de: Arc::new(de),
+            ser,
+            patch,
+            copy: Arc::new(copy),
+            merge: Arc::new(merge),
+            remove: Some(Arc::new(remove::<T>)),
+            take: Some(Arc::new(take::<T>)),
+            hidden: false,
         })?;
         Ok(())
     }
+
+    fn hide<T: Component>(&mut self) {
+        if let Some(descriptor) = self.find_by_type_mut(TypeId::of::<T>()) {
+            descriptor.hidden = true;
+        }
+    }
+
+    /// Registers every component of the `type_registry` with both [`ReflectComponent`] and
+    /// [`ReflectDeserialize`] type data, deserialized, copied and entity mapped through reflection;
+    /// Components already registered are left untouched, returns how many were added
+    ///
+    /// **NOTE** These components keep the instance version on merge, can't be patched by prefab
+    /// instances nor removed by them, files that try fail to load; Register them explicitly when that's needed
+    pub(crate) fn register_reflected(
+        &mut self,
+        type_registry: &TypeRegistry,
+        component_entity_mapper: &mut ComponentEntityMapperRegistry,
+    ) -> usize {
+        let mut count = 0;
+        for registration in type_registry.iter() {
+            let (reflect_component, reflect_deserialize) = match (
+                registration.data::<ReflectComponent>(),
+                registration.data::<ReflectDeserialize>(),
+            ) {
+                (Some(reflect_component), Some(reflect_deserialize)) => {
+                    (reflect_component.clone(), reflect_deserialize.clone())
+                }
+                _ => continue,
+            };
+
+            let type_id = registration.type_id();
+            if self.by_type.contains_key(&type_id) {
+                continue;
+            }
+
+            // registered once per type for the whole application lifetime
+            let name: &'static str = Box::leak(registration.name().to_string().into_boxed_str());
+
+            let alias = shorten_name(name);
+            let uuid = self.unique_uuid();
+            let mapper_reflect_component = reflect_component.clone();
+            let result = self.register_internal(alias, (type_id, uuid, name), || {
+                reflected_descriptor(name, reflect_component, reflect_deserialize)
+            });
+
+            match result {
+                Ok(_) => {
+                    component_entity_mapper.register_reflected(type_id, mapper_reflect_component);
+                    count += 1;
+                }
+                Err(err) => warn!("reflected component `{}` skipped, {}", name, err),
+            }
+        }
+        count
+    }
+}
+
+/// Descriptor of a component only known through reflection
+fn reflected_descriptor(
+    name: &'static str,
+    reflect_component: ReflectComponent,
+    reflect_deserialize: ReflectDeserialize,
+) -> ComponentDescriptor {
+    let de_reflect_component = reflect_component.clone();
+    let copy_reflect_component = reflect_component.clone();
+
+    ComponentDescriptor {
+        name,
+        de: Arc::new(
+            move |deserializer: &mut dyn erased_serde::Deserializer, entity: &mut EntityMut| {
+                let value = reflect_deserialize.deserialize(deserializer)?;
+                let id = entity.id();
+                // SAFETY: entity location is updated right after the component is added
+                unsafe {
+                    de_reflect_component.add_component(entity.world_mut(), id, &*value);
+                    entity.update_location();
+                }
+                Ok(())
+            },
+        ),
+        ser: None,
+        patch: None,
+        copy: Arc::new(
+            move |from_world: &World, to_world: &mut World, from_entity, to_entity| {
+                copy_reflect_component.copy_component(from_world, to_world, from_entity, to_entity)
+            },
+        ),
+        merge: Arc::new(
+            move |from_world: &World, to_world: &mut World, from_entity, to_entity| {
+                // keep instance
+                if reflect_component
+                    .reflect_component(to_world, to_entity)
+                    .is_none()
+                {
+                    reflect_component.copy_component(from_world, to_world, from_entity, to_entity)
+                }
+            },
+        ),
+        // TODO: needs a way to remove components without knowing their type,
+        // until then prefab instances can't remove nor override them in nested entities
+        remove: None,
+        take: None,
+        hidden: false,
+    }
+}
+
+fn copy_dynamic_component(
+    name: &str,
+    from_world: &World,
+    to_world: &mut World,
+    from_entity: Entity,
+    to_entity: Entity,
+) {
+    let value = from_world
+        .get::<PrefabDynamicComponents>(from_entity)
+        .and_then(|components| components.get(name));
+    if let Some(value) = value {
+        PrefabDynamicComponents::insert_into(
+            &mut to_world.entity_mut(to_entity),
+            value.clone_dynamic(),
+        );
+    }
+}
+
+/// Keeps the instance dynamic components, only the missing ones are added
+fn merge_dynamic_components(
+    from_world: &World,
+    to_world: &mut World,
+    from_entity: Entity,
+    to_entity: Entity,
+) {
+    let from = from_world
+        .get::<PrefabDynamicComponents>(from_entity)
+        .unwrap();
+    let mut to = to_world.entity_mut(to_entity);
+    if let Some(mut instance) = to.get_mut::<PrefabDynamicComponents>() {
+        for value in from.iter() {
+            if !instance.contains(value.name()) {
+                instance.insert(value.clone_dynamic());
+            }
+        }
+    } else {
+        to.insert(from.clone());
+    }
+}
+
+/// Only the dynamic components present in the `from_entity` are replaced
+fn take_dynamic_components(world: &mut World, from_entity: Entity, to_entity: Entity) {
+    if let Some(components) = world
+        .entity_mut(from_entity)
+        .remove::<PrefabDynamicComponents>()
+    {
+        let mut to = world.entity_mut(to_entity);
+        for value in components.iter() {
+            PrefabDynamicComponents::insert_into(&mut to, value.clone_dynamic());
+        }
+    }
+}
+
+/// Stands for a component built by it's constructor once copied out of the prefab world
+struct ConstructedComponent<T>(PhantomData<fn() -> T>);
+
+impl<T> Clone for ConstructedComponent<T> {
+    fn clone(&self) -> Self {
+        ConstructedComponent(PhantomData)
+    }
+}
+
+fn construct_copy<T: Component>(constructor: ComponentConstructorFn<T>) -> ComponentCopyFn {
+    Arc::new(
+        move |_: &World, to_world: &mut World, _: Entity, to_entity: Entity| {
+            let component = (constructor)(to_world, to_entity);
+            to_world.entity_mut(to_entity).insert(component);
+        },
+    )
+}
+
+/// Keeps the instance component, the `constructor` only runs when it's missing
+fn construct_merge<T: Component>(constructor: ComponentConstructorFn<T>) -> ComponentCopyFn {
+    Arc::new(
+        move |_: &World, to_world: &mut World, _: Entity, to_entity: Entity| {
+            if !to_world.entity(to_entity).contains::<T>() {
+                let component = (constructor)(to_world, to_entity);
+                to_world.entity_mut(to_entity).insert(component);
+            }
+        },
+    )
+}
+
+fn deserialize_from<P, T>(
+    deserializer: &mut dyn erased_serde::Deserializer,
+    entity: &mut EntityMut,
+) -> Result<()>
+where
+    T: Component,
+    P: for<'de> Deserialize<'de> + Into<T>,
+{
+    let proxy: P = Deserialize::deserialize(deserializer)?;
+    entity.insert(proxy.into());
+    Ok(())
+}
+
+fn serialize_from<P, T>(world: &World, entity: Entity) -> Option<Box<dyn erased_serde::Serialize>>
+where
+    T: Component,
+    P: Serialize + for<'a> From<&'a T> + 'static,
+{
+    world
+        .get::<T>(entity)
+        .map(|component| Box::new(P::from(component)) as Box<dyn erased_serde::Serialize>)
 }
 
 fn copy<T: Component + Clone>(
@@ -153,19 +711,504 @@ fn copy<T: Component + Clone>(
     to_world.entity_mut(to_entity).insert(from.clone());
 }
 
-fn copy_without_overriding<T: Component + Clone>(
+fn remove<T: Component>(entity: &mut EntityMut) {
+    entity.remove::<T>();
+}
+
+fn take<T: Component>(world: &mut World, from_entity: Entity, to_entity: Entity) {
+    if let Some(component) = world.entity_mut(from_entity).remove::<T>() {
+        world.entity_mut(to_entity).insert(component);
+    }
+}
+
+fn keep_instance<T: Component + Clone>(
     from_world: &World,
     to_world: &mut World,
     from_entity: Entity,
     to_entity: Entity,
 ) {
     let mut to = to_world.entity_mut(to_entity);
-    if to.contains::<T>() {
+    if !to.contains::<T>() {
         let from = from_world.get::<T>(from_entity).unwrap();
         to.insert(from.clone());
     }
 }
 
+/// Source prefab component with the fields written by the instance patched over it,
+/// see [`ComponentMergePolicy::Reflect`]
+fn reflect_merge<T: Component + Clone + Reflect>(
+    from_world: &World,
+    to_world: &mut World,
+    from_entity: Entity,
+    to_entity: Entity,
+) {
+    let mut to = to_world.entity_mut(to_entity);
+    if to.contains::<T>() {
+        // inserted as a whole, there's no telling which fields were written
+        return;
+    }
+
+    let mut merged = from_world.get::<T>(from_entity).unwrap().clone();
+    let mut report = PrefabOverrideReport::default();
+    if let Some(mut overrides) = to.get_mut::<PrefabComponentOverrides>() {
+        overrides.apply_root_patches(type_name::<T>(), &mut merged, &mut report);
+    }
+    to.insert(merged);
+
+    // the other overrides are appended to this report later on
+    if !report.entries.is_empty() {
+        if let Some(mut merged_report) = to.get_mut::<PrefabOverrideReport>() {
+            merged_report.entries.append(&mut report.entries);
+        } else {
+            to.insert(report);
+        }
+    }
+}
+
+fn apply_patch<T: Component + Reflect + Default>(
+    world: &mut World,
+    entity: Entity,
+    patch: &dyn Override,
+    report: &mut OverrideReporter,
+) {
+    let mut entity = world.entity_mut(entity);
+    if let Some(mut component) = entity.get_mut::<T>() {
+        patch.apply_override(&mut *component, report);
+    } else {
+        // patch over the default value
+        let mut component = T::default();
+        patch.apply_override(&mut component, report);
+        entity.insert(component);
+    }
+}
+
+fn reflect_component<T: Component + Reflect>(
+    world: &World,
+    entity: Entity,
+) -> Option<&dyn Reflect> {
+    world
+        .get::<T>(entity)
+        .map(|component| component as &dyn Reflect)
+}
+
 // TODO: Save and load between interation and new component
 // /// List of all Uuids for each component alias
 // pub struct TableOfComponentsUuidByName(Vec<(String, Uuid)>);
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::{entity::EntityMap, reflect::ReflectComponent, world::World},
+        reflect::{Reflect, ReflectDeserialize, TypeRegistry},
+    };
+    use serde::{de::DeserializeSeed, Deserialize, Serialize};
+
+    use super::*;
+    use crate::{
+        data::{ComponentOverride, ComponentOverrideOp},
+        path::PrefabEntityPath,
+    };
+
+    #[derive(Default, Debug, Deserialize, PartialEq, Clone, Reflect)]
+    struct Kept(u32);
+
+    #[derive(Default, Debug, Deserialize, PartialEq, Clone, Reflect)]
+    struct Replaced(u32);
+
+    #[derive(Default, Debug, Deserialize, PartialEq, Clone, Reflect)]
+    struct Merged {
+        intensity: f32,
+        range: f32,
+    }
+
+    #[derive(Default, Debug, Deserialize, PartialEq, Clone, Reflect)]
+    #[reflect(Component, Deserialize)]
+    struct Tinted {
+        r: f32,
+    }
+
+    #[derive(Default, Debug, Deserialize, PartialEq, Clone, Reflect)]
+    #[reflect(Component, Deserialize)]
+    struct Aimed {
+        target: Option<Entity>,
+    }
+
+    #[test]
+    fn reflected_components() {
+        let mut type_registry = TypeRegistry::default();
+        type_registry.register::<Tinted>();
+        type_registry.register::<Aimed>();
+        type_registry.register::<Kept>();
+
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        let mut component_entity_mapper = ComponentEntityMapperRegistry::default();
+        assert_eq!(
+            component_registry.register_reflected(&type_registry, &mut component_entity_mapper),
+            2
+        );
+        assert_eq!(
+            component_registry.register_reflected(&type_registry, &mut component_entity_mapper),
+            0
+        );
+        assert!(component_registry.find_by_name("Kept").is_none());
+
+        // can't be removed nor moved around without knowing their type
+        let descriptor = component_registry.find_by_name("Tinted").unwrap();
+        assert!(descriptor.remove.is_none());
+        assert!(descriptor.take.is_none());
+
+        let mut world = World::default();
+        let entity = world.spawn().id();
+        let mut deserializer = ron::de::Deserializer::from_str("(r: 0.5)").unwrap();
+        (descriptor.de)(
+            &mut <dyn erased_serde::Deserializer>::erase(&mut deserializer),
+            &mut world.entity_mut(entity),
+        )
+        .unwrap();
+        assert_eq!(world.get::<Tinted>(entity), Some(&Tinted { r: 0.5 }));
+
+        let mut other_world = World::default();
+        let other_entity = other_world.spawn().id();
+        (descriptor.copy)(&world, &mut other_world, entity, other_entity);
+        assert_eq!(
+            other_world.get::<Tinted>(other_entity),
+            Some(&Tinted { r: 0.5 })
+        );
+
+        // entities are mapped through reflection
+        let mut world = World::default();
+        let target = world.spawn().id();
+        let mapped = world.spawn().id();
+        let entity = world
+            .spawn()
+            .insert(Aimed {
+                target: Some(target),
+            })
+            .id();
+        let mut entity_map = EntityMap::default();
+        entity_map.insert(target, mapped);
+        component_entity_mapper
+            .map_entity_components(&mut world.entity_mut(entity), &entity_map)
+            .unwrap();
+        assert_eq!(world.get::<Aimed>(entity).unwrap().target, Some(mapped));
+
+        entity_map.insert(mapped, target);
+        entity_map.insert(entity, entity);
+        component_entity_mapper
+            .map_world_components(&mut world, &entity_map)
+            .unwrap();
+        assert_eq!(world.get::<Aimed>(entity).unwrap().target, Some(target));
+    }
+
+    /// Angle in radians, not serializable
+    #[derive(Debug, PartialEq, Clone)]
+    struct Angle(f32);
+
+    #[derive(Deserialize, Serialize)]
+    struct Degrees(f32);
+
+    impl From<Degrees> for Angle {
+        fn from(degrees: Degrees) -> Self {
+            Angle(degrees.0.to_radians())
+        }
+    }
+
+    impl From<&Angle> for Degrees {
+        fn from(angle: &Angle) -> Self {
+            Degrees(angle.0.to_degrees())
+        }
+    }
+
+    #[test]
+    fn proxy_components() {
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register_from::<Degrees, Angle>("Angle".to_string())
+            .unwrap();
+        let descriptor = component_registry.find_by_name("Angle").unwrap();
+
+        let mut world = World::default();
+        let entity = world.spawn().id();
+        let mut deserializer = ron::de::Deserializer::from_str("(180.0)").unwrap();
+        (descriptor.de)(
+            &mut <dyn erased_serde::Deserializer>::erase(&mut deserializer),
+            &mut world.entity_mut(entity),
+        )
+        .unwrap();
+        assert_eq!(
+            world.get::<Angle>(entity),
+            Some(&Angle(std::f32::consts::PI))
+        );
+
+        // serialization is opt-in
+        assert!(descriptor.serializable(&world, entity).is_none());
+        component_registry
+            .register_serialize_from::<Degrees, Angle>()
+            .unwrap();
+        let descriptor = component_registry.find_by_name("Angle").unwrap();
+        let value = descriptor.serializable(&world, entity).unwrap();
+        assert_eq!(ron::ser::to_string(&value).unwrap(), "(180)");
+    }
+
+    /// Handle like component, every instance must get it's own
+    #[derive(Debug, PartialEq)]
+    struct Material(u32);
+
+    #[derive(Default)]
+    struct Materials(u32);
+
+    #[test]
+    fn constructed_components() {
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register_with_constructor("Material".to_string(), |world, _| {
+                let mut materials = world.get_resource_mut::<Materials>().unwrap();
+                materials.0 += 1;
+                Material(materials.0)
+            })
+            .unwrap();
+        let descriptor = component_registry.find_by_name("Material").unwrap();
+
+        // prefab world has no resources
+        let mut prefab_world = World::default();
+        let prefab_entity = prefab_world.spawn().id();
+        let mut deserializer = ron::de::Deserializer::from_str("()").unwrap();
+        (descriptor.de)(
+            &mut <dyn erased_serde::Deserializer>::erase(&mut deserializer),
+            &mut prefab_world.entity_mut(prefab_entity),
+        )
+        .unwrap();
+        assert!(prefab_world.get::<Material>(prefab_entity).is_none());
+
+        let marker = component_registry
+            .find_by_type(TypeId::of::<ConstructedComponent<Material>>())
+            .unwrap();
+        assert!(marker.hidden);
+
+        let mut world = World::default();
+        world.insert_resource(Materials::default());
+        let first = world.spawn().id();
+        let second = world.spawn().id();
+        (marker.copy)(&prefab_world, &mut world, prefab_entity, first);
+        (marker.copy)(&prefab_world, &mut world, prefab_entity, second);
+        assert_eq!(world.get::<Material>(first), Some(&Material(1)));
+        assert_eq!(world.get::<Material>(second), Some(&Material(2)));
+
+        // instance component is kept
+        (marker.merge)(&prefab_world, &mut world, prefab_entity, first);
+        assert_eq!(world.get::<Material>(first), Some(&Material(1)));
+    }
+
+    #[test]
+    fn dynamic_components() {
+        let component_types = DynamicComponentType::from_ron(
+            r#"[
+                (name: "Explosive", fields: [
+                    (name: "radius", type: "f32", default: 2, constraints: [Range(min: 0, max: 10)]),
+                ]),
+                (name: "Loot"),
+            ]"#,
+        )
+        .unwrap();
+
+        let mut overrides = OverrideRegistry::default();
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        for component_type in component_types {
+            component_registry
+                .register_dynamic(component_type, &mut overrides)
+                .unwrap();
+        }
+
+        let mut world = World::default();
+        let entity = world.spawn().id();
+        for (name, source) in &[("Explosive", "(radius: 3)"), ("Loot", "()")] {
+            let descriptor = component_registry.find_by_name(name).unwrap();
+            let mut deserializer = ron::de::Deserializer::from_str(source).unwrap();
+            (descriptor.de)(
+                &mut <dyn erased_serde::Deserializer>::erase(&mut deserializer),
+                &mut world.entity_mut(entity),
+            )
+            .unwrap();
+        }
+
+        let components = world.get::<PrefabDynamicComponents>(entity).unwrap();
+        assert!(components.contains("Loot"));
+        assert_eq!(components.field::<f32>("Explosive", "radius"), Some(&3.0));
+
+        let descriptor = component_registry.find_by_name("Explosive").unwrap();
+        let value = descriptor.serializable(&world, entity).unwrap();
+        let output = ron::ser::to_string(&value).unwrap();
+        assert_eq!(output, "(radius:3)");
+        assert_eq!(
+            ron::ser::to_string(components).unwrap(),
+            "(Explosive:(radius:3),Loot:())"
+        );
+
+        // round trip
+        let round_trip = world.spawn().id();
+        let mut deserializer = ron::de::Deserializer::from_str(&output).unwrap();
+        (descriptor.de)(
+            &mut <dyn erased_serde::Deserializer>::erase(&mut deserializer),
+            &mut world.entity_mut(round_trip),
+        )
+        .unwrap();
+        let components = world.get::<PrefabDynamicComponents>(round_trip).unwrap();
+        assert_eq!(components.field::<f32>("Explosive", "radius"), Some(&3.0));
+
+        // patches
+        let patch = descriptor.patch.as_ref().unwrap();
+        let mut deserializer = ron::de::Deserializer::from_str("(radius: 50)").unwrap();
+        assert!(patch.overrides.deserialize(&mut deserializer).is_err());
+
+        let mut deserializer = ron::de::Deserializer::from_str("(radius: 5)").unwrap();
+        let value = patch.overrides.deserialize(&mut deserializer).unwrap();
+        let mut report = crate::data::PrefabOverrideReport::default();
+        let mut reporter = report.reporter(Default::default(), Default::default(), None);
+        let other_entity = world.spawn().id();
+        (patch.apply)(&mut world, other_entity, &*value, &mut reporter);
+        let components = world.get::<PrefabDynamicComponents>(other_entity).unwrap();
+        assert_eq!(components.field::<f32>("Explosive", "radius"), Some(&5.0));
+        assert!(!components.contains("Loot"));
+
+        // instance components are kept
+        let storage = component_registry
+            .find_by_type(TypeId::of::<PrefabDynamicComponents>())
+            .unwrap();
+        let mut instance_world = World::default();
+        let instance_entity = instance_world.spawn().id();
+        (storage.copy)(&world, &mut instance_world, other_entity, instance_entity);
+        (storage.merge)(&world, &mut instance_world, entity, instance_entity);
+        let components = instance_world
+            .get::<PrefabDynamicComponents>(instance_entity)
+            .unwrap();
+        assert_eq!(components.field::<f32>("Explosive", "radius"), Some(&5.0));
+        assert!(components.contains("Loot"));
+
+        (descriptor.remove.as_ref().unwrap())(&mut instance_world.entity_mut(instance_entity));
+        let components = instance_world
+            .get::<PrefabDynamicComponents>(instance_entity)
+            .unwrap();
+        assert!(!components.contains("Explosive"));
+    }
+
+    #[test]
+    fn deprecated_aliases() {
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register::<Kept>("Kept".to_string())
+            .unwrap();
+        component_registry
+            .register_deprecated_alias::<Kept>("Held".to_string())
+            .unwrap();
+        assert!(component_registry
+            .register_deprecated_alias::<Kept>("Kept".to_string())
+            .is_err());
+        assert!(component_registry
+            .register_deprecated_alias::<Replaced>("Swapped".to_string())
+            .is_err());
+
+        assert_eq!(component_registry.canonical_name("Held"), Some("Kept"));
+        assert_eq!(component_registry.canonical_name("Kept"), None);
+        assert_eq!(
+            component_registry.find_by_name("Held").unwrap().name,
+            component_registry.find_by_name("Kept").unwrap().name
+        );
+    }
+
+    #[test]
+    fn merge_policies() {
+        let mut overrides = OverrideRegistry::default();
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register::<Kept>("Kept".to_string())
+            .unwrap();
+        component_registry
+            .register_with_merge_policy::<Replaced>(
+                "Replaced".to_string(),
+                ComponentMergePolicy::Replace,
+                &mut overrides,
+            )
+            .unwrap();
+        component_registry
+            .register_with_merge_policy::<Merged>(
+                "Merged".to_string(),
+                ComponentMergePolicy::Reflect,
+                &mut overrides,
+            )
+            .unwrap();
+
+        let mut prefab_world = World::default();
+        let prefab_root = prefab_world
+            .spawn()
+            .insert_bundle((
+                Kept(1),
+                Replaced(1),
+                Merged {
+                    intensity: 1.0,
+                    range: 1.0,
+                },
+            ))
+            .id();
+
+        // instance only wrote `intensity`, even if `range` has it's default value
+        let descriptor = component_registry.find_by_name("Merged").unwrap();
+        let patch = descriptor.patch.as_ref().unwrap();
+        let mut deserializer = ron::de::Deserializer::from_str("( intensity: 2 )").unwrap();
+        let value = patch.overrides.deserialize(&mut deserializer).unwrap();
+        let component_overrides = PrefabComponentOverrides(vec![ComponentOverride {
+            source: Default::default(),
+            path: PrefabEntityPath::root(),
+            op: ComponentOverrideOp::Patch {
+                component: descriptor.name,
+                apply: patch.apply.clone(),
+                value,
+            },
+        }]);
+
+        let mut world = World::default();
+        let instance_root = world
+            .spawn()
+            .insert_bundle((Replaced(2), component_overrides))
+            .id();
+        let other_instance_root = world
+            .spawn()
+            .insert(Merged {
+                intensity: 2.0,
+                range: 0.0,
+            })
+            .id();
+
+        for name in &["Kept", "Replaced", "Merged"] {
+            let descriptor = component_registry.find_by_name(name).unwrap();
+            (descriptor.merge)(&prefab_world, &mut world, prefab_root, instance_root);
+            (descriptor.merge)(&prefab_world, &mut world, prefab_root, other_instance_root);
+        }
+
+        assert_eq!(world.get::<Kept>(instance_root), Some(&Kept(1)));
+        assert_eq!(world.get::<Replaced>(instance_root), Some(&Replaced(1)));
+        assert_eq!(
+            world.get::<Merged>(instance_root),
+            Some(&Merged {
+                intensity: 2.0,
+                range: 1.0,
+            })
+        );
+
+        // component inserted as a whole isn't merged
+        assert_eq!(
+            world.get::<Merged>(other_instance_root),
+            Some(&Merged {
+                intensity: 2.0,
+                range: 0.0,
+            })
+        );
+
+        // merged patches are reported once and aren't applied again with the other overrides
+        let report = world.get::<PrefabOverrideReport>(instance_root).unwrap();
+        assert_eq!(report.applied().count(), 1);
+        let component_overrides = world
+            .get::<PrefabComponentOverrides>(instance_root)
+            .unwrap();
+        assert!(component_overrides.0.is_empty());
+    }
+}
@@ -1,8 +1,16 @@
+use std::any::TypeId;
+
 use anyhow::Result;
-use bevy::ecs::{
-    component::Component,
-    entity::{EntityMap, MapEntities},
-    world::{EntityMut, World},
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::{Entity, EntityMap, MapEntities, MapEntitiesError},
+        reflect::ReflectComponent,
+        world::{EntityMut, World},
+    },
+    prelude::warn,
+    reflect::{Reflect, ReflectMut, ReflectRef},
+    utils::HashMap,
 };
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -11,10 +19,14 @@ pub type MapWorldComponentsFn = fn(&mut World, &EntityMap) -> Result<()>;
 
 pub type MapEntityComponentsFn = fn(&mut EntityMut, &EntityMap) -> Result<()>;
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub(crate) struct ComponentEntityMapperRegistry {
     world: Vec<MapWorldComponentsFn>,
     entity: Vec<MapEntityComponentsFn>,
+    /// Index of each registered component mappers
+    by_type: HashMap<TypeId, usize>,
+    /// Components only known through reflection, mapped with [`map_reflect_entities`]
+    reflected: HashMap<TypeId, ReflectComponent>,
 }
 
 impl ComponentEntityMapperRegistry {
@@ -23,10 +35,30 @@ impl ComponentEntityMapperRegistry {
         for map in &self.world {
             (map)(world, &entity_map)?;
         }
+
+        for (type_id, reflect_component) in &self.reflected {
+            let component_id = match world.components().get_id(*type_id) {
+                Some(component_id) => component_id,
+                None => continue,
+            };
+            let entities: Vec<Entity> = world
+                .archetypes()
+                .iter()
+                .filter(|archetype| archetype.contains(component_id))
+                .flat_map(|archetype| archetype.entities().iter().copied())
+                .collect();
+            for entity in entities {
+                let mut component = reflect_component
+                    .reflect_component_mut(world, entity)
+                    .unwrap();
+                map_reflect_entities(&mut *component, entity_map)?;
+            }
+        }
         Ok(())
     }
 
-    /// Map entities on all registered components for a single [`Entity`]
+    /// Map entities on all registered components for a single [`Entity`],
+    /// entities missing from `entity_map` are left unchanged
     pub fn map_entity_components(
         &self,
         entity: &mut EntityMut,
@@ -35,29 +67,343 @@ impl ComponentEntityMapperRegistry {
         for map in &self.entity {
             (map)(entity, &entity_map)?;
         }
+
+        let id = entity.id();
+        for reflect_component in self.reflected.values() {
+            // SAFETY: the entity is borrowed mutably so no one else is holding this component
+            let component =
+                unsafe { reflect_component.reflect_component_unchecked_mut(entity.world(), id) };
+            if let Some(mut component) = component {
+                map_known_reflect_entities(&mut *component, entity_map)?;
+            }
+        }
         Ok(())
     }
 
-    /// Register a component that references other entities
+    /// Register a component that references other entities, replaces the [`Reflect`] based
+    /// mapping of the same component (if any)
     pub fn register<T>(&mut self)
     where
         T: Component + MapEntities + Clone,
     {
-        // maps entities all components in the world
-        self.world.push(|world, entity_map| {
-            let mut query = world.query::<&mut T>();
-            for mut component in query.iter_mut(world) {
-                component.map_entities(entity_map)?;
+        self.insert::<T>(
+            // maps entities all components in the world
+            |world, entity_map| {
+                let mut query = world.query::<&mut T>();
+                for mut component in query.iter_mut(world) {
+                    component.map_entities(entity_map)?;
+                }
+                Ok(())
+            },
+            // maps entities in this component for a single entity
+            |entity, entity_map| {
+                map_known_entities::<T>(entity, entity_map);
+                Ok(())
+            },
+        );
+    }
+
+    /// Register a component whose entities are found through [`Reflect`], see [`map_reflect_entities`];
+    /// Components already registered with their own [`MapEntities`] are left untouched
+    ///
+    /// **NOTE** Entities inside maps aren't mapped, use [`ComponentEntityMapperRegistry::register_reflect_checked`]
+    /// to be warned about them
+    pub fn register_reflect<T>(&mut self)
+    where
+        T: Component + Reflect,
+    {
+        if self.by_type.contains_key(&TypeId::of::<T>()) {
+            return;
+        }
+
+        self.insert::<T>(
+            |world, entity_map| {
+                let mut query = world.query::<&mut T>();
+                for mut component in query.iter_mut(world) {
+                    map_reflect_entities(&mut *component, entity_map)?;
+                }
+                Ok(())
+            },
+            |entity, entity_map| {
+                if let Some(mut component) = entity.get_mut::<T>() {
+                    map_known_reflect_entities(&mut *component, entity_map)?;
+                }
+                Ok(())
+            },
+        );
+    }
+
+    /// Same as [`ComponentEntityMapperRegistry::register_reflect`] but warns when the default value
+    /// of the component has maps, since their entities can't be mapped
+    pub fn register_reflect_checked<T>(&mut self)
+    where
+        T: Component + Reflect + Default,
+    {
+        if self.by_type.contains_key(&TypeId::of::<T>()) {
+            return;
+        }
+
+        if let Some(map) = find_reflect_map(&T::default()) {
+            warn!(
+                "entities inside `{}` of `{}` can't be mapped, maps aren't supported",
+                map,
+                std::any::type_name::<T>()
+            );
+        }
+
+        self.register_reflect::<T>();
+    }
+
+    /// Register a component only known through reflection, see [`map_reflect_entities`];
+    /// Components already registered are left untouched
+    pub fn register_reflected(&mut self, type_id: TypeId, reflect_component: ReflectComponent) {
+        if self.by_type.contains_key(&type_id) {
+            return;
+        }
+        self.reflected.entry(type_id).or_insert(reflect_component);
+    }
+
+    fn insert<T: Component>(&mut self, world: MapWorldComponentsFn, entity: MapEntityComponentsFn) {
+        self.reflected.remove(&TypeId::of::<T>());
+        match self.by_type.get(&TypeId::of::<T>()) {
+            Some(index) => {
+                self.world[*index] = world;
+                self.entity[*index] = entity;
             }
-            Ok(())
-        });
+            None => {
+                self.by_type.insert(TypeId::of::<T>(), self.world.len());
+                self.world.push(world);
+                self.entity.push(entity);
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Maps the entities of a [`MapEntities`] component, leaving the ones outside `entity_map` unchanged
+fn map_known_entities<T>(entity: &mut EntityMut, entity_map: &EntityMap)
+where
+    T: Component + MapEntities + Clone,
+{
+    let mut mapped = match entity.get::<T>() {
+        Some(component) => component.clone(),
+        None => return,
+    };
+
+    if mapped.map_entities(entity_map).is_err() {
+        // `MapEntities` stops at the first unknown entity, so every entity of the world
+        // is mapped to itself unless `entity_map` says otherwise, letting the whole component
+        // go through at once
+        let mut known = EntityMap::default();
+        for archetype in entity.world().archetypes().iter() {
+            for other in archetype.entities() {
+                known.insert(*other, *other);
+            }
+        }
+        for from in entity_map.keys() {
+            known.insert(from, entity_map.get(from).unwrap());
+        }
 
-        // maps entities in this component for a single entity
-        self.entity.push(|entity, entity_map| {
-            if let Some(mut component) = entity.get_mut::<T>() {
-                component.map_entities(entity_map)?;
+        loop {
+            mapped = entity.get::<T>().unwrap().clone();
+            match mapped.map_entities(&known) {
+                Ok(()) => break,
+                // despawned entities are kept as well
+                Err(MapEntitiesError::EntityNotFound(missing)) => known.insert(missing, missing),
             }
+        }
+    }
+
+    *entity.get_mut::<T>().unwrap() = mapped;
+}
+
+/// Maps every [`Entity`] found inside `value`, including the ones nested
+/// in structs, tuples, lists and `Option<Entity>` values
+pub fn map_reflect_entities(
+    value: &mut dyn Reflect,
+    entity_map: &EntityMap,
+) -> Result<(), MapEntitiesError> {
+    visit_reflect_entities(value, &mut |entity| {
+        *entity = entity_map.get(*entity)?;
+        Ok(())
+    })
+}
+
+/// Same as [`map_reflect_entities`] but leaves the entities outside `entity_map` unchanged
+fn map_known_reflect_entities(
+    value: &mut dyn Reflect,
+    entity_map: &EntityMap,
+) -> Result<(), MapEntitiesError> {
+    visit_reflect_entities(value, &mut |entity| {
+        if let Ok(mapped) = entity_map.get(*entity) {
+            *entity = mapped;
+        }
+        Ok(())
+    })
+}
+
+fn visit_reflect_entities(
+    value: &mut dyn Reflect,
+    visit: &mut dyn FnMut(&mut Entity) -> Result<(), MapEntitiesError>,
+) -> Result<(), MapEntitiesError> {
+    match value.reflect_mut() {
+        ReflectMut::Struct(value) => {
+            for i in 0..value.field_len() {
+                visit_reflect_entities(value.field_at_mut(i).unwrap(), visit)?;
+            }
+        }
+        ReflectMut::TupleStruct(value) => {
+            for i in 0..value.field_len() {
+                visit_reflect_entities(value.field_mut(i).unwrap(), visit)?;
+            }
+        }
+        ReflectMut::Tuple(value) => {
+            for i in 0..value.field_len() {
+                visit_reflect_entities(value.field_mut(i).unwrap(), visit)?;
+            }
+        }
+        ReflectMut::List(value) => {
+            for i in 0..value.len() {
+                visit_reflect_entities(value.get_mut(i).unwrap(), visit)?;
+            }
+        }
+        ReflectMut::Map(_) => {
+            // TODO: maps don't give mutable access to their values by index,
+            // they are reported once when the component is registered
+        }
+        ReflectMut::Value(value) => {
+            let value = value.any_mut();
+            if let Some(entity) = value.downcast_mut::<Entity>() {
+                visit(entity)?;
+            } else if let Some(Some(entity)) = value.downcast_mut::<Option<Entity>>() {
+                visit(entity)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Finds the type name of the first map inside `value`, if any
+fn find_reflect_map(value: &dyn Reflect) -> Option<&str> {
+    match value.reflect_ref() {
+        ReflectRef::Struct(value) => {
+            (0..value.field_len()).find_map(|i| find_reflect_map(value.field_at(i).unwrap()))
+        }
+        ReflectRef::TupleStruct(value) => {
+            (0..value.field_len()).find_map(|i| find_reflect_map(value.field(i).unwrap()))
+        }
+        ReflectRef::Tuple(value) => {
+            (0..value.field_len()).find_map(|i| find_reflect_map(value.field(i).unwrap()))
+        }
+        ReflectRef::List(value) => {
+            (0..value.len()).find_map(|i| find_reflect_map(value.get(i).unwrap()))
+        }
+        ReflectRef::Map(value) => Some(value.type_name()),
+        ReflectRef::Value(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Reflect)]
+    struct Target {
+        entity: Entity,
+        optional: Option<Entity>,
+    }
+
+    #[derive(Reflect)]
+    struct Lamp {
+        target: Target,
+        wires: Vec<Entity>,
+        intensity: f32,
+    }
+
+    #[test]
+    fn nested_entities() {
+        let mut entity_map = EntityMap::default();
+        for i in 0..4 {
+            entity_map.insert(Entity::new(i), Entity::new(i + 10));
+        }
+
+        let mut lamp = Lamp {
+            target: Target {
+                entity: Entity::new(0),
+                optional: Some(Entity::new(1)),
+            },
+            wires: vec![Entity::new(2), Entity::new(3)],
+            intensity: 1.0,
+        };
+        map_reflect_entities(&mut lamp, &entity_map).unwrap();
+
+        assert_eq!(lamp.target.entity, Entity::new(10));
+        assert_eq!(lamp.target.optional, Some(Entity::new(11)));
+        assert_eq!(lamp.wires, vec![Entity::new(12), Entity::new(13)]);
+
+        // entities outside the map are errors, just like `MapEntities`
+        lamp.target.entity = Entity::new(100);
+        assert!(map_reflect_entities(&mut lamp, &entity_map).is_err());
+    }
+
+    #[derive(Default, Reflect)]
+    struct Wire {
+        from: Option<Entity>,
+        to: Option<Entity>,
+    }
+
+    #[derive(Clone)]
+    struct Link(Entity, Entity);
+
+    impl MapEntities for Link {
+        fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+            self.0 = entity_map.get(self.0)?;
+            self.1 = entity_map.get(self.1)?;
             Ok(())
-        });
+        }
+    }
+
+    #[test]
+    fn unmapped_entities_are_kept() {
+        let mut registry = ComponentEntityMapperRegistry::default();
+        registry.register::<Link>();
+        registry.register_reflect::<Wire>();
+
+        let mut world = World::default();
+        let outside = world.spawn().id();
+        let proxy = world.spawn().id();
+        let resolved = world.spawn().id();
+        let owner = world
+            .spawn()
+            .insert(Link(outside, proxy))
+            .insert(Wire {
+                from: Some(proxy),
+                to: Some(outside),
+            })
+            .id();
+
+        let mut entity_map = EntityMap::default();
+        entity_map.insert(proxy, resolved);
+        registry
+            .map_entity_components(&mut world.entity_mut(owner), &entity_map)
+            .unwrap();
+
+        let link = world.get::<Link>(owner).unwrap();
+        assert_eq!((link.0, link.1), (outside, resolved));
+        let wire = world.get::<Wire>(owner).unwrap();
+        assert_eq!((wire.from, wire.to), (Some(resolved), Some(outside)));
+    }
+
+    #[derive(Default, Reflect)]
+    struct Switchboard {
+        wires: Vec<Wire>,
+        ports: HashMap<usize, Entity>,
+    }
+
+    #[test]
+    fn maps_are_found() {
+        assert!(find_reflect_map(&Wire::default()).is_none());
+        assert!(find_reflect_map(&Switchboard::default()).is_some());
     }
 }
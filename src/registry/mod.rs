@@ -1,17 +1,23 @@
-use std::any::TypeId;
+use std::any::{type_name, TypeId};
 
-use bevy::{reflect::Uuid, utils::HashMap};
+use bevy::{
+    prelude::warn,
+    reflect::Uuid,
+    utils::{HashMap, HashSet},
+};
 use thiserror::Error;
 
 mod component;
 mod mapped;
 mod prefab;
+mod resource;
 
 ///////////////////////////////////////////////////////////////////////////////
 
 pub use component::*;
 pub use mapped::*;
 pub use prefab::*;
+pub use resource::*;
 
 #[derive(Error, Debug)]
 pub enum RegistryError {
@@ -21,23 +27,50 @@ pub enum RegistryError {
     TypeAlreadyRegistered(&'static str),
     #[error("uuid `{0}` already registered")]
     UuidAlreadyRegistered(Uuid),
+    #[error("type `{0}` isn't registered")]
+    TypeNotRegistered(&'static str),
+    #[error("alias `{0}` is reserved")]
+    ReservedAlias(String),
+    #[error("migration version `{0}` must be above the current version `{1}`")]
+    OutdatedMigration(u32, u32),
 }
 
+#[derive(Clone)]
 pub(crate) struct Registry<T> {
     reg: Vec<T>,
+    /// Canonical name of each entry
+    names: Vec<String>,
     by_name: HashMap<String, usize>,
+    /// Old names still accepted, also present in `by_name`
+    deprecated: HashSet<String>,
     by_type: HashMap<TypeId, usize>,
     by_uuid: HashMap<Uuid, usize>,
+    /// Names with a special meaning in prefab files that can't be registered
+    reserved: &'static [&'static str],
 }
 
 impl<T> Registry<T> {
     fn empty() -> Self {
+        Self::with_reserved(&[])
+    }
+
+    fn with_reserved(reserved: &'static [&'static str]) -> Self {
         Self {
             reg: Default::default(),
+            names: Default::default(),
             by_name: Default::default(),
+            deprecated: Default::default(),
             by_type: Default::default(),
             by_uuid: Default::default(),
+            reserved,
+        }
+    }
+
+    fn check_reserved(&self, alias: &str) -> Result<(), RegistryError> {
+        if self.reserved.contains(&alias) {
+            return Err(RegistryError::ReservedAlias(alias.to_string()));
         }
+        Ok(())
     }
 
     // TODO: Used to support prefabs uuid deserialization
@@ -45,22 +78,82 @@ impl<T> Registry<T> {
     //     self.by_uuid.get(uuid).and_then(|i| self.reg.get(*i))
     // }
 
+    /// Finds by canonical name or deprecated alias, the latter also logs a warning
     pub fn find_by_name(&self, name: &str) -> Option<&T> {
+        if let Some(canonical) = self.canonical_name(name) {
+            warn!("`{}` is deprecated, use `{}` instead", name, canonical);
+        }
         self.by_name.get(name).and_then(|i| self.reg.get(*i))
     }
 
+    /// Canonical name when `name` is a deprecated alias
+    pub fn canonical_name(&self, name: &str) -> Option<&str> {
+        if !self.deprecated.contains(name) {
+            return None;
+        }
+        self.by_name.get(name).map(|i| self.names[*i].as_str())
+    }
+
+    /// Adds an old `alias` that still resolves to the already registered type `R`
+    pub fn register_deprecated_alias<R: 'static>(
+        &mut self,
+        alias: String,
+    ) -> Result<(), RegistryError> {
+        use std::collections::hash_map::Entry::*;
+
+        self.check_reserved(&alias)?;
+        let i = *self
+            .by_type
+            .get(&TypeId::of::<R>())
+            .ok_or_else(|| RegistryError::TypeNotRegistered(type_name::<R>()))?;
+        match self.by_name.entry(alias) {
+            Occupied(alias) => Err(RegistryError::AliasAlreadyRegistered(
+                alias.key().to_string(),
+            )),
+            Vacant(alias) => {
+                self.deprecated.insert(alias.key().clone());
+                alias.insert(i);
+                Ok(())
+            }
+        }
+    }
+
     pub fn find_by_type(&self, type_id: TypeId) -> Option<&T> {
         self.by_type.get(&type_id).and_then(|i| self.reg.get(*i))
     }
 
+    pub fn find_by_type_mut(&mut self, type_id: TypeId) -> Option<&mut T> {
+        let i = *self.by_type.get(&type_id)?;
+        self.reg.get_mut(i)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.reg.iter()
+    }
+
+    /// Every entry along with it's canonical name and deprecated aliases
+    pub fn iter_named(&self) -> impl Iterator<Item = (&str, Vec<&str>, &T)> {
+        self.reg.iter().enumerate().map(move |(i, entry)| {
+            let mut deprecated: Vec<&str> = self
+                .deprecated
+                .iter()
+                .filter(|alias| self.by_name.get(alias.as_str()) == Some(&i))
+                .map(|alias| alias.as_str())
+                .collect();
+            deprecated.sort_unstable();
+            (self.names[i].as_str(), deprecated, entry)
+        })
+    }
+
     fn register_internal(
         &mut self,
         alias: String,
         type_info: (TypeId, Uuid, &'static str),
-        mut build: impl FnMut() -> T,
+        build: impl FnOnce() -> T,
     ) -> Result<usize, RegistryError> {
         use std::collections::hash_map::Entry::*;
 
+        self.check_reserved(&alias)?;
         let (type_id, type_uuid, type_name) = type_info;
         match (
             self.by_type.entry(type_id),
@@ -75,6 +168,7 @@ impl<T> Registry<T> {
             (Vacant(id), Vacant(alias), Vacant(uuid)) => {
                 let i = self.reg.len();
                 self.reg.push((build)());
+                self.names.push(alias.key().clone());
                 alias.insert(i);
                 id.insert(i);
                 uuid.insert(i);
@@ -82,6 +176,41 @@ impl<T> Registry<T> {
             }
         }
     }
+
+    /// Registers an entry without a rust type, like the prefab types declared in data
+    fn register_untyped(
+        &mut self,
+        alias: String,
+        uuid: Uuid,
+        build: impl FnOnce() -> T,
+    ) -> Result<usize, RegistryError> {
+        use std::collections::hash_map::Entry::*;
+
+        self.check_reserved(&alias)?;
+        match (self.by_name.entry(alias), self.by_uuid.entry(uuid)) {
+            (Occupied(alias), _) => Err(RegistryError::AliasAlreadyRegistered(
+                alias.key().to_string(),
+            ))?,
+            (_, Occupied(uuid)) => Err(RegistryError::UuidAlreadyRegistered(*uuid.key()))?,
+            (Vacant(alias), Vacant(uuid)) => {
+                let i = self.reg.len();
+                self.reg.push((build)());
+                self.names.push(alias.key().clone());
+                alias.insert(i);
+                uuid.insert(i);
+                Ok(i)
+            }
+        }
+    }
+
+    fn unique_uuid(&self) -> Uuid {
+        loop {
+            let uuid = Uuid::new_v4();
+            if !self.by_uuid.contains_key(&uuid) {
+                return uuid;
+            }
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
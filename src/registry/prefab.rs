@@ -1,40 +1,75 @@
-use std::any::{type_name, TypeId};
+use std::{
+    any::{type_name, TypeId},
+    sync::Arc,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bevy::{
     ecs::entity::EntityMap,
     prelude::{Entity, World},
     reflect::{Struct, TypeUuid, Uuid},
 };
-use serde::Deserialize;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    data::{BlankPrefab, OverrideDescriptor, OverrideRegistry},
+    data::{
+        BlankPrefab, DynamicPrefab, DynamicPrefabType, OverrideDescriptor, OverrideRegistry,
+        PrefabDataHelper,
+    },
     BoxedPrefabData, PrefabData,
 };
 
-use super::Registry;
+use super::{Registry, RegistryError};
+
+// functions are shared behind an `Arc` so prefab types declared in data can carry their layout along
 
 pub(crate) type PrefabDeserializerFn =
-    fn(&mut dyn erased_serde::Deserializer) -> Result<BoxedPrefabData>;
+    Arc<dyn Fn(&mut dyn erased_serde::Deserializer) -> Result<BoxedPrefabData> + Send + Sync>;
+
+pub(crate) type PrefabDefaultFn = Arc<dyn Fn() -> BoxedPrefabData + Send + Sync>;
 
-pub(crate) type PrefabDefaultFn = fn() -> BoxedPrefabData;
+pub(crate) type PrefabConstructFn =
+    Arc<dyn Fn(&mut World, Entity, &EntityMap) -> Result<()> + Send + Sync>;
 
-pub(crate) type PrefabConstructFn = fn(&mut World, Entity, &EntityMap) -> Result<()>;
+/// Upgrades the raw prefab data of an older prefab version in place
+pub type PrefabMigrationFn = fn(&mut ron::Value) -> Result<()>;
+
+pub(crate) type PrefabSerializerFn = fn(&BoxedPrefabData) -> Result<String>;
 
 #[derive(Clone)]
 pub struct PrefabDescriptor {
+    /// Prefab data type name
+    pub(crate) name: &'static str,
     pub(crate) source_prefab_required: bool,
     pub(crate) de: PrefabDeserializerFn,
     pub(crate) overrides: OverrideDescriptor,
     pub(crate) default: PrefabDefaultFn,
     pub(crate) construct: PrefabConstructFn,
     pub(crate) uuid: Uuid,
+    /// Sorted by version
+    pub(crate) migrations: Vec<(u32, PrefabMigrationFn)>,
+    /// Only available for prefabs with migrations
+    pub(crate) ser: Option<PrefabSerializerFn>,
+}
+
+impl PrefabDescriptor {
+    /// Migrations needed by data saved with the prefab `version`, in order
+    pub(crate) fn migrations_since(
+        &self,
+        version: u32,
+    ) -> impl Iterator<Item = PrefabMigrationFn> + '_ {
+        self.migrations
+            .iter()
+            .filter(move |(v, _)| *v > version)
+            .map(|(_, migrate)| *migrate)
+    }
 }
 
 /// Registry of all prefab types available
 ///
 /// **NOTE** The alias `"Prefab"` is registered by default, and uses [`()`] as their [`PrefabData`];
+#[derive(Clone)]
 pub(crate) struct PrefabDescriptorRegistry {
     pub overrides: OverrideRegistry,
     base: Registry<PrefabDescriptor>,
@@ -47,7 +82,7 @@ impl Default for PrefabDescriptorRegistry {
             base: Registry::<PrefabDescriptor>::empty(),
         };
         registry
-            .register_aliased::<BlankPrefab>("Prefab".to_string(), true)
+            .register_aliased::<BlankPrefab>("Prefab".to_string())
             .unwrap();
         registry
     }
@@ -59,36 +94,149 @@ impl PrefabDescriptorRegistry {
         self.base.find_by_name(name)
     }
 
-    // TODO: `source_prefab_required` should be configured statically in a trait not during registration
-    pub fn register_aliased<T>(&mut self, alias: String, source_prefab_required: bool) -> Result<()>
+    #[inline]
+    pub fn canonical_name(&self, name: &str) -> Option<&str> {
+        self.base.canonical_name(name)
+    }
+
+    #[inline]
+    pub fn iter_named(&self) -> impl Iterator<Item = (&str, Vec<&str>, &PrefabDescriptor)> {
+        self.base.iter_named()
+    }
+
+    #[inline]
+    pub fn register_deprecated_alias<T: 'static>(
+        &mut self,
+        alias: String,
+    ) -> Result<(), RegistryError> {
+        self.base.register_deprecated_alias::<T>(alias)
+    }
+
+    pub fn register_aliased<T>(&mut self, alias: String) -> Result<()>
     where
         T: PrefabData + TypeUuid + Default + Struct + Clone + for<'de> Deserialize<'de>,
     {
         let PrefabDescriptorRegistry { overrides, base } = self;
 
+        // invalid override rules are rejected before the prefab is registered
+        overrides.register_rules::<T>(T::override_rules());
+        overrides.register_struct::<T>()?;
+        let overrides = overrides.find::<T>().unwrap().clone();
+
         let type_info = (TypeId::of::<T>(), T::TYPE_UUID, type_name::<T>());
-        base.register_internal(alias, type_info, || {
-            overrides.register_struct::<T>();
-            PrefabDescriptor {
-                source_prefab_required,
-                de: |deserializer| {
-                    let value: T = Deserialize::deserialize(deserializer)?;
-                    Ok(BoxedPrefabData(Box::new(value)))
-                },
-                overrides: overrides.find::<T>().unwrap().clone(),
-                default: || BoxedPrefabData(Box::new(T::default())),
-                construct: |world, root, prefab_to_instance| {
-                    T::default().apply_overrides_and_construct_instance(
+        base.register_internal(alias, type_info, || PrefabDescriptor {
+            name: type_name::<T>(),
+            source_prefab_required: T::source_prefab_required(),
+            de: Arc::new(deserialize_data::<T>),
+            overrides,
+            default: Arc::new(|| BoxedPrefabData(Box::new(T::default()))),
+            construct: Arc::new(construct_default::<T>),
+            uuid: T::TYPE_UUID,
+            migrations: vec![],
+            ser: None,
+        })?;
+        Ok(())
+    }
+
+    /// Registers a prefab type declared in data, backed by [`crate::data::DynamicPrefab`]
+    pub fn register_dynamic(&mut self, prefab_type: DynamicPrefabType) -> Result<()> {
+        let PrefabDescriptorRegistry { overrides, base } = self;
+
+        // registered once per type for the whole application lifetime
+        let name: &'static str = Box::leak(prefab_type.name.clone().into_boxed_str());
+
+        let uuid = base.unique_uuid();
+        let layout = prefab_type.layout(name)?;
+        let overrides = overrides
+            .describe_dynamic_struct(layout.default_value(), &prefab_type.override_rules())?;
+
+        let default = DynamicPrefab::new(uuid, layout.default_value().clone_dynamic());
+        let construct_default = default.clone();
+        base.register_untyped(name.to_string(), uuid, || PrefabDescriptor {
+            name,
+            source_prefab_required: prefab_type.source_prefab_required,
+            de: Arc::new(move |deserializer: &mut dyn erased_serde::Deserializer| {
+                let data = layout.deserialize(deserializer)?;
+                Ok(BoxedPrefabData(Box::new(DynamicPrefab::new(uuid, data))))
+            }),
+            overrides,
+            default: Arc::new(move || BoxedPrefabData(Box::new(default.clone()))),
+            construct: Arc::new(
+                move |world: &mut World, root: Entity, prefab_to_instance: &EntityMap| {
+                    construct_default.apply_overrides_and_construct_instance(
                         world,
                         root,
                         prefab_to_instance,
                     )
                 },
-                uuid: T::TYPE_UUID,
-            }
+            ),
+            uuid,
+            migrations: vec![],
+            ser: None,
         })?;
         Ok(())
     }
+
+    /// Adds a migration for prefab data of `T` saved before `version`; Versions are shared by all
+    /// prefab types, so `version` must be above [`PrefabDescriptorRegistry::current_version`], files
+    /// already saved with the current version would skip it otherwise
+    pub fn register_migration<T>(
+        &mut self,
+        version: u32,
+        migrate: PrefabMigrationFn,
+    ) -> Result<(), RegistryError>
+    where
+        T: PrefabData + Serialize,
+    {
+        let current = self.current_version();
+        if version <= current {
+            return Err(RegistryError::OutdatedMigration(version, current));
+        }
+
+        let descriptor = self
+            .base
+            .find_by_type_mut(TypeId::of::<T>())
+            .ok_or_else(|| RegistryError::TypeNotRegistered(type_name::<T>()))?;
+
+        // always the latest version, so migrations stay sorted
+        descriptor.migrations.push((version, migrate));
+        descriptor.ser = Some(|data| {
+            let data = data
+                .0
+                .reflect_data()
+                .any()
+                .downcast_ref::<T>()
+                .ok_or_else(|| anyhow!("expected `{}` prefab data", type_name::<T>()))?;
+            Ok(ron::ser::to_string_pretty(data, PrettyConfig::new())?)
+        });
+        Ok(())
+    }
+
+    /// Latest prefab version, files saved without a `version` are version `0`
+    pub fn current_version(&self) -> u32 {
+        self.base
+            .iter()
+            .filter_map(|descriptor| descriptor.migrations.last())
+            .map(|(version, _)| *version)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn deserialize_data<T>(deserializer: &mut dyn erased_serde::Deserializer) -> Result<BoxedPrefabData>
+where
+    T: PrefabData + for<'de> Deserialize<'de>,
+{
+    let value: T = Deserialize::deserialize(deserializer)?;
+    Ok(BoxedPrefabData(Box::new(value)))
+}
+
+fn construct_default<T: PrefabData + Default>(
+    world: &mut World,
+    root: Entity,
+    prefab_to_instance: &EntityMap,
+) -> Result<()> {
+    T::default().apply_overrides_and_construct_instance(world, root, prefab_to_instance)
 }
 
 // pub(crate) fn prefab_construct<T: PrefabData + Default + Struct + Clone >(
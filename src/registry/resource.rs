@@ -0,0 +1,307 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    fmt,
+};
+
+use anyhow::Result;
+use bevy::{
+    ecs::{component::Component, world::World},
+    utils::HashMap,
+};
+use serde::Deserialize;
+
+use super::Registry;
+
+/// Resource value replaced by a prefab instance, kept to be restored later on
+pub(crate) type PreviousResource = Box<dyn Any + Send + Sync>;
+
+pub(crate) type ResourceDeserializerFn =
+    fn(&mut dyn erased_serde::Deserializer, &mut World) -> Result<()>;
+
+pub(crate) type ResourceInsertFn = fn(&World, &mut World) -> Option<PreviousResource>;
+
+pub(crate) type ResourceRestoreFn = fn(&mut World, Option<PreviousResource>);
+
+/// What happens to a resource declared by a prefab when the instance that inserted it
+/// is despawned with [`crate::command::PrefabCommands::despawn_prefab`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceDespawnPolicy {
+    /// Resource is left as is (default)
+    Keep,
+    /// Resource is removed
+    Remove,
+    /// Resource goes back to the value it had before the instance was spawned,
+    /// or is removed when there wasn't any
+    Restore,
+}
+
+impl Default for ResourceDespawnPolicy {
+    fn default() -> Self {
+        ResourceDespawnPolicy::Keep
+    }
+}
+
+#[derive(Clone)]
+pub struct ResourceDescriptor {
+    /// Resource type name
+    pub(crate) name: &'static str,
+    pub(crate) type_id: TypeId,
+    /// Inserts the resource in the prefab world
+    pub(crate) de: ResourceDeserializerFn,
+    /// Copies the resource out of the prefab world, returns the value it replaced
+    pub(crate) insert: ResourceInsertFn,
+    pub(crate) restore: ResourceRestoreFn,
+    pub(crate) despawn_policy: ResourceDespawnPolicy,
+}
+
+impl fmt::Debug for ResourceDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResourceDescriptor")
+            .field("name", &self.name)
+            .field("despawn_policy", &self.despawn_policy)
+            .finish()
+    }
+}
+
+/// Registry of all resources prefabs can declare
+pub(crate) type ResourceDescriptorRegistry = Registry<ResourceDescriptor>;
+
+impl Default for ResourceDescriptorRegistry {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl ResourceDescriptorRegistry {
+    pub fn register<T>(
+        &mut self,
+        alias: String,
+        despawn_policy: ResourceDespawnPolicy,
+    ) -> Result<()>
+    where
+        T: Component + Clone + for<'de> Deserialize<'de>,
+    {
+        let type_info = (TypeId::of::<T>(), self.unique_uuid(), type_name::<T>());
+        self.register_internal(alias, type_info, || ResourceDescriptor {
+            name: type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            de: |deserializer, world| {
+                let value: T = Deserialize::deserialize(deserializer)?;
+                world.insert_resource(value);
+                Ok(())
+            },
+            insert: insert::<T>,
+            restore: restore::<T>,
+            despawn_policy,
+        })?;
+        Ok(())
+    }
+}
+
+fn insert<T: Component + Clone>(
+    from_world: &World,
+    to_world: &mut World,
+) -> Option<PreviousResource> {
+    let value = from_world.get_resource::<T>().unwrap().clone();
+    let previous = to_world.remove_resource::<T>();
+    to_world.insert_resource(value);
+    previous.map(|previous| Box::new(previous) as PreviousResource)
+}
+
+fn restore<T: Component>(world: &mut World, previous: Option<PreviousResource>) {
+    match previous.and_then(|previous| previous.downcast::<T>().ok()) {
+        Some(previous) => world.insert_resource(*previous),
+        None => {
+            world.remove_resource::<T>();
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Instances that replaced each resource type and the value they replaced, the last one
+/// owns the current value; Kept in the world, so instances can be despawned in any order
+#[derive(Default)]
+struct PrefabResourceOwners {
+    generation: u64,
+    by_type: HashMap<TypeId, Vec<ResourceOwner>>,
+}
+
+struct ResourceOwner {
+    generation: u64,
+    /// Value restored once the owner is despawned, `None` removes the resource
+    previous: Option<PreviousResource>,
+}
+
+/// Resources inserted by a prefab instance that must be removed or restored once it's
+/// despawned, kept on the instance root
+pub(crate) struct PrefabInstanceResources(Vec<(TypeId, u64, ResourceRestoreFn)>);
+
+impl PrefabInstanceResources {
+    /// Inserts every resource of the prefab world in the instance world
+    pub fn insert(
+        resources: &[ResourceDescriptor],
+        prefab_world: &World,
+        world: &mut World,
+    ) -> Self {
+        let mut owners = world
+            .remove_resource::<PrefabResourceOwners>()
+            .unwrap_or_default();
+
+        let mut inserted = vec![];
+        for descriptor in resources {
+            let previous = (descriptor.insert)(prefab_world, world);
+            let stack = owners.by_type.entry(descriptor.type_id).or_default();
+            let previous = match descriptor.despawn_policy {
+                ResourceDespawnPolicy::Keep => {
+                    // the value is left for good, no one gets to restore the values it replaced
+                    stack.clear();
+                    continue;
+                }
+                ResourceDespawnPolicy::Remove => None,
+                ResourceDespawnPolicy::Restore => previous,
+            };
+
+            owners.generation += 1;
+            stack.push(ResourceOwner {
+                generation: owners.generation,
+                previous,
+            });
+            inserted.push((descriptor.type_id, owners.generation, descriptor.restore));
+        }
+
+        world.insert_resource(owners);
+        Self(inserted)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Applies each resource despawn policy, in the reverse order they were inserted; Resources
+    /// replaced by another instance since are left alone, that instance restores the previous value instead
+    pub fn despawn(self, world: &mut World) {
+        let mut owners = match world.remove_resource::<PrefabResourceOwners>() {
+            Some(owners) => owners,
+            None => return,
+        };
+
+        for (type_id, generation, restore) in self.0.into_iter().rev() {
+            let stack = match owners.by_type.get_mut(&type_id) {
+                Some(stack) => stack,
+                None => continue,
+            };
+            // missing when a kept value replaced it
+            let index = match stack
+                .iter()
+                .position(|owner| owner.generation == generation)
+            {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let owner = stack.remove(index);
+            if index == stack.len() {
+                (restore)(world, owner.previous);
+            } else {
+                // the instance that replaced this one's value gets to restore what was there before
+                stack[index].previous = owner.previous;
+            }
+        }
+
+        world.insert_resource(owners);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Clone)]
+    struct AmbientLight(f32);
+
+    #[derive(Debug, Deserialize, PartialEq, Clone)]
+    struct NavMesh(u32);
+
+    #[derive(Debug, Deserialize, PartialEq, Clone)]
+    struct Fog(f32);
+
+    #[test]
+    fn despawn_policies() {
+        let mut resource_registry = ResourceDescriptorRegistry::default();
+        resource_registry
+            .register::<AmbientLight>("AmbientLight".to_string(), ResourceDespawnPolicy::Restore)
+            .unwrap();
+        resource_registry
+            .register::<NavMesh>("NavMesh".to_string(), ResourceDespawnPolicy::Remove)
+            .unwrap();
+        resource_registry
+            .register::<Fog>("Fog".to_string(), ResourceDespawnPolicy::Keep)
+            .unwrap();
+        assert!(resource_registry
+            .register::<Fog>("Mist".to_string(), ResourceDespawnPolicy::Keep)
+            .is_err());
+
+        let mut prefab_world = World::default();
+        let mut resources = vec![];
+        for (name, source) in &[
+            ("AmbientLight", "(0.5)"),
+            ("NavMesh", "(7)"),
+            ("Fog", "(0.1)"),
+        ] {
+            let descriptor = resource_registry.find_by_name(name).unwrap();
+            let mut deserializer = ron::de::Deserializer::from_str(source).unwrap();
+            (descriptor.de)(
+                &mut <dyn erased_serde::Deserializer>::erase(&mut deserializer),
+                &mut prefab_world,
+            )
+            .unwrap();
+            resources.push(descriptor.clone());
+        }
+
+        let mut world = World::default();
+        world.insert_resource(AmbientLight(1.0));
+        world.insert_resource(NavMesh(1));
+
+        let inserted = PrefabInstanceResources::insert(&resources, &prefab_world, &mut world);
+        assert_eq!(
+            world.get_resource::<AmbientLight>(),
+            Some(&AmbientLight(0.5))
+        );
+        assert_eq!(world.get_resource::<NavMesh>(), Some(&NavMesh(7)));
+        assert_eq!(world.get_resource::<Fog>(), Some(&Fog(0.1)));
+
+        inserted.despawn(&mut world);
+        assert_eq!(
+            world.get_resource::<AmbientLight>(),
+            Some(&AmbientLight(1.0))
+        );
+        assert_eq!(world.get_resource::<NavMesh>(), None);
+        assert_eq!(world.get_resource::<Fog>(), Some(&Fog(0.1)));
+
+        // restoring a resource that wasn't there before removes it
+        world.remove_resource::<AmbientLight>();
+        let inserted = PrefabInstanceResources::insert(&resources[..1], &prefab_world, &mut world);
+        inserted.despawn(&mut world);
+        assert_eq!(world.get_resource::<AmbientLight>(), None);
+
+        // instances despawned out of order leave the value of the latest one
+        world.insert_resource(AmbientLight(1.0));
+        let mut other_prefab_world = World::default();
+        other_prefab_world.insert_resource(AmbientLight(0.25));
+        let first = PrefabInstanceResources::insert(&resources[..1], &prefab_world, &mut world);
+        let second =
+            PrefabInstanceResources::insert(&resources[..1], &other_prefab_world, &mut world);
+        first.despawn(&mut world);
+        assert_eq!(
+            world.get_resource::<AmbientLight>(),
+            Some(&AmbientLight(0.25))
+        );
+        second.despawn(&mut world);
+        assert_eq!(
+            world.get_resource::<AmbientLight>(),
+            Some(&AmbientLight(1.0))
+        );
+    }
+}
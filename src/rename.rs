@@ -0,0 +1,390 @@
+use std::{fs, io, path::Path};
+
+use bevy::ecs::world::World;
+
+use crate::de::PrefabDeserializer;
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Replaces every deprecated prefab or component name in the prefab `source` with it's current name,
+/// comments and formatting are kept as is; Returns `None` when there's nothing to rename
+pub fn rename_deprecated(world: &World, source: &str) -> Option<String> {
+    let inner = world.get_resource::<PrefabDeserializer>().unwrap().load();
+    rename_identifiers(source, |name| {
+        inner
+            .component_registry
+            .canonical_name(name)
+            .or_else(|| inner.prefab_registry.canonical_name(name))
+    })
+}
+
+/// Same as [`rename_deprecated`] but rewrites the prefab file in place, returns `true` if it was changed
+pub fn rename_deprecated_in_file(world: &World, path: impl AsRef<Path>) -> io::Result<bool> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    match rename_deprecated(world, &source) {
+        Some(renamed) => {
+            fs::write(path, renamed)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Renames the identifiers in name positions: prefab variants (root, `scene` instances and entity
+/// `overrides`), `components` entries and `Remove(..)` arguments; Any other identifier, like enum
+/// variants inside prefab data, is kept even when it matches a deprecated name
+fn rename_identifiers<'a>(
+    source: &str,
+    rename: impl Fn(&str) -> Option<&'a str>,
+) -> Option<String> {
+    let scanner = Scanner::scan(source);
+
+    let mut output = String::with_capacity(source.len());
+    let mut changed = false;
+    let mut copied = 0;
+    for (start, end) in scanner.names {
+        if let Some(canonical) = rename(&source[start..end]) {
+            output.push_str(&source[copied..start]);
+            output.push_str(canonical);
+            copied = end;
+            changed = true;
+        }
+    }
+
+    if changed {
+        output.push_str(&source[copied..]);
+        Some(output)
+    } else {
+        None
+    }
+}
+
+/// Names of the prefab instances in `source` with data `overrides`, including the ones of entity overrides
+pub(crate) fn overridden_prefabs(source: &str) -> Vec<&str> {
+    Scanner::scan(source)
+        .overridden
+        .into_iter()
+        .map(|(start, end)| &source[start..end])
+        .collect()
+}
+
+/// Walks the prefab file structure collecting the spans of prefab and component names
+struct Scanner<'s> {
+    bytes: &'s [u8],
+    i: usize,
+    names: Vec<(usize, usize)>,
+    /// Prefab names whose data is overridden
+    overridden: Vec<(usize, usize)>,
+}
+
+impl<'s> Scanner<'s> {
+    fn scan(source: &'s str) -> Self {
+        let mut scanner = Scanner {
+            bytes: source.as_bytes(),
+            i: 0,
+            names: vec![],
+            overridden: vec![],
+        };
+        scanner.attributes();
+        // a malformed file still gets the names found before the error
+        let _ = scanner.prefab();
+        scanner
+    }
+
+    /// Skips whitespace and comments, returns the next byte
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(&c) = self.bytes.get(self.i) {
+            if c.is_ascii_whitespace() {
+                self.i += 1;
+            } else if c == b'/' {
+                self.i = skip_non_code(self.bytes, self.i)?;
+            } else {
+                return Some(c);
+            }
+        }
+        None
+    }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        if self.peek()? == c {
+            self.i += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Consumes `c` when it's the next byte
+    fn accept(&mut self, c: u8) -> bool {
+        self.expect(c).is_some()
+    }
+
+    fn ident(&mut self) -> Option<(usize, usize)> {
+        let c = self.peek()?;
+        if c != b'_' && !c.is_ascii_alphabetic() {
+            return None;
+        }
+        let start = self.i;
+        while self
+            .bytes
+            .get(self.i)
+            .map_or(false, |c| *c == b'_' || c.is_ascii_alphanumeric())
+        {
+            self.i += 1;
+        }
+        Some((start, self.i))
+    }
+
+    fn name(&mut self) -> Option<(usize, usize)> {
+        let name = self.ident()?;
+        self.names.push(name);
+        Some(name)
+    }
+
+    /// Skips a value up to the next `,` or closing bracket of the enclosing value
+    fn skip_value(&mut self) -> Option<()> {
+        let mut depth = 0usize;
+        loop {
+            match self.peek()? {
+                b'"' | b'\'' => self.i = skip_non_code(self.bytes, self.i)?,
+                b'(' | b'[' | b'{' => {
+                    depth += 1;
+                    self.i += 1;
+                }
+                b')' | b']' | b'}' if depth == 0 => return Some(()),
+                b')' | b']' | b'}' => {
+                    depth -= 1;
+                    self.i += 1;
+                }
+                b',' if depth == 0 => return Some(()),
+                _ => self.i += 1,
+            }
+        }
+    }
+
+    /// `Name( .. )` of a prefab, or plain `Entity`
+    fn prefab(&mut self) -> Option<()> {
+        let name = self.name()?;
+        self.expect(b'(')?;
+        self.body(Some(name))
+    }
+
+    /// Fields of a prefab, instance or entity up to the closing `)`, entity overrides have no `name`
+    fn body(&mut self, name: Option<(usize, usize)>) -> Option<()> {
+        loop {
+            if self.accept(b')') {
+                return Some(());
+            }
+            let (start, end) = self.ident()?;
+            self.expect(b':')?;
+            match &self.bytes[start..end] {
+                b"scene" => self.list(Self::prefab)?,
+                b"components" => self.list(Self::component)?,
+                b"entities" => self.map(|scanner| {
+                    scanner.expect(b'(')?;
+                    scanner.body(None)
+                })?,
+                // instances override their own type unnamed
+                b"overrides" if name.is_some() => {
+                    self.overridden.extend(name);
+                    self.skip_value()?;
+                }
+                // named after the nested prefab type
+                b"overrides" => {
+                    let name = self.name()?;
+                    self.overridden.push(name);
+                    self.skip_value()?;
+                }
+                _ => self.skip_value()?,
+            }
+            if !self.accept(b',') {
+                return self.expect(b')');
+            }
+        }
+    }
+
+    /// `Component( .. )` or `Remove(Component, ..)`
+    fn component(&mut self) -> Option<()> {
+        let (start, end) = self.ident()?;
+        if &self.bytes[start..end] != b"Remove" {
+            self.names.push((start, end));
+            return self.skip_value();
+        }
+
+        self.expect(b'(')?;
+        self.name()?;
+        // entity path
+        loop {
+            if self.accept(b')') {
+                return Some(());
+            }
+            self.expect(b',')?;
+            self.skip_value()?;
+        }
+    }
+
+    fn list(&mut self, mut element: impl FnMut(&mut Self) -> Option<()>) -> Option<()> {
+        self.expect(b'[')?;
+        loop {
+            if self.accept(b']') {
+                return Some(());
+            }
+            element(self)?;
+            if !self.accept(b',') {
+                return self.expect(b']');
+            }
+        }
+    }
+
+    fn map(&mut self, mut value: impl FnMut(&mut Self) -> Option<()>) -> Option<()> {
+        self.expect(b'{')?;
+        loop {
+            if self.accept(b'}') {
+                return Some(());
+            }
+            self.key()?;
+            self.expect(b':')?;
+            value(self)?;
+            if !self.accept(b',') {
+                return self.expect(b'}');
+            }
+        }
+    }
+
+    /// Skips a map key, keys are entity path strings
+    fn key(&mut self) -> Option<()> {
+        match self.peek()? {
+            b'"' => {
+                self.i = skip_non_code(self.bytes, self.i)?;
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    /// Skips `#![enable(..)]` attributes at the start of the file
+    fn attributes(&mut self) {
+        while self.accept(b'#') {
+            self.accept(b'!');
+            if self.expect(b'[').is_none() {
+                return;
+            }
+            let mut depth = 1;
+            while depth > 0 {
+                match self.peek() {
+                    Some(b'[') => depth += 1,
+                    Some(b']') => depth -= 1,
+                    Some(_) => {}
+                    None => return,
+                }
+                self.i += 1;
+            }
+        }
+    }
+}
+
+/// Returns the end of the string, char literal or comment starting at `i`, if any
+pub(crate) fn skip_non_code(bytes: &[u8], mut i: usize) -> Option<usize> {
+    match bytes[i] {
+        quote @ b'"' | quote @ b'\'' => {
+            i += 1;
+            while i < bytes.len() && bytes[i] != quote {
+                if bytes[i] == b'\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            Some((i + 1).min(bytes.len()))
+        }
+        b'/' if bytes.get(i + 1) == Some(&b'/') => {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            Some(i)
+        }
+        b'/' if bytes.get(i + 1) == Some(&b'*') => {
+            i += 2;
+            while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                i += 1;
+            }
+            Some((i + 2).min(bytes.len()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rename_only_names() {
+        let source = r#"Prefab(
+    // Light(range: 1.0) in a comment
+    scene: [
+        OldLamp (
+            source: External("Light(.prefab"),
+            components: [
+                Light((range: 5.0)),
+                /* Light() */
+            ],
+        ),
+    ],
+    Light: 1,
+)"#;
+
+        let rename = |name: &str| match name {
+            "Light" => Some("PointLight"),
+            "OldLamp" => Some("LampPrefab"),
+            _ => None,
+        };
+
+        let renamed = rename_identifiers(source, rename).unwrap();
+        assert_eq!(
+            renamed,
+            source
+                .replace("OldLamp (", "LampPrefab (")
+                .replace("Light((", "PointLight((")
+        );
+
+        assert!(rename_identifiers(&renamed, rename).is_none());
+    }
+
+    #[test]
+    fn rename_skips_colliding_variants() {
+        // `Light` is also a variant of the `Source` enum used by the prefab data
+        let source = r#"#![enable(implicit_some)]
+OldLamp(
+    data: ( source: Light(2), fallback: [Light(1)] ),
+    scene: [
+        Entity( components: [ Light(( range: 5 )) ] ),
+        OldLamp(
+            overrides: ( source: Light(3) ),
+            components: [ Remove(Light, "Body/Glass"), Remove(Light) ],
+            entities: {
+                "Body": (
+                    overrides: OldLamp(( source: Light(4) )),
+                    components: [ Light(( range: 1 )) ],
+                ),
+            },
+        ),
+    ],
+)"#;
+
+        let rename = |name: &str| match name {
+            "Light" => Some("PointLight"),
+            "OldLamp" => Some("LampPrefab"),
+            _ => None,
+        };
+
+        let renamed = rename_identifiers(source, rename).unwrap();
+        assert_eq!(
+            renamed,
+            source
+                .replace("OldLamp", "LampPrefab")
+                .replace("Light((", "PointLight((")
+                .replace("Remove(Light", "Remove(PointLight")
+        );
+    }
+}
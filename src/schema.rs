@@ -0,0 +1,406 @@
+use std::collections::BTreeMap;
+
+use bevy::ecs::world::World;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    data::FieldConstraint,
+    registry::{ComponentDescriptorRegistry, PrefabDescriptorRegistry},
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Everything that can be written in a prefab file, taken from the registries
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefabSchema {
+    /// Current prefab version
+    pub version: u32,
+    pub prefabs: Vec<PrefabTypeSchema>,
+    pub components: Vec<ComponentSchema>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefabTypeSchema {
+    pub name: String,
+    pub deprecated_aliases: Vec<String>,
+    pub type_name: String,
+    pub source_prefab_required: bool,
+    /// Prefab data fields that can be overridden by prefab instances
+    pub data: FieldSchema,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentSchema {
+    pub name: String,
+    pub deprecated_aliases: Vec<String>,
+    pub type_name: String,
+    /// Fields that can be patched by prefab instances, only for patchable components
+    pub overrides: Option<FieldSchema>,
+}
+
+/// Shape of an overridable value
+#[derive(Debug, Clone, Serialize)]
+pub enum FieldSchema {
+    Value {
+        type_name: String,
+        constraints: Vec<FieldConstraint>,
+    },
+    Struct {
+        type_name: String,
+        fields: BTreeMap<String, FieldSchema>,
+    },
+    List {
+        type_name: String,
+        element: Box<FieldSchema>,
+    },
+}
+
+impl PrefabSchema {
+    /// Schema of the prefabs and components registered in the `world` so far
+    pub fn from_world(world: &World) -> Self {
+        Self::from_registries(
+            world.get_resource::<ComponentDescriptorRegistry>().unwrap(),
+            world.get_resource::<PrefabDescriptorRegistry>().unwrap(),
+        )
+    }
+
+    fn from_registries(
+        component_registry: &ComponentDescriptorRegistry,
+        prefab_registry: &PrefabDescriptorRegistry,
+    ) -> Self {
+        let names =
+            |deprecated: Vec<&str>| deprecated.iter().map(|name| name.to_string()).collect();
+
+        let mut prefabs: Vec<_> = prefab_registry
+            .iter_named()
+            .map(|(name, deprecated, descriptor)| PrefabTypeSchema {
+                name: name.to_string(),
+                deprecated_aliases: names(deprecated),
+                type_name: descriptor.name.to_string(),
+                source_prefab_required: descriptor.source_prefab_required,
+                data: descriptor.overrides.schema(),
+            })
+            .collect();
+        prefabs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut components: Vec<_> = component_registry
+            .iter_named()
+            .filter(|(_, _, descriptor)| !descriptor.hidden)
+            .map(|(name, deprecated, descriptor)| ComponentSchema {
+                name: name.to_string(),
+                deprecated_aliases: names(deprecated),
+                type_name: descriptor.name.to_string(),
+                overrides: descriptor
+                    .patch
+                    .as_ref()
+                    .map(|patch| patch.overrides.schema()),
+            })
+            .collect();
+        components.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            version: prefab_registry.current_version(),
+            prefabs,
+            components,
+        }
+    }
+
+    /// JSON Schema (draft 7) of prefab files, using serde's representation of `Name( .. )` as `{ "Name": { .. } }`
+    pub fn to_json_schema(&self) -> Value {
+        let mut prefabs = Map::new();
+        let mut instances = Map::new();
+        for prefab in &self.prefabs {
+            let data = prefab.data.to_json_schema();
+            let body = json!({
+                "type": "object",
+                "properties": {
+                    "version": { "type": "integer", "minimum": 0, "maximum": self.version },
+                    "id": { "type": "integer" },
+                    "transform": { "type": "object" },
+                    "data": data,
+                    "components": { "$ref": "#/definitions/components" },
+                    "scene": { "type": "array", "items": { "$ref": "#/definitions/instance" } },
+                },
+                "additionalProperties": false,
+            });
+            let instance = json!({
+                "type": "object",
+                "properties": {
+                    "id": { "type": "integer" },
+                    "source": {},
+                    "parent": { "type": "string" },
+                    "transform": { "type": "object" },
+                    "overrides": data,
+                    "components": { "$ref": "#/definitions/components" },
+                    "entities": {
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/definitions/entity_override" },
+                    },
+                },
+                "additionalProperties": false,
+            });
+
+            let aliases = prefab.deprecated_aliases.iter().map(|alias| (alias, true));
+            for (name, deprecated) in Some((&prefab.name, false)).into_iter().chain(aliases) {
+                prefabs.insert(name.clone(), variant(name, deprecated, &body));
+                instances.insert(name.clone(), variant(name, deprecated, &instance));
+            }
+        }
+
+        let mut components = Map::new();
+        for component in &self.components {
+            let value = match &component.overrides {
+                Some(overrides) => overrides.to_json_schema(),
+                None => json!({ "description": component.type_name }),
+            };
+
+            let aliases = component
+                .deprecated_aliases
+                .iter()
+                .map(|alias| (alias, true));
+            for (name, deprecated) in Some((&component.name, false)).into_iter().chain(aliases) {
+                components.insert(name.clone(), variant(name, deprecated, &value));
+            }
+        }
+
+        let refs = |group: &str, variants: &Map<String, Value>| -> Vec<Value> {
+            variants
+                .keys()
+                .map(|name| json!({ "$ref": format!("#/definitions/{}/{}", group, name) }))
+                .collect()
+        };
+
+        let mut instance_variants = refs("instances", &instances);
+        instance_variants.push(variant(
+            "Entity",
+            false,
+            &json!({
+                "type": "object",
+                "properties": {
+                    "id": { "type": "integer" },
+                    "components": { "$ref": "#/definitions/components" },
+                },
+                "additionalProperties": false,
+            }),
+        ));
+
+        json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": "Prefab",
+            "oneOf": refs("prefabs", &prefabs),
+            "definitions": {
+                "prefabs": prefabs,
+                "instances": instances,
+                "instance": { "oneOf": instance_variants },
+                "entity_override": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "integer" },
+                        "parent": { "type": "string" },
+                        "overrides": {},
+                        "components": { "$ref": "#/definitions/components" },
+                    },
+                    "additionalProperties": false,
+                },
+                "component": { "oneOf": refs("component_variants", &components) },
+                "component_variants": components,
+                "components": {
+                    "type": "array",
+                    "items": {
+                        "oneOf": [
+                            { "$ref": "#/definitions/component" },
+                            // `Remove(Component)` or `Remove(Component, "Path/To/Entity")`
+                            variant("Remove", false, &json!({
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "minItems": 1,
+                                "maxItems": 2,
+                            })),
+                        ],
+                    },
+                },
+            },
+        })
+    }
+}
+
+impl FieldSchema {
+    pub fn type_name(&self) -> &str {
+        match self {
+            FieldSchema::Value { type_name, .. }
+            | FieldSchema::Struct { type_name, .. }
+            | FieldSchema::List { type_name, .. } => type_name,
+        }
+    }
+
+    /// Struct fields may also be written as paths like `{ "inner.field": .. }` and
+    /// lists overridden by index like `{ "2": .. }`, so they aren't closed
+    pub fn to_json_schema(&self) -> Value {
+        let mut schema = match self {
+            FieldSchema::Value {
+                type_name,
+                constraints,
+            } => {
+                let mut schema = match type_name.as_str() {
+                    "bool" => json!({ "type": "boolean" }),
+                    "u8" | "u16" | "u32" | "u64" | "Entity" => {
+                        json!({ "type": "integer", "minimum": 0 })
+                    }
+                    "i8" | "i16" | "i32" | "i64" => json!({ "type": "integer" }),
+                    "f32" | "f64" => json!({ "type": "number" }),
+                    _ => json!({}),
+                };
+                for constraint in constraints {
+                    match constraint {
+                        FieldConstraint::Range { min, max } => {
+                            schema["minimum"] = json!(min);
+                            schema["maximum"] = json!(max);
+                        }
+                        // values that can't be written are left out of the schema
+                        FieldConstraint::OneOf(values) => {
+                            if let Ok(values) = serde_json::to_value(values) {
+                                schema["enum"] = values;
+                            }
+                        }
+                        FieldConstraint::NonEmptyHandle => {
+                            schema["not"] = json!({ "type": "null" })
+                        }
+                    }
+                }
+                schema
+            }
+            FieldSchema::Struct { fields, .. } => {
+                let properties: Map<String, Value> = fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), field.to_json_schema()))
+                    .collect();
+                json!({ "type": "object", "properties": properties })
+            }
+            FieldSchema::List { element, .. } => {
+                let element = element.to_json_schema();
+                json!({
+                    "type": ["array", "object"],
+                    "items": element,
+                    "additionalProperties": element,
+                })
+            }
+        };
+        schema["description"] = json!(self.type_name());
+        schema
+    }
+}
+
+/// Externally tagged enum variant `{ "name": value }`
+fn variant(name: &str, deprecated: bool, value: &Value) -> Value {
+    let mut properties = Map::new();
+    properties.insert(name.to_string(), value.clone());
+    let mut schema = json!({
+        "type": "object",
+        "properties": properties,
+        "required": [name],
+        "additionalProperties": false,
+    });
+    if deprecated {
+        schema["deprecated"] = json!(true);
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::entity::Entity,
+        reflect::{Reflect, TypeUuid},
+    };
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{
+        data::{OverrideRules, PrefabData},
+        registry::ComponentMergePolicy,
+    };
+
+    #[derive(Default, Debug, Deserialize, Clone, TypeUuid, Reflect)]
+    #[uuid = "e4f0bb59-2cb4-41b0-a0a4-5d0c8d26ee1b"]
+    struct Lamp {
+        intensity: f32,
+        cycles: Vec<f32>,
+    }
+
+    impl PrefabData for Lamp {
+        fn construct(&self, _: &mut World, _: Entity) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn override_rules() -> OverrideRules {
+            OverrideRules::default().constrain("intensity", FieldConstraint::range(0.0, 10.0))
+        }
+    }
+
+    #[derive(Default, Debug, Deserialize, Clone, Reflect)]
+    struct Glow {
+        radius: f32,
+    }
+
+    #[test]
+    fn registered_types() {
+        let mut prefab_registry = PrefabDescriptorRegistry::default();
+        prefab_registry
+            .register_aliased::<Lamp>("Lamp".to_string())
+            .unwrap();
+        prefab_registry
+            .register_deprecated_alias::<Lamp>("OldLamp".to_string())
+            .unwrap();
+
+        let mut component_registry = ComponentDescriptorRegistry::default();
+        component_registry
+            .register_with_merge_policy::<Glow>(
+                "Glow".to_string(),
+                ComponentMergePolicy::Reflect,
+                &mut prefab_registry.overrides,
+            )
+            .unwrap();
+        component_registry
+            .register_prefab_data::<Lamp>("Lamp".to_string())
+            .unwrap();
+
+        let schema = PrefabSchema::from_registries(&component_registry, &prefab_registry);
+
+        let names: Vec<_> = schema.prefabs.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Lamp", "Prefab"]);
+        assert_eq!(schema.prefabs[0].deprecated_aliases, vec!["OldLamp"]);
+
+        // prefab data components are hidden
+        assert_eq!(schema.components.len(), 1);
+        assert_eq!(schema.components[0].name, "Glow");
+
+        match &schema.prefabs[0].data {
+            FieldSchema::Struct { fields, .. } => {
+                assert_eq!(fields["cycles"].type_name(), "Vec<f32>");
+                match &fields["intensity"] {
+                    FieldSchema::Value { constraints, .. } => {
+                        assert_eq!(constraints, &vec![FieldConstraint::range(0.0, 10.0)])
+                    }
+                    _ => panic!("expected a value"),
+                }
+            }
+            _ => panic!("expected a struct"),
+        }
+
+        let json = schema.to_json_schema();
+        let intensity = &json["definitions"]["prefabs"]["Lamp"]["properties"]["Lamp"]["properties"]
+            ["data"]["properties"]["intensity"];
+        assert_eq!(intensity["type"], "number");
+        assert_eq!(intensity["maximum"], 10.0);
+        assert_eq!(
+            json["definitions"]["prefabs"]["OldLamp"]["deprecated"],
+            true
+        );
+        assert_eq!(
+            json["definitions"]["component_variants"]["Glow"]["properties"]["Glow"]["properties"]
+                ["radius"]["type"],
+            "number"
+        );
+    }
+}
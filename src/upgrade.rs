@@ -0,0 +1,478 @@
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use bevy::{asset::AssetServer, ecs::world::World};
+use serde::de::DeserializeSeed;
+
+use crate::{
+    de::{PrefabDataDeserializer, PrefabDeserializer},
+    registry::PrefabDescriptorRegistry,
+    rename::{overridden_prefabs, rename_deprecated, skip_non_code},
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Brings the prefab `source` to the current prefab version, deprecated names are renamed,
+/// the root prefab data runs through it's migrations and the `version` field is updated;
+/// Returns `None` when the source is already up to date
+///
+/// **NOTE** Only the root prefab data is migrated, sources with instances overriding the data of
+/// a prefab that has pending migrations are rejected, those must be updated by hand (they fail
+/// to load as well)
+pub fn upgrade_prefab(world: &World, source: &str) -> Result<Option<String>> {
+    let inner = world.get_resource::<PrefabDeserializer>().unwrap().load();
+    let renamed = rename_deprecated(world, source);
+    let source = renamed.as_deref().unwrap_or(source);
+
+    let migrated = match world.get_resource::<AssetServer>() {
+        Some(asset_server) => asset_server
+            .with_asset_refs_serialization(|| migrate_prefab(&inner.prefab_registry, source)),
+        None => migrate_prefab(&inner.prefab_registry, source),
+    }?;
+    Ok(migrated.or(renamed))
+}
+
+/// Upgrades the prefab files in place, see [`upgrade_prefab`]; Returns the files that changed,
+/// stops at the first file that can't be upgraded
+pub fn upgrade_prefab_files<P: AsRef<Path>>(
+    world: &World,
+    files: impl IntoIterator<Item = P>,
+) -> Result<Vec<PathBuf>> {
+    let mut changed = vec![];
+    for file in files {
+        let file = file.as_ref();
+        let source = fs::read_to_string(file)?;
+        let upgraded = upgrade_prefab(world, &source)
+            .map_err(|err| anyhow!("`{}` couldn't be upgraded: {}", file.display(), err))?;
+        if let Some(upgraded) = upgraded {
+            fs::write(file, upgraded)?;
+            changed.push(file.to_path_buf());
+        }
+    }
+    Ok(changed)
+}
+
+/// Upgrades every `.prefab` file inside `folder` and it's sub folders, see [`upgrade_prefab_files`]
+pub fn upgrade_prefab_folder(world: &World, folder: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut folders = vec![folder.as_ref().to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in fs::read_dir(folder)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                folders.push(path);
+            } else if path.extension().map_or(false, |ext| ext == "prefab") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    upgrade_prefab_files(world, files)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+fn migrate_prefab(
+    prefab_registry: &PrefabDescriptorRegistry,
+    source: &str,
+) -> Result<Option<String>> {
+    let current = prefab_registry.current_version();
+    let root = PrefabRoot::parse(source)?;
+
+    let version = match &root.version {
+        Some(span) => source[span.clone()].parse::<u32>()?,
+        None => 0,
+    };
+    if version >= current {
+        return Ok(None);
+    }
+
+    let name = &source[root.name.clone()];
+    let descriptor = prefab_registry
+        .find_by_name(name)
+        .ok_or_else(|| anyhow!("unknown prefab `{}`", name))?;
+
+    // overrides only have a few fields, migrations expect the whole data
+    for name in overridden_prefabs(source) {
+        let pending = prefab_registry
+            .find_by_name(name)
+            .map_or(false, |descriptor| {
+                descriptor.migrations_since(version).next().is_some()
+            });
+        if pending {
+            bail!(
+                "overrides of `{}` instances can't be migrated from version `{}`, update them and the `version` by hand",
+                name,
+                version
+            );
+        }
+    }
+
+    // spans are replaced from the back so the ones before stay valid
+    let mut output = source.to_string();
+    if let Some(data) = root.data {
+        if let Some(ser) = descriptor.ser {
+            if descriptor.migrations_since(version).next().is_some() {
+                let mut deserializer = ron::de::Deserializer::from_str(&source[data.clone()])?;
+                let value = PrefabDataDeserializer {
+                    descriptor,
+                    version,
+                }
+                .deserialize(&mut deserializer)?;
+
+                let indent = format!("\n{}", line_indent(source, data.start));
+                output.replace_range(data, &ser(&value)?.replace('\n', &indent));
+            }
+        }
+    }
+
+    match root.version {
+        Some(span) => output.replace_range(span, &current.to_string()),
+        None => {
+            let first = skip_trivia(source.as_bytes(), root.body);
+            if source[root.body..first].contains('\n') {
+                let field = format!("\n{}version: {},", line_indent(source, first), current);
+                output.insert_str(root.body, &field);
+            } else {
+                output.insert_str(first, &format!("version: {}, ", current));
+            }
+        }
+    }
+
+    Ok(Some(output))
+}
+
+/// Spans of the root prefab name and the `version`, `data` and `scene` values
+struct PrefabRoot {
+    name: Range<usize>,
+    /// Right after the opening `(`
+    body: usize,
+    version: Option<Range<usize>>,
+    data: Option<Range<usize>>,
+    scene: Option<Range<usize>>,
+}
+
+impl PrefabRoot {
+    fn parse(source: &str) -> Result<Self> {
+        let bytes = source.as_bytes();
+
+        let name = identifier(bytes, skip_trivia(bytes, 0));
+        let mut i = skip_trivia(bytes, name.end);
+        if name.is_empty() || bytes.get(i) != Some(&b'(') {
+            bail!("expected a prefab");
+        }
+
+        let mut root = PrefabRoot {
+            name,
+            body: i + 1,
+            version: None,
+            data: None,
+            scene: None,
+        };
+
+        i += 1;
+        loop {
+            i = skip_trivia(bytes, i);
+            if bytes.get(i) == Some(&b')') {
+                break;
+            }
+
+            let field = identifier(bytes, i);
+            i = skip_trivia(bytes, field.end);
+            if field.is_empty() || bytes.get(i) != Some(&b':') {
+                bail!("expected a prefab field at {}", i);
+            }
+
+            let start = skip_trivia(bytes, i + 1);
+            let end = value_end(bytes, start);
+            match &source[field] {
+                "version" => root.version = Some(start..end),
+                "data" => root.data = Some(start..end),
+                "scene" => root.scene = Some(start..end),
+                _ => {}
+            }
+
+            i = skip_trivia(bytes, end);
+            match bytes.get(i) {
+                Some(b',') => i += 1,
+                Some(b')') => {}
+                _ => bail!("expected `,` or `)` at {}", i),
+            }
+        }
+
+        if let Some(version) = &root.version {
+            let after = |span: &Option<Range<usize>>| {
+                span.as_ref()
+                    .map_or(false, |span| version.start > span.start)
+            };
+            if after(&root.data) || after(&root.scene) {
+                bail!("`version` must come before `data` and `scene`");
+            }
+        }
+
+        Ok(root)
+    }
+}
+
+fn identifier(bytes: &[u8], start: usize) -> Range<usize> {
+    let mut end = start;
+    while end < bytes.len()
+        && (bytes[end] == b'_' || bytes[end].is_ascii_alphanumeric())
+        && !(end == start && bytes[end].is_ascii_digit())
+    {
+        end += 1;
+    }
+    start..end
+}
+
+/// Skips whitespaces and comments
+fn skip_trivia(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        if bytes[i].is_ascii_whitespace() {
+            i += 1;
+        } else if let (b'/', Some(end)) = (bytes[i], skip_non_code(bytes, i)) {
+            i = end;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+/// End of the value starting at `i`, trailing whitespaces and comments aren't included
+fn value_end(bytes: &[u8], mut i: usize) -> usize {
+    let mut depth = 0usize;
+    let mut end = i;
+    while i < bytes.len() {
+        if let Some(next) = skip_non_code(bytes, i) {
+            if bytes[i] != b'/' {
+                end = next;
+            }
+            i = next;
+            continue;
+        }
+
+        match bytes[i] {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' | b',' if depth == 0 => break,
+            b')' | b']' | b'}' => depth -= 1,
+            _ => {}
+        }
+
+        i += 1;
+        if !bytes[i - 1].is_ascii_whitespace() {
+            end = i;
+        }
+    }
+    end
+}
+
+fn line_indent(source: &str, at: usize) -> &str {
+    let line = &source[source[..at].rfind('\n').map_or(0, |i| i + 1)..];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::entity::Entity,
+        reflect::{Reflect, TypeUuid},
+    };
+    use ron::{Number, Value};
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{registry::RegistryError, PrefabData};
+
+    #[derive(Default, Debug, Serialize, Deserialize, Clone, TypeUuid, Reflect)]
+    #[uuid = "1c9ae6a4-5d43-4c4d-8d36-86f0b1f6bd55"]
+    struct Lamp {
+        intensity: f32,
+    }
+
+    impl PrefabData for Lamp {
+        fn construct(&self, _: &mut World, _: Entity) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default, Debug, Serialize, Deserialize, Clone, TypeUuid, Reflect)]
+    #[uuid = "0b4f3e57-3a8e-4a52-9d3c-5b8e1f1f7a02"]
+    struct Door {
+        open: bool,
+    }
+
+    impl PrefabData for Door {
+        fn construct(&self, _: &mut World, _: Entity) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn rename_opened(data: &mut Value) -> Result<()> {
+        if let Value::Map(map) = data {
+            if let Some(opened) = map.remove(&Value::String("opened".to_string())) {
+                map.insert(Value::String("open".to_string()), opened);
+            }
+        }
+        Ok(())
+    }
+
+    fn rename_strength(data: &mut Value) -> Result<()> {
+        if let Value::Map(map) = data {
+            if let Some(strength) = map.remove(&Value::String("strength".to_string())) {
+                map.insert(Value::String("intensity".to_string()), strength);
+            }
+        }
+        Ok(())
+    }
+
+    fn intensity_in_candelas(data: &mut Value) -> Result<()> {
+        if let Value::Map(map) = data {
+            if let Some(Value::Number(intensity)) =
+                map.remove(&Value::String("intensity".to_string()))
+            {
+                let intensity = Value::Number(Number::new(intensity.into_f64() * 100.0));
+                map.insert(Value::String("intensity".to_string()), intensity);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn migrations() {
+        let mut prefab_registry = PrefabDescriptorRegistry::default();
+        prefab_registry
+            .register_aliased::<Lamp>("Lamp".to_string())
+            .unwrap();
+        prefab_registry
+            .register_migration::<Lamp>(1, rename_strength)
+            .unwrap();
+        prefab_registry
+            .register_migration::<Lamp>(2, intensity_in_candelas)
+            .unwrap();
+        assert_eq!(prefab_registry.current_version(), 2);
+
+        // files saved with the current version would never run it
+        assert!(prefab_registry
+            .register_migration::<Lamp>(2, rename_strength)
+            .is_err());
+
+        // loading
+        let descriptor = prefab_registry.find_by_name("Lamp").unwrap();
+        let mut deserializer = ron::de::Deserializer::from_str("(strength: 2.5)").unwrap();
+        let data = PrefabDataDeserializer {
+            descriptor,
+            version: 0,
+        }
+        .deserialize(&mut deserializer)
+        .unwrap();
+        let lamp = data.0.reflect_data().any().downcast_ref::<Lamp>().unwrap();
+        assert_eq!(lamp.intensity, 250.0);
+
+        // upgrading
+        let source = r#"Lamp(
+    // keep me
+    data: (
+        strength: 2.5,
+    ),
+    scene: [],
+)"#;
+        let upgraded = migrate_prefab(&prefab_registry, source).unwrap().unwrap();
+        assert_eq!(
+            upgraded,
+            r#"Lamp(
+    version: 2,
+    // keep me
+    data: (
+        intensity: 250,
+    ),
+    scene: [],
+)"#
+        );
+        assert!(migrate_prefab(&prefab_registry, &upgraded)
+            .unwrap()
+            .is_none());
+
+        let source = "Lamp(version: 1, data: (intensity: 0.5))";
+        assert_eq!(
+            migrate_prefab(&prefab_registry, source).unwrap().unwrap(),
+            "Lamp(version: 2, data: (\n    intensity: 50,\n))"
+        );
+
+        let source = "Lamp(data: (intensity: 0.5), version: 1)";
+        assert!(migrate_prefab(&prefab_registry, source).is_err());
+
+        // instances overrides can't be migrated
+        let source = r#"Lamp(
+    version: 1,
+    scene: [
+        Lamp( source: External("lamp.prefab"), overrides: ( intensity: 0.5 ) ),
+    ],
+)"#;
+        assert!(migrate_prefab(&prefab_registry, source).is_err());
+        assert!(migrate_prefab(
+            &prefab_registry,
+            &source.replace("version: 1", "version: 2")
+        )
+        .unwrap()
+        .is_none());
+    }
+
+    #[test]
+    fn migrations_of_two_plugins() {
+        // a lamp plugin followed by a door plugin
+        let mut prefab_registry = PrefabDescriptorRegistry::default();
+        prefab_registry
+            .register_aliased::<Lamp>("Lamp".to_string())
+            .unwrap();
+        prefab_registry
+            .register_aliased::<Door>("Door".to_string())
+            .unwrap();
+        prefab_registry
+            .register_migration::<Lamp>(1, rename_strength)
+            .unwrap();
+        prefab_registry
+            .register_migration::<Door>(2, rename_opened)
+            .unwrap();
+        assert_eq!(prefab_registry.current_version(), 2);
+
+        // doors saved with the lamp version still get migrated
+        let source = "Door(version: 1, data: (opened: true))";
+        assert_eq!(
+            migrate_prefab(&prefab_registry, source).unwrap().unwrap(),
+            "Door(version: 2, data: (\n    open: true,\n))"
+        );
+        // lamps only get the version bump
+        let source = "Lamp(version: 1, data: (intensity: 0.5))";
+        assert_eq!(
+            migrate_prefab(&prefab_registry, source).unwrap().unwrap(),
+            "Lamp(version: 2, data: (intensity: 0.5))"
+        );
+
+        // the lamp plugin can't go back under the door version
+        assert!(matches!(
+            prefab_registry.register_migration::<Lamp>(2, intensity_in_candelas),
+            Err(RegistryError::OutdatedMigration(2, 2))
+        ));
+
+        // the plugins added the other way around
+        let mut prefab_registry = PrefabDescriptorRegistry::default();
+        prefab_registry
+            .register_aliased::<Lamp>("Lamp".to_string())
+            .unwrap();
+        prefab_registry
+            .register_aliased::<Door>("Door".to_string())
+            .unwrap();
+        prefab_registry
+            .register_migration::<Door>(2, rename_opened)
+            .unwrap();
+        assert!(matches!(
+            prefab_registry.register_migration::<Lamp>(1, rename_strength),
+            Err(RegistryError::OutdatedMigration(1, 2))
+        ));
+    }
+}